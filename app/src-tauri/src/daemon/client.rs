@@ -7,8 +7,7 @@ use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::{json, Value};
 use tauri::{AppHandle, Emitter};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
 use tokio::net::TcpStream;
 use tokio::sync::{oneshot, Mutex, RwLock};
 use tokio::time::timeout;
@@ -17,6 +16,7 @@ use super::claudecode_adapter::ClaudeCodeAdapter;
 use super::config::DaemonConfig;
 use super::opencode_adapter::OpenCodeAdapter;
 use super::protocol::*;
+use super::tls;
 use crate::agent_state::{AgentError, AgentEvent, ErrorSource, StreamEvent as StateMachineStreamEvent};
 use crate::emit_stream_event;
 use crate::sessions::{
@@ -44,12 +44,18 @@ const RECONNECT_DELAYS: &[Duration] = &[
 
 type PendingRequests = HashMap<u64, oneshot::Sender<Result<Value, String>>>;
 
+/// Read/write halves of the daemon connection (plain TCP or TLS)
+type DaemonReader = Box<dyn AsyncRead + Send + Unpin>;
+type DaemonWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
 /// Client connection to the remote daemon
 pub struct DaemonClient {
-    writer: Mutex<BufWriter<OwnedWriteHalf>>,
+    writer: Mutex<BufWriter<DaemonWriter>>,
     pending: Arc<Mutex<PendingRequests>>,
     next_id: AtomicU64,
     connected: Arc<RwLock<bool>>,
+    /// Fingerprint of the daemon certificate (TLS connections only)
+    tls_fingerprint: Option<String>,
 }
 
 /// Shared daemon state for Tauri
@@ -143,6 +149,30 @@ impl DaemonState {
                 return Err(error);
             }
         };
+
+        // Trust on first use: pin the certificate seen on the first TLS connect
+        if config.tls && config.tls_fingerprint.is_none() {
+            if let Some(fingerprint) = client.tls_fingerprint.clone() {
+                let pinned = DaemonConfig {
+                    tls_fingerprint: Some(fingerprint.clone()),
+                    ..config
+                };
+                if let Err(error) = pinned.save(&app_handle).await {
+                    emit_debug(
+                        Some(&app_handle),
+                        "tls:pin_save_failed",
+                        Some(json!({ "error": error })),
+                    );
+                }
+                emit_debug(
+                    Some(&app_handle),
+                    "tls:pinned",
+                    Some(json!({ "fingerprint": fingerprint })),
+                );
+                self.set_config(Some(pinned)).await;
+            }
+        }
+
         let client = Arc::new(client);
 
         *self.client.write().await = Some(client);
@@ -204,7 +234,17 @@ impl DaemonClient {
             .map_err(|_| "daemon_connection_failed: timeout")?
             .map_err(|e| format!("daemon_connection_failed: {e}"))?;
 
-        let (reader, writer) = stream.into_split();
+        let (reader, writer, tls_fingerprint): (DaemonReader, DaemonWriter, Option<String>) =
+            if config.tls {
+                let (stream, fingerprint) =
+                    tls::connect(stream, &config.host, config.tls_fingerprint.as_deref()).await?;
+                let (reader, writer) = tokio::io::split(stream);
+                (Box::new(reader), Box::new(writer), Some(fingerprint))
+            } else {
+                let (reader, writer) = stream.into_split();
+                (Box::new(reader), Box::new(writer), None)
+            };
+
         let reader = BufReader::new(reader);
         let writer = Mutex::new(BufWriter::new(writer));
         let pending: Arc<Mutex<PendingRequests>> = Arc::new(Mutex::new(HashMap::new()));
@@ -215,6 +255,7 @@ impl DaemonClient {
             pending: pending.clone(),
             next_id: AtomicU64::new(1),
             connected: connected.clone(),
+            tls_fingerprint,
         };
 
         // Start reader task
@@ -235,7 +276,7 @@ impl DaemonClient {
     }
 
     fn spawn_reader(
-        mut reader: BufReader<OwnedReadHalf>,
+        mut reader: BufReader<DaemonReader>,
         pending: Arc<Mutex<PendingRequests>>,
         connected: Arc<RwLock<bool>>,
         app_handle: Option<AppHandle>,
@@ -696,6 +737,8 @@ mod tests {
                 host: "127.0.0.1".to_string(),
                 port: addr.port(),
                 token: "secret".to_string(),
+                tls: false,
+                tls_fingerprint: None,
             };
 
             let client = DaemonClient::connect_without_app(&config)
@@ -731,6 +774,8 @@ mod tests {
                 host: "127.0.0.1".to_string(),
                 port: addr.port(),
                 token: "secret".to_string(),
+                tls: false,
+                tls_fingerprint: None,
             };
 
             let client = DaemonClient::connect_without_app(&config)
@@ -773,6 +818,8 @@ mod tests {
                 host: "127.0.0.1".to_string(),
                 port: addr.port(),
                 token: "secret".to_string(),
+                tls: false,
+                tls_fingerprint: None,
             };
 
             let client = DaemonClient::connect_without_app(&config)
@@ -790,9 +837,8 @@ mod tests {
 
             assert_eq!(diff.files.len(), 1);
             assert_eq!(diff.files[0].path, "src/lib.rs");
-            assert!(diff.files[0].diff.contains("+fn test()"));
-            assert!(!diff.truncated);
-            assert!(diff.truncated_files.is_empty());
+            assert_eq!(diff.files[0].hunks, 1);
+            assert!(!diff.files[0].staged);
         });
     }
 
@@ -815,6 +861,8 @@ mod tests {
                 host: "127.0.0.1".to_string(),
                 port: addr.port(),
                 token: "secret".to_string(),
+                tls: false,
+                tls_fingerprint: None,
             };
 
             let client = DaemonClient::connect_without_app(&config)
@@ -943,11 +991,17 @@ mod tests {
         let diff_response = json!({
             "id": 2,
             "result": {
-                "files": [
-                    {"path": "src/lib.rs", "diff": "@@ -1 +1\n+fn test()"}
-                ],
-                "truncated": false,
-                "truncated_files": []
+                "files": [{
+                    "path": "src/lib.rs",
+                    "status": "modified",
+                    "staged": false,
+                    "binary": false,
+                    "size": 64,
+                    "hunks": 1,
+                    "additions": 1,
+                    "deletions": 0,
+                    "hash": "0123456789abcdef"
+                }]
             }
         })
         .to_string();
@@ -54,6 +54,8 @@ pub async fn daemon_configure(
     host: String,
     port: u16,
     token: String,
+    tls: Option<bool>,
+    tls_fingerprint: Option<String>,
     state: State<'_, Arc<DaemonState>>,
     app: tauri::AppHandle,
 ) -> Result<(), String> {
@@ -61,7 +63,13 @@ pub async fn daemon_configure(
     state.disconnect().await;
 
     // Save new config
-    let config = DaemonConfig { host, port, token };
+    let config = DaemonConfig {
+        host,
+        port,
+        token,
+        tls: tls.unwrap_or(false),
+        tls_fingerprint: tls_fingerprint.filter(|f| !f.trim().is_empty()),
+    };
     config.save(&app).await?;
     state.set_config(Some(config)).await;
 
@@ -185,10 +193,42 @@ pub async fn git_status(
 #[tauri::command]
 pub async fn git_diff(
     session_id: String,
+    options: Option<GitDiffOptions>,
     state: State<'_, Arc<DaemonState>>,
 ) -> Result<GitDiffResult, String> {
     state
-        .call(METHOD_GIT_DIFF, Some(SessionIdParams { session_id }))
+        .call(
+            METHOD_GIT_DIFF,
+            Some(GitDiffParams {
+                session_id,
+                options: options.unwrap_or_default(),
+            }),
+        )
+        .await
+}
+
+#[tauri::command]
+pub async fn git_diff_file(
+    session_id: String,
+    path: String,
+    staged: bool,
+    hunk_offset: Option<usize>,
+    hunk_limit: Option<usize>,
+    options: Option<GitDiffOptions>,
+    state: State<'_, Arc<DaemonState>>,
+) -> Result<GitDiffFileResult, String> {
+    state
+        .call(
+            METHOD_GIT_DIFF_FILE,
+            Some(GitDiffFileParams {
+                session_id,
+                path,
+                staged,
+                hunk_offset: hunk_offset.unwrap_or(0),
+                hunk_limit,
+                options: options.unwrap_or_default(),
+            }),
+        )
         .await
 }
 
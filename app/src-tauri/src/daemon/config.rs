@@ -9,6 +9,12 @@ pub struct DaemonConfig {
     pub host: String,
     pub port: u16,
     pub token: String,
+    /// Connect over TLS
+    #[serde(default)]
+    pub tls: bool,
+    /// Pinned SHA-256 fingerprint of the daemon certificate (recorded on first TLS connect)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_fingerprint: Option<String>,
 }
 
 impl DaemonConfig {
@@ -4,6 +4,7 @@ mod commands;
 mod config;
 pub mod opencode_adapter;
 mod protocol;
+mod tls;
 
 pub use commands::*;
 pub use config::DaemonConfig;
@@ -54,6 +54,7 @@ pub const METHOD_TERMINAL_RESIZE: &str = "terminal_resize";
 pub const METHOD_TERMINAL_CLOSE: &str = "terminal_close";
 pub const METHOD_GIT_STATUS: &str = "git_status";
 pub const METHOD_GIT_DIFF: &str = "git_diff";
+pub const METHOD_GIT_DIFF_FILE: &str = "git_diff_file";
 pub const METHOD_GIT_LOG: &str = "git_log";
 
 // OpenCode method names
@@ -129,6 +130,47 @@ pub struct GitLogParams {
     pub limit: Option<u32>,
 }
 
+/// Diff settings shared by git_diff and git_diff_file (camelCase from the frontend)
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all(deserialize = "camelCase"))]
+pub struct GitDiffOptions {
+    /// Ref or range ("main...HEAD") to compare from instead of HEAD/the index
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    /// Ref to compare to instead of the working tree
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_lines: Option<u32>,
+    /// "show", "ignore_all", "ignore_change" or "ignore_at_eol"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub whitespace: Option<String>,
+    /// Rename similarity percent; 0 disables rename detection
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rename_threshold: Option<u8>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub word_diff: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GitDiffParams {
+    pub session_id: String,
+    #[serde(flatten)]
+    pub options: GitDiffOptions,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GitDiffFileParams {
+    pub session_id: String,
+    pub path: String,
+    pub staged: bool,
+    pub hunk_offset: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hunk_limit: Option<usize>,
+    #[serde(flatten)]
+    pub options: GitDiffOptions,
+}
+
 // --- OpenCode request params ---
 
 #[derive(Debug, Serialize)]
@@ -266,18 +308,49 @@ pub struct GitStatusResult {
     pub total_deletions: i32,
 }
 
+/// Changed file listed by git_diff; its diff is fetched with git_diff_file
 #[derive(Debug, Deserialize, Serialize)]
-pub struct GitFileDiff {
+#[serde(rename_all = "camelCase")]
+pub struct GitDiffFileInfo {
     pub path: String,
-    pub diff: String,
+    #[serde(default, alias = "orig_path", skip_serializing_if = "Option::is_none")]
+    pub orig_path: Option<String>,
+    pub status: String,
+    pub staged: bool,
+    #[serde(default)]
+    pub binary: bool,
+    #[serde(default)]
+    pub size: usize,
+    #[serde(default)]
+    pub hunks: usize,
+    #[serde(default)]
+    pub additions: i32,
+    #[serde(default)]
+    pub deletions: i32,
+    #[serde(default)]
+    pub hash: String,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct GitDiffResult {
-    pub files: Vec<GitFileDiff>,
-    pub truncated: bool,
+    pub files: Vec<GitDiffFileInfo>,
+}
+
+/// One page of a file's diff (header plus a range of hunks)
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitDiffFileResult {
+    pub path: String,
+    pub staged: bool,
     #[serde(default)]
-    pub truncated_files: Vec<String>,
+    pub hash: String,
+    pub diff: String,
+    #[serde(default, alias = "total_hunks")]
+    pub total_hunks: usize,
+    #[serde(default, alias = "hunk_offset")]
+    pub hunk_offset: usize,
+    #[serde(default, alias = "next_hunk_offset")]
+    pub next_hunk_offset: Option<usize>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -339,7 +412,7 @@ pub const EVENT_CLAUDECODE: &str = "claudecode:event";
 #[cfg(test)]
 mod tests {
     use super::{
-        GitDiffResult, IncomingMessage, Request, Response, METHOD_GIT_STATUS, EVENT_TERMINAL_OUTPUT,
+        GitDiffFileResult, GitDiffOptions, GitDiffParams, IncomingMessage, Request, Response, METHOD_GIT_STATUS, EVENT_TERMINAL_OUTPUT,
     };
     use serde_json::json;
 
@@ -400,10 +473,32 @@ mod tests {
     }
 
     #[test]
-    fn git_diff_result_defaults_truncated_files() {
-        let result: GitDiffResult =
-            serde_json::from_str(r#"{"files":[],"truncated":false}"#)
-                .expect("diff result to parse");
-        assert!(result.truncated_files.is_empty());
+    fn git_diff_options_flatten_into_snake_case_params() {
+        let options: GitDiffOptions =
+            serde_json::from_str(r#"{"base":"main...HEAD","contextLines":1,"wordDiff":true}"#)
+                .expect("options to parse");
+        let params = GitDiffParams {
+            session_id: "/tmp/project".to_string(),
+            options,
+        };
+        assert_eq!(
+            serde_json::to_value(params).expect("params to serialize"),
+            json!({
+                "session_id": "/tmp/project",
+                "base": "main...HEAD",
+                "context_lines": 1,
+                "word_diff": true
+            })
+        );
+    }
+
+    #[test]
+    fn git_diff_file_result_accepts_snake_case() {
+        let result: GitDiffFileResult = serde_json::from_str(
+            r#"{"path":"a.rs","staged":false,"hash":"00","diff":"","total_hunks":4,"hunk_offset":0,"next_hunk_offset":3}"#,
+        )
+        .expect("diff file result to parse");
+        assert_eq!(result.total_hunks, 4);
+        assert_eq!(result.next_hunk_offset, Some(3));
     }
 }
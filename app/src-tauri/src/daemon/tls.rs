@@ -0,0 +1,146 @@
+//! TLS transport for the daemon connection
+//!
+//! The daemon typically serves a self-signed certificate, so instead of CA validation the
+//! client pins the server certificate by its SHA-256 fingerprint (trust on first use when
+//! no fingerprint is configured yet).
+
+use std::sync::{Arc, Mutex};
+
+use sha2::{Digest, Sha256};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{ClientConfig, DigitallySignedStruct, Error as TlsError, SignatureScheme};
+use tokio_rustls::TlsConnector;
+
+/// Perform the TLS handshake over an established TCP stream.
+///
+/// Returns the TLS stream and the server certificate fingerprint. Fails with
+/// `daemon_tls_fingerprint_mismatch` when `pinned` is set and does not match.
+pub async fn connect(
+    stream: TcpStream,
+    host: &str,
+    pinned: Option<&str>,
+) -> Result<(TlsStream<TcpStream>, String), String> {
+    let provider = Arc::new(ring::default_provider());
+    let seen = Arc::new(Mutex::new(None));
+    let verifier = PinnedCertVerifier {
+        pinned: pinned.map(normalize_fingerprint),
+        seen: seen.clone(),
+        provider: provider.clone(),
+    };
+
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("daemon_tls_failed: {e}"))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+
+    let server_name = ServerName::try_from(host.to_string())
+        .map_err(|e| format!("daemon_tls_failed: invalid host {host}: {e}"))?;
+
+    let result = TlsConnector::from(Arc::new(config))
+        .connect(server_name, stream)
+        .await;
+    let fingerprint = seen.lock().unwrap().clone();
+
+    match (result, fingerprint) {
+        (Ok(stream), Some(fingerprint)) => Ok((stream, fingerprint)),
+        (Ok(_), None) => Err("daemon_tls_failed: no server certificate".to_string()),
+        (Err(_), Some(fingerprint))
+            if pinned.is_some_and(|p| normalize_fingerprint(p) != normalize_fingerprint(&fingerprint)) =>
+        {
+            Err(format!("daemon_tls_fingerprint_mismatch: {fingerprint}"))
+        }
+        (Err(e), _) => Err(format!("daemon_tls_failed: {e}")),
+    }
+}
+
+/// SHA-256 fingerprint of a DER certificate, as colon-separated uppercase hex
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Normalize user-entered fingerprints (case, separators) for comparison
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .chars()
+        .filter(|c| c.is_ascii_hexdigit())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+#[derive(Debug)]
+struct PinnedCertVerifier {
+    pinned: Option<String>,
+    seen: Arc<Mutex<Option<String>>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, TlsError> {
+        let actual = fingerprint(end_entity);
+        let matches = self
+            .pinned
+            .as_ref()
+            .is_none_or(|pinned| *pinned == normalize_fingerprint(&actual));
+        *self.seen.lock().unwrap() = Some(actual);
+
+        if matches {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(TlsError::General("certificate fingerprint mismatch".to_string()))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, TlsError> {
+        verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, TlsError> {
+        verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_fingerprint;
+
+    #[test]
+    fn normalize_fingerprint_ignores_case_and_separators() {
+        assert_eq!(normalize_fingerprint("ab:cd:0F"), "ABCD0F");
+        assert_eq!(normalize_fingerprint("ABCD0f"), "ABCD0F");
+    }
+}
@@ -113,6 +113,7 @@ pub fn run() {
             // Git commands (proxied to daemon)
             daemon::git_status,
             daemon::git_diff,
+            daemon::git_diff_file,
             daemon::git_log,
             // OpenCode commands (proxied to daemon)
             daemon::opencode_connect_workspace,
@@ -3,6 +3,8 @@ import type {
   AgentHarness,
   AgentSession,
   DaemonStatus,
+  GitDiffFileResult,
+  GitDiffOptions,
   GitDiffResult,
   GitFileDiff,
   GitLogResult,
@@ -53,8 +55,16 @@ export async function daemonConfigure(
   host: string,
   port: number,
   token: string,
+  tls?: boolean,
+  tlsFingerprint?: string,
 ): Promise<void> {
-  return invokeCommand("daemon_configure", { host, port, token });
+  return invokeCommand("daemon_configure", {
+    host,
+    port,
+    token,
+    tls,
+    tlsFingerprint,
+  });
 }
 
 /** Connect to configured daemon */
@@ -151,9 +161,31 @@ export async function gitStatus(sessionId: string): Promise<GitStatusResult> {
   return invokeCommand<GitStatusResult>("git_status", { sessionId });
 }
 
-/** Retrieve git diffs for a session workspace */
-export async function gitDiff(sessionId: string): Promise<GitDiffResult> {
-  return invokeCommand<GitDiffResult>("git_diff", { sessionId });
+/** List changed files (metadata only) for a session workspace */
+export async function gitDiff(
+  sessionId: string,
+  options?: GitDiffOptions,
+): Promise<GitDiffResult> {
+  return invokeCommand<GitDiffResult>("git_diff", { sessionId, options });
+}
+
+/** Retrieve one file's diff, optionally a range of its hunks */
+export async function gitDiffFile(
+  sessionId: string,
+  path: string,
+  staged = false,
+  hunkOffset = 0,
+  hunkLimit?: number,
+  options?: GitDiffOptions,
+): Promise<GitDiffFileResult> {
+  return invokeCommand<GitDiffFileResult>("git_diff_file", {
+    sessionId,
+    path,
+    staged,
+    hunkOffset,
+    hunkLimit,
+    options,
+  });
 }
 
 /** Retrieve git log entries for a session workspace */
@@ -171,10 +203,29 @@ export async function getGitStatus(sessionId: string): Promise<GitStatusResult>
   return gitStatus(sessionId);
 }
 
-/** @deprecated Use gitDiff instead */
+/** @deprecated Use gitDiff and gitDiffFile instead */
 export async function getGitDiffs(sessionId: string): Promise<GitFileDiff[]> {
   const result = await gitDiff(sessionId);
-  return result.files;
+  // One diff per path, preferring the staged side
+  const files = result.files.filter(
+    (file, index, all) =>
+      all.findIndex((other) => other.path === file.path) === index,
+  );
+  return Promise.all(
+    files.map(async (file) => {
+      // Fetch every page; later pages repeat the file header before their hunks
+      let page = await gitDiffFile(sessionId, file.path, file.staged);
+      let diff = page.diff;
+      while (page.nextHunkOffset !== null && page.nextHunkOffset > page.hunkOffset) {
+        page = await gitDiffFile(sessionId, file.path, file.staged, page.nextHunkOffset);
+        const firstHunk = page.diff.search(/^@@/m);
+        if (firstHunk !== -1) {
+          diff += page.diff.slice(firstHunk);
+        }
+      }
+      return { path: file.path, diff };
+    }),
+  );
 }
 
 /** @deprecated Use gitLog instead */
@@ -34,6 +34,11 @@ const PARAM_KEY_MAP: Record<string, string> = {
   terminalId: "terminal_id",
   workspaceId: "workspace_id",
   workspacePath: "workspace_path",
+  hunkOffset: "hunk_offset",
+  hunkLimit: "hunk_limit",
+  contextLines: "context_lines",
+  renameThreshold: "rename_threshold",
+  wordDiff: "word_diff",
 };
 
 const eventTarget = new EventTarget();
@@ -99,6 +104,11 @@ function normalizeParams(
 
   const normalized: Record<string, unknown> = {};
   for (const [key, value] of Object.entries(params)) {
+    // Option objects (e.g. git diff options) are flattened into the daemon params
+    if (key === "options" && value && typeof value === "object") {
+      Object.assign(normalized, normalizeParams(value as Record<string, unknown>));
+      continue;
+    }
     const mapped = PARAM_KEY_MAP[key] ?? key;
     normalized[mapped] = value;
   }
@@ -129,7 +139,17 @@ function normalizeResult(command: string, result: unknown): unknown {
     case "git_diff":
       return {
         ...data,
-        truncatedFiles: data.truncatedFiles ?? data.truncated_files ?? [],
+        files: ((data.files ?? []) as Record<string, unknown>[]).map((file) => ({
+          ...file,
+          origPath: file.origPath ?? file.orig_path,
+        })),
+      };
+    case "git_diff_file":
+      return {
+        ...data,
+        totalHunks: data.totalHunks ?? data.total_hunks ?? 0,
+        hunkOffset: data.hunkOffset ?? data.hunk_offset ?? 0,
+        nextHunkOffset: data.nextHunkOffset ?? data.next_hunk_offset ?? null,
       };
     case "terminal_open":
       return {
@@ -70,6 +70,20 @@ export type GitFileDiff = {
   diff: string;
 };
 
+/** Changed file listed by git_diff; fetch its diff with git_diff_file */
+export type GitDiffFileInfo = {
+  path: string;
+  origPath?: string;
+  status: string;
+  staged: boolean;
+  binary: boolean;
+  size: number;
+  hunks: number;
+  additions: number;
+  deletions: number;
+  hash: string;
+};
+
 export type GitLogEntry = {
   sha: string;
   summary: string;
@@ -86,11 +100,34 @@ export type GitStatusResult = {
   totalDeletions: number;
 };
 
+/** Diff settings shared by git_diff and git_diff_file */
+export type GitDiffOptions = {
+  /** Ref or range ("main...HEAD") to compare from instead of HEAD/the index */
+  base?: string;
+  /** Ref to compare to instead of the working tree */
+  target?: string;
+  contextLines?: number;
+  whitespace?: "show" | "ignore_all" | "ignore_change" | "ignore_at_eol";
+  /** Rename similarity percent; 0 disables rename detection */
+  renameThreshold?: number;
+  /** Word-level diff in git's --word-diff=porcelain format */
+  wordDiff?: boolean;
+};
+
 /** Result from git_diff command */
 export type GitDiffResult = {
-  files: GitFileDiff[];
-  truncated: boolean;
-  truncatedFiles: string[];
+  files: GitDiffFileInfo[];
+};
+
+/** Result from git_diff_file command: the file header plus a range of hunks */
+export type GitDiffFileResult = {
+  path: string;
+  staged: boolean;
+  hash: string;
+  diff: string;
+  totalHunks: number;
+  hunkOffset: number;
+  nextHunkOffset: number | null;
 };
 
 /** Result from git_log command */
@@ -0,0 +1,178 @@
+//! API token scopes and per-method access control
+//!
+//! Each token has a scope (read_only < operator < admin) and an optional allowlist of
+//! session paths. `handlers::dispatch` checks every request against the client's principal.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::*;
+
+/// Token scope. Each scope includes everything the lower scopes allow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Session listing, git inspection, agent status and history
+    ReadOnly,
+    /// Adds agent control: connect, prompt, abort, permission replies, settings
+    Operator,
+    /// Adds terminals and session management
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::ReadOnly => "read_only",
+            Scope::Operator => "operator",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+/// Named API token (config.json `tokens` entry)
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiToken {
+    pub name: String,
+    pub token: String,
+    pub scope: Scope,
+    /// Session paths this token may access (None = all sessions)
+    #[serde(default)]
+    pub sessions: Option<Vec<String>>,
+}
+
+impl ApiToken {
+    /// Unrestricted token (used for --token)
+    pub fn admin(name: &str, token: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            token: token.to_string(),
+            scope: Scope::Admin,
+            sessions: None,
+        }
+    }
+}
+
+/// Authenticated identity of a client connection
+#[derive(Debug, Clone)]
+pub struct Principal {
+    /// Token name (or a description of how the client was trusted)
+    pub name: String,
+    pub scope: Scope,
+    /// Session paths this client may access (None = all sessions)
+    pub sessions: Option<Vec<String>>,
+}
+
+impl Principal {
+    /// Full access, for connections that need no token (auth disabled, same-user Unix peer)
+    pub fn unrestricted(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            scope: Scope::Admin,
+            sessions: None,
+        }
+    }
+
+    pub fn from_token(token: &ApiToken) -> Self {
+        Self {
+            name: token.name.clone(),
+            scope: token.scope,
+            sessions: token.sessions.clone(),
+        }
+    }
+
+    /// Whether this principal may access the session at `path`
+    pub fn allows_session(&self, path: &str) -> bool {
+        match &self.sessions {
+            None => true,
+            Some(allowed) => allowed.iter().any(|s| Path::new(s) == Path::new(path)),
+        }
+    }
+}
+
+/// Minimum scope needed to call a method. Unknown methods require admin.
+pub fn required_scope(method: &str) -> Scope {
+    match method {
+        METHOD_AUTH
+        | METHOD_LIST_SESSIONS
+        | METHOD_SESSION_INFO
+        | METHOD_GIT_STATUS
+        | METHOD_GIT_DIFF
+        | METHOD_GIT_DIFF_FILE
+        | METHOD_GIT_LOG
+        | METHOD_GIT_SHOW
+        | METHOD_GIT_BRANCHES
+        | METHOD_GIT_CONFLICTS
+        | METHOD_OPENCODE_STATUS
+        | METHOD_OPENCODE_SESSION_LIST
+        | METHOD_OPENCODE_SESSION_MESSAGES
+        | METHOD_CLAUDE_SDK_STATUS
+        | METHOD_CLAUDE_SDK_SESSION_LIST
+        | METHOD_CLAUDE_SDK_SESSION_MESSAGES
+        | METHOD_CLAUDE_SDK_MODELS
+        | METHOD_CLAUDE_SDK_PERMISSION_PENDING => Scope::ReadOnly,
+
+        METHOD_GIT_STAGE
+        | METHOD_GIT_UNSTAGE
+        | METHOD_GIT_STAGE_HUNKS
+        | METHOD_GIT_UNSTAGE_HUNKS
+        | METHOD_GIT_COMMIT
+        | METHOD_GIT_BRANCH_CREATE
+        | METHOD_GIT_CHECKOUT
+        | METHOD_GIT_FETCH
+        | METHOD_GIT_PULL
+        | METHOD_GIT_PUSH
+        | METHOD_GIT_RESOLVE
+        | METHOD_GIT_REBASE_CONTINUE
+        | METHOD_OPENCODE_CONNECT_WORKSPACE
+        | METHOD_OPENCODE_DISCONNECT_WORKSPACE
+        | METHOD_OPENCODE_SESSION_CREATE
+        | METHOD_OPENCODE_SESSION_PROMPT
+        | METHOD_OPENCODE_SESSION_ABORT
+        | METHOD_CLAUDE_SDK_CONNECT_WORKSPACE
+        | METHOD_CLAUDE_SDK_DISCONNECT_WORKSPACE
+        | METHOD_CLAUDE_SDK_SESSION_CREATE
+        | METHOD_CLAUDE_SDK_SESSION_PROMPT
+        | METHOD_CLAUDE_SDK_SESSION_ABORT
+        | METHOD_CLAUDE_SDK_PERMISSION_REPLY
+        | METHOD_CLAUDE_SDK_SESSION_SETTINGS_UPDATE => Scope::Operator,
+
+        _ => Scope::Admin,
+    }
+}
+
+/// Whether a method addresses agent workspaces (`workspace_id`/`workspace_path`)
+/// rather than daemon sessions (`session_id` = session path)
+pub fn is_workspace_method(method: &str) -> bool {
+    method.starts_with("opencode_") || method.starts_with("claude_sdk_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{required_scope, Principal, Scope};
+    use crate::protocol::*;
+
+    #[test]
+    fn required_scope_orders_methods() {
+        assert_eq!(required_scope(METHOD_GIT_DIFF), Scope::ReadOnly);
+        assert_eq!(required_scope(METHOD_CLAUDE_SDK_SESSION_PROMPT), Scope::Operator);
+        assert_eq!(required_scope(METHOD_TERMINAL_OPEN), Scope::Admin);
+        assert_eq!(required_scope(METHOD_GIT_COMMIT), Scope::Operator);
+        assert_eq!(required_scope(METHOD_GIT_DISCARD), Scope::Admin);
+        assert_eq!(required_scope("not_a_method"), Scope::Admin);
+        assert!(Scope::Operator > Scope::ReadOnly && Scope::Admin > Scope::Operator);
+    }
+
+    #[test]
+    fn principal_session_allowlist() {
+        let principal = Principal {
+            name: "ci".to_string(),
+            scope: Scope::ReadOnly,
+            sessions: Some(vec!["/srv/app/".to_string()]),
+        };
+        assert!(principal.allows_session("/srv/app"));
+        assert!(!principal.allows_session("/srv/other"));
+        assert!(Principal::unrestricted("local").allows_session("/anything"));
+    }
+}
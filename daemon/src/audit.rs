@@ -0,0 +1,320 @@
+//! RPC audit log
+//!
+//! `handlers::dispatch` records every mutating request (terminal input, prompts,
+//! permission replies, settings updates, session and git mutations) as one JSON line in
+//! `audit.log` in the data directory. The log rotates to `audit.log.1..N` by size.
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::auth::open_append;
+use crate::protocol::*;
+
+/// Rotate once the current log reaches this size
+const MAX_LOG_BYTES: u64 = 10 * 1024 * 1024;
+/// Rotated files kept (audit.log.1 is the most recent)
+const ROTATED_FILES: usize = 3;
+
+pub const DEFAULT_QUERY_LIMIT: usize = 100;
+pub const MAX_QUERY_LIMIT: usize = 1000;
+
+/// Whether a method is recorded in the audit log
+pub fn is_audited(method: &str) -> bool {
+    matches!(
+        method,
+        METHOD_SESSION_ADD
+            | METHOD_SESSION_REMOVE
+            | METHOD_SESSION_RENAME
+            | METHOD_TERMINAL_OPEN
+            | METHOD_TERMINAL_WRITE
+            | METHOD_TERMINAL_CLOSE
+            | METHOD_TERMINAL_ATTACH
+            | METHOD_TERMINAL_SUBSCRIBE
+            | METHOD_EXEC
+            | METHOD_EXEC_CANCEL
+            | METHOD_GIT_STAGE
+            | METHOD_GIT_UNSTAGE
+            | METHOD_GIT_STAGE_HUNKS
+            | METHOD_GIT_UNSTAGE_HUNKS
+            | METHOD_GIT_DISCARD
+            | METHOD_GIT_COMMIT
+            | METHOD_GIT_BRANCH_CREATE
+            | METHOD_GIT_CHECKOUT
+            | METHOD_GIT_BRANCH_DELETE
+            | METHOD_GIT_FETCH
+            | METHOD_GIT_PULL
+            | METHOD_GIT_PUSH
+            | METHOD_GIT_RESOLVE
+            | METHOD_GIT_MERGE_ABORT
+            | METHOD_GIT_REBASE_CONTINUE
+            | METHOD_GIT_REBASE_ABORT
+            | METHOD_OPENCODE_CONNECT_WORKSPACE
+            | METHOD_OPENCODE_DISCONNECT_WORKSPACE
+            | METHOD_OPENCODE_SESSION_CREATE
+            | METHOD_OPENCODE_SESSION_PROMPT
+            | METHOD_OPENCODE_SESSION_ABORT
+            | METHOD_CLAUDE_SDK_CONNECT_WORKSPACE
+            | METHOD_CLAUDE_SDK_DISCONNECT_WORKSPACE
+            | METHOD_CLAUDE_SDK_SESSION_CREATE
+            | METHOD_CLAUDE_SDK_SESSION_PROMPT
+            | METHOD_CLAUDE_SDK_SESSION_ABORT
+            | METHOD_CLAUDE_SDK_PERMISSION_REPLY
+            | METHOD_CLAUDE_SDK_SESSION_SETTINGS_UPDATE
+    )
+}
+
+/// SHA-256 of the params JSON, so entries can be correlated without logging contents
+pub fn params_digest(params: &Value) -> String {
+    let digest = Sha256::digest(params.to_string().as_bytes());
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// "ok" for success responses, otherwise the error code
+pub fn response_outcome(response: &str) -> String {
+    let value: Value = serde_json::from_str(response).unwrap_or(Value::Null);
+    match value.pointer("/error/code").and_then(|c| c.as_str()) {
+        Some(code) => code.to_string(),
+        None => "ok".to_string(),
+    }
+}
+
+struct OpenLog {
+    file: File,
+    size: u64,
+}
+
+enum LogMessage {
+    Write(String),
+    /// Acknowledged once every earlier entry is written
+    Flush(mpsc::Sender<()>),
+}
+
+/// Size-rotated JSONL audit log. Entries are written by a background thread so
+/// recording never blocks the runtime on disk I/O.
+pub struct AuditLog {
+    path: PathBuf,
+    tx: mpsc::Sender<LogMessage>,
+}
+
+impl AuditLog {
+    pub fn new(data_dir: &Path) -> Self {
+        Self::with_max_bytes(data_dir.join("audit.log"), MAX_LOG_BYTES)
+    }
+
+    fn with_max_bytes(path: PathBuf, max_bytes: u64) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let mut writer = LogWriter {
+            path: path.clone(),
+            max_bytes,
+            current: None,
+        };
+        std::thread::spawn(move || {
+            for message in rx {
+                match message {
+                    LogMessage::Write(line) => writer.write(line.as_bytes()),
+                    LogMessage::Flush(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+        Self { path, tx }
+    }
+
+    /// Queue an entry for writing. Failures to write are logged, not returned.
+    pub fn record(&self, entry: &AuditEntry) {
+        let mut line = serde_json::to_string(entry).unwrap();
+        line.push('\n');
+        let _ = self.tx.send(LogMessage::Write(line));
+    }
+
+    /// Most recent entries matching the filters and `visible`, newest first. Entries
+    /// recorded before the call are included.
+    pub async fn query(
+        &self,
+        limit: usize,
+        method: Option<String>,
+        session_id: Option<String>,
+        visible: impl Fn(&AuditEntry) -> bool + Send + 'static,
+    ) -> Result<Vec<AuditEntry>, String> {
+        let path = self.path.clone();
+        let (done_tx, done) = mpsc::channel();
+        let _ = self.tx.send(LogMessage::Flush(done_tx));
+        tokio::task::spawn_blocking(move || {
+            let _ = done.recv();
+            let mut entries = Vec::new();
+            let files = std::iter::once(path.clone())
+                .chain((1..=ROTATED_FILES).map(|n| rotated_path(&path, n)));
+
+            for file in files {
+                if entries.len() >= limit {
+                    break;
+                }
+                let mut matching: Vec<AuditEntry> = read_entries(&file)?
+                    .into_iter()
+                    .filter(|e| method.as_ref().is_none_or(|m| e.method == *m))
+                    .filter(|e| {
+                        session_id
+                            .as_ref()
+                            .is_none_or(|s| e.session_id.as_deref() == Some(s.as_str()))
+                    })
+                    .filter(&visible)
+                    .collect();
+                matching.reverse();
+                entries.extend(matching.into_iter().take(limit - entries.len()));
+            }
+            Ok(entries)
+        })
+        .await
+        .map_err(|e| format!("Failed to read audit log: {e}"))?
+    }
+}
+
+/// Appends to the current log file on the writer thread
+struct LogWriter {
+    path: PathBuf,
+    max_bytes: u64,
+    current: Option<OpenLog>,
+}
+
+impl LogWriter {
+    fn write(&mut self, line: &[u8]) {
+        if let Err(e) = self.write_line(line) {
+            warn!("{e}");
+            self.current = None;
+        }
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> Result<(), String> {
+        if self.current.is_none() {
+            let file = open_append(&self.path)?;
+            let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+            self.current = Some(OpenLog { file, size });
+        }
+
+        if self.current.as_ref().is_some_and(|log| log.size >= self.max_bytes) {
+            self.current = None;
+            self.rotate()?;
+            let file = open_append(&self.path)?;
+            self.current = Some(OpenLog { file, size: 0 });
+        }
+
+        let log = self.current.as_mut().unwrap();
+        log.file
+            .write_all(line)
+            .map_err(|e| format!("Failed to write {}: {e}", self.path.display()))?;
+        log.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shift audit.log → audit.log.1 → … → audit.log.N, dropping the oldest
+    fn rotate(&self) -> Result<(), String> {
+        for n in (1..ROTATED_FILES).rev() {
+            let from = rotated_path(&self.path, n);
+            if from.exists() {
+                fs::rename(&from, rotated_path(&self.path, n + 1))
+                    .map_err(|e| format!("Failed to rotate {}: {e}", from.display()))?;
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))
+            .map_err(|e| format!("Failed to rotate {}: {e}", self.path.display()))
+    }
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+/// Parse a log file, skipping malformed lines (e.g. a partial write)
+fn read_entries(path: &Path) -> Result<Vec<AuditEntry>, String> {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to open {}: {e}", path.display())),
+    };
+
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(method: &str, session_id: &str) -> AuditEntry {
+        AuditEntry {
+            ts: "2024-03-01T00:00:00.000Z".to_string(),
+            client_id: 1,
+            peer: "127.0.0.1:5000".to_string(),
+            token_name: Some("default".to_string()),
+            method: method.to_string(),
+            session_id: Some(session_id.to_string()),
+            workspace_id: None,
+            params_digest: params_digest(&Value::Null),
+            outcome: "ok".to_string(),
+            duration_ms: 3,
+        }
+    }
+
+    #[tokio::test]
+    async fn audit_log_rotates_and_queries_newest_first() {
+        let dir = std::env::temp_dir().join(format!("maestro-audit-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        // Every write after the first rotates
+        let log = AuditLog::with_max_bytes(dir.join("audit.log"), 1);
+        log.record(&entry(METHOD_TERMINAL_WRITE, "/a"));
+        log.record(&entry(METHOD_SESSION_ADD, "/b"));
+        log.record(&entry(METHOD_TERMINAL_WRITE, "/b"));
+
+        let all = log.query(10, None, None, |_| true).await.unwrap();
+        assert!(rotated_path(&log.path, 2).exists());
+        let methods: Vec<_> = all.iter().map(|e| e.method.as_str()).collect();
+        assert_eq!(methods, [METHOD_TERMINAL_WRITE, METHOD_SESSION_ADD, METHOD_TERMINAL_WRITE]);
+        assert_eq!(all[0].session_id.as_deref(), Some("/b"));
+
+        let writes = log
+            .query(10, Some(METHOD_TERMINAL_WRITE.to_string()), Some("/a".to_string()), |_| true)
+            .await
+            .unwrap();
+        assert_eq!(writes.len(), 1);
+        assert_eq!(log.query(1, None, None, |_| true).await.unwrap().len(), 1);
+
+        // The visibility filter applies before the limit
+        let visible = log
+            .query(1, None, None, |e| e.session_id.as_deref() == Some("/a"))
+            .await
+            .unwrap();
+        assert_eq!(visible[0].session_id.as_deref(), Some("/a"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn terminal_input_and_mutations_are_audited() {
+        assert!(is_audited(METHOD_TERMINAL_WRITE));
+        assert!(is_audited(METHOD_GIT_COMMIT));
+        assert!(!is_audited(METHOD_GIT_STATUS));
+    }
+
+    #[test]
+    fn outcome_is_error_code_or_ok() {
+        assert_eq!(response_outcome(r#"{"id":1,"result":{}}"#), "ok");
+        assert_eq!(
+            response_outcome(r#"{"id":1,"error":{"code":"forbidden","message":"no"}}"#),
+            "forbidden"
+        );
+    }
+}
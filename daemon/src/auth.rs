@@ -0,0 +1,254 @@
+//! Token authentication: constant-time matching, brute-force lockout and the auth audit log
+//!
+//! Failed attempts are tracked per peer IP (per uid for Unix socket peers). After
+//! `LOCKOUT_THRESHOLD` consecutive failures the peer is locked out for an exponentially
+//! growing period. Every attempt is appended to `auth.log` in the data directory.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::access::{ApiToken, Principal};
+
+/// Consecutive failures allowed before a peer is locked out
+const LOCKOUT_THRESHOLD: u32 = 5;
+/// Lockout after the first failure past the threshold; doubles with each further failure
+const LOCKOUT_BASE: Duration = Duration::from_secs(2);
+const LOCKOUT_MAX: Duration = Duration::from_secs(15 * 60);
+/// Failure records idle for this long are forgotten
+const FAILURE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Result of an authentication attempt
+#[derive(Debug, Clone)]
+pub enum AuthOutcome {
+    Authenticated(Principal),
+    Rejected,
+    /// Peer is locked out; no token comparison was made
+    LockedOut(Duration),
+}
+
+/// Compare two secrets without leaking where they differ (or their lengths)
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    let a = Sha256::digest(a.as_bytes());
+    let b = Sha256::digest(b.as_bytes());
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Find the token matching `presented`, comparing against every configured token
+pub fn match_token<'a>(tokens: &'a [ApiToken], presented: &str) -> Option<&'a ApiToken> {
+    let mut matched = None;
+    for token in tokens {
+        if constant_time_eq(&token.token, presented) && matched.is_none() {
+            matched = Some(token);
+        }
+    }
+    matched
+}
+
+/// Key used to track failures: the IP for TCP peers, the uid for Unix socket peers
+pub fn peer_key(peer: &str) -> String {
+    if let Ok(addr) = peer.parse::<SocketAddr>() {
+        return addr.ip().to_string();
+    }
+    match peer.split_once(",pid=") {
+        Some((key, _)) => key.to_string(),
+        None => peer.to_string(),
+    }
+}
+
+struct FailureRecord {
+    failures: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Per-peer failed-attempt tracking with exponential lockout
+#[derive(Default)]
+pub struct AuthLimiter {
+    failures: Mutex<HashMap<String, FailureRecord>>,
+}
+
+impl AuthLimiter {
+    /// Remaining lockout for a peer, if it is locked out
+    pub fn locked_for(&self, key: &str) -> Option<Duration> {
+        let failures = self.failures.lock().unwrap();
+        let until = failures.get(key)?.locked_until?;
+        until.checked_duration_since(Instant::now())
+    }
+
+    /// Record a failed attempt. Returns the lockout imposed, if any.
+    pub fn record_failure(&self, key: &str) -> Option<Duration> {
+        let now = Instant::now();
+        let mut failures = self.failures.lock().unwrap();
+        failures.retain(|_, r| now.duration_since(r.last_failure) < FAILURE_TTL);
+
+        let record = failures.entry(key.to_string()).or_insert(FailureRecord {
+            failures: 0,
+            last_failure: now,
+            locked_until: None,
+        });
+        record.failures += 1;
+        record.last_failure = now;
+
+        let lockout = lockout_duration(record.failures)?;
+        record.locked_until = Some(now + lockout);
+        Some(lockout)
+    }
+
+    /// Clear the failure history of a peer after a successful attempt
+    pub fn record_success(&self, key: &str) {
+        self.failures.lock().unwrap().remove(key);
+    }
+}
+
+/// Lockout imposed after `failures` consecutive failures
+fn lockout_duration(failures: u32) -> Option<Duration> {
+    if failures < LOCKOUT_THRESHOLD {
+        return None;
+    }
+    let exponent = (failures - LOCKOUT_THRESHOLD).min(16);
+    Some(LOCKOUT_BASE.saturating_mul(1 << exponent).min(LOCKOUT_MAX))
+}
+
+#[derive(Serialize)]
+struct AuthLogRecord<'a> {
+    ts: String,
+    peer: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_name: Option<&'a str>,
+    outcome: &'a str,
+}
+
+/// Append-only JSONL log of authentication attempts (data_dir/auth.log)
+pub struct AuthAuditLog {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl AuthAuditLog {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join("auth.log"),
+            file: Mutex::new(None),
+        }
+    }
+
+    /// Append a record for an attempt. Failures to write are logged, not returned.
+    pub fn record(&self, peer: &str, outcome: &AuthOutcome) {
+        let (token_name, outcome) = match outcome {
+            AuthOutcome::Authenticated(principal) => (Some(principal.name.as_str()), "success"),
+            AuthOutcome::Rejected => (None, "failure"),
+            AuthOutcome::LockedOut(_) => (None, "locked_out"),
+        };
+        let record = AuthLogRecord {
+            ts: format_timestamp(SystemTime::now()),
+            peer,
+            token_name,
+            outcome,
+        };
+        let mut line = serde_json::to_string(&record).unwrap();
+        line.push('\n');
+
+        let mut file = self.file.lock().unwrap();
+        if file.is_none() {
+            match open_append(&self.path) {
+                Ok(f) => *file = Some(f),
+                Err(e) => {
+                    warn!("{e}");
+                    return;
+                }
+            }
+        }
+        if let Some(f) = file.as_mut() {
+            if let Err(e) = f.write_all(line.as_bytes()) {
+                warn!("Failed to write {}: {e}", self.path.display());
+                *file = None;
+            }
+        }
+    }
+}
+
+/// Open a file for appending, creating it owner-only
+pub fn open_append(path: &Path) -> Result<File, String> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {e}", path.display()))
+}
+
+/// Format a time as an RFC 3339 UTC timestamp with millisecond precision
+pub fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        rem / 3_600,
+        (rem % 3_600) / 60,
+        rem % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lockout_grows_exponentially_after_threshold() {
+        let limiter = AuthLimiter::default();
+        for _ in 1..LOCKOUT_THRESHOLD {
+            assert_eq!(limiter.record_failure("10.0.0.1"), None);
+        }
+        assert_eq!(limiter.record_failure("10.0.0.1"), Some(LOCKOUT_BASE));
+        assert_eq!(limiter.record_failure("10.0.0.1"), Some(LOCKOUT_BASE * 2));
+        assert!(limiter.locked_for("10.0.0.1").is_some());
+        assert!(limiter.locked_for("10.0.0.2").is_none());
+
+        limiter.record_success("10.0.0.1");
+        assert!(limiter.locked_for("10.0.0.1").is_none());
+        assert_eq!(lockout_duration(100), Some(LOCKOUT_MAX));
+    }
+
+    #[test]
+    fn token_matching_and_peer_keys() {
+        let tokens = vec![ApiToken::admin("a", "alpha"), ApiToken::admin("b", "beta")];
+        assert_eq!(match_token(&tokens, "beta").map(|t| t.name.as_str()), Some("b"));
+        assert!(match_token(&tokens, "bet").is_none());
+        assert!(!constant_time_eq("alpha", "alphA"));
+
+        assert_eq!(peer_key("192.168.1.5:51234"), "192.168.1.5");
+        assert_eq!(peer_key("[::1]:4733"), "::1");
+        assert_eq!(peer_key("unix:uid=1000,pid=42"), "unix:uid=1000");
+    }
+
+    #[test]
+    fn timestamps_are_rfc3339_utc() {
+        let t = UNIX_EPOCH + Duration::from_millis(1_709_251_200_123);
+        assert_eq!(format_timestamp(t), "2024-03-01T00:00:00.123Z");
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    }
+}
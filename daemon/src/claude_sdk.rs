@@ -83,6 +83,7 @@ impl ClaudeSdkServer {
     }
 
     /// Start the SSE bridge to forward events to all clients
+    #[allow(dead_code)]
     pub fn start_sse_bridge(&mut self, state: Arc<DaemonState>) {
         let base_url = self.base_url.clone();
         let workspace_id = self.workspace_id.clone();
@@ -721,7 +722,7 @@ async fn run_sse_stream(
                     let msg =
                         serde_json::to_string(&event).unwrap_or_else(|_| "{}".to_string());
 
-                    let client_count = state.broadcast_to_all_clients(msg).await;
+                    let client_count = state.broadcast_to_session_clients(workspace_path, msg).await;
                     debug!("[claude_sdk] Broadcast to {} clients", client_count);
                 }
             }
@@ -1,16 +1,30 @@
 use clap::Parser;
-use serde::Deserialize;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-use crate::protocol::SessionInfo;
+use crate::access::ApiToken;
+use crate::protocol::{SessionInfo, TerminalProfile};
 
 /// Maestro daemon - remote terminal and git operations
 #[derive(Parser, Debug)]
 #[command(name = "maestro-daemon")]
 pub struct Args {
-    /// Bind address
-    #[arg(long, default_value = "127.0.0.1:4733")]
-    pub listen: String,
+    /// Bind address [default: 127.0.0.1:4733, not bound when only --unix-socket is given]
+    #[arg(long)]
+    pub listen: Option<String>,
+
+    /// Unix domain socket path (mode 0600; same-user peers skip the token handshake)
+    #[arg(long)]
+    pub unix_socket: Option<PathBuf>,
+
+    /// Require the token handshake on the Unix socket even for same-user peers
+    #[arg(long, requires = "unix_socket")]
+    pub unix_socket_require_token: bool,
+
+    /// WebSocket bind address (same JSON frames as --listen, one per text message)
+    #[arg(long)]
+    pub ws_listen: Option<String>,
 
     /// Auth token (or set MAESTRO_DAEMON_TOKEN env var)
     #[arg(long, env = "MAESTRO_DAEMON_TOKEN")]
@@ -20,12 +34,48 @@ pub struct Args {
     #[arg(long, env = "MAESTRO_DATA_DIR")]
     pub data_dir: Option<PathBuf>,
 
+    /// Seconds a terminal stays alive after its client disconnects (0 = close immediately)
+    #[arg(long, env = "MAESTRO_TERMINAL_GRACE_SECS", default_value_t = 300)]
+    pub terminal_grace_secs: u64,
+
+    /// Record terminals as asciinema casts under <data-dir>/recordings by default
+    #[arg(long, env = "MAESTRO_RECORD_TERMINALS")]
+    pub record_terminals: bool,
+
     /// Disable auth (dev only)
     #[arg(long)]
     pub insecure_no_auth: bool,
+
+    /// Serve TLS (self-signed cert in the data dir unless --tls-cert/--tls-key are set)
+    #[arg(long)]
+    pub tls: bool,
+
+    /// TLS certificate chain (PEM); implies --tls
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// TLS private key (PEM); implies --tls
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
 }
 
+const DEFAULT_LISTEN: &str = "127.0.0.1:4733";
+
 impl Args {
+    /// TCP bind address, or None in Unix-socket-only mode
+    pub fn listen_addr(&self) -> Option<String> {
+        match (&self.listen, &self.unix_socket) {
+            (Some(listen), _) => Some(listen.clone()),
+            (None, None) => Some(DEFAULT_LISTEN.to_string()),
+            (None, Some(_)) => None,
+        }
+    }
+
+    /// Whether any listener needs the shared token for authentication
+    pub fn needs_token(&self) -> bool {
+        self.listen_addr().is_some() || self.ws_listen.is_some() || self.unix_socket_require_token
+    }
+
     pub fn data_dir(&self) -> PathBuf {
         self.data_dir
             .clone()
@@ -35,6 +85,10 @@ impl Args {
     pub fn require_auth(&self) -> bool {
         !self.insecure_no_auth
     }
+
+    pub fn tls_enabled(&self) -> bool {
+        self.tls || self.tls_cert.is_some()
+    }
 }
 
 fn dirs_data_dir() -> PathBuf {
@@ -43,20 +97,47 @@ fn dirs_data_dir() -> PathBuf {
         .unwrap_or_else(|_| PathBuf::from("/tmp"))
 }
 
+/// config.json format (optional daemon settings)
+#[derive(Debug, Default, Deserialize)]
+pub struct DaemonConfig {
+    /// Named API tokens with scopes (in addition to --token)
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
+
+    /// Named terminal profiles usable from `terminal_open`
+    #[serde(default)]
+    pub terminal_profiles: HashMap<String, TerminalProfile>,
+}
+
+impl DaemonConfig {
+    pub fn load(data_dir: &Path) -> Result<Self, String> {
+        let path = data_dir.join("config.json");
+        if !path.exists() {
+            return Ok(DaemonConfig::default());
+        }
+
+        let content =
+            std::fs::read_to_string(&path).map_err(|e| format!("Failed to read config.json: {e}"))?;
+
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse config.json: {e}"))
+    }
+}
+
 /// sessions.json format
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SessionsConfig {
     pub sessions: Vec<SessionEntry>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SessionEntry {
     pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
 }
 
 impl SessionsConfig {
-    pub fn load(data_dir: &PathBuf) -> Result<Self, String> {
+    pub fn load(data_dir: &Path) -> Result<Self, String> {
         let path = data_dir.join("sessions.json");
         if !path.exists() {
             return Ok(SessionsConfig { sessions: vec![] });
@@ -68,18 +149,82 @@ impl SessionsConfig {
         serde_json::from_str(&content).map_err(|e| format!("Failed to parse sessions.json: {e}"))
     }
 
+    /// Write sessions.json atomically (temp file + rename)
+    pub fn save(&self, data_dir: &Path) -> Result<(), String> {
+        let path = data_dir.join("sessions.json");
+        let tmp_path = data_dir.join("sessions.json.tmp");
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize sessions.json: {e}"))?;
+
+        std::fs::write(&tmp_path, content)
+            .map_err(|e| format!("Failed to write sessions.json: {e}"))?;
+        std::fs::rename(&tmp_path, &path)
+            .map_err(|e| format!("Failed to replace sessions.json: {e}"))
+    }
+
     pub fn to_session_infos(&self) -> Vec<SessionInfo> {
         self.sessions
             .iter()
             .map(|e| SessionInfo {
                 path: e.path.clone(),
-                name: e.name.clone().unwrap_or_else(|| {
-                    PathBuf::from(&e.path)
-                        .file_name()
-                        .map(|n| n.to_string_lossy().to_string())
-                        .unwrap_or_else(|| e.path.clone())
-                }),
+                name: e.name.clone().unwrap_or_else(|| default_session_name(&e.path)),
             })
             .collect()
     }
+
+    /// Build a config from runtime sessions, omitting names that match the default
+    pub fn from_session_infos(sessions: &[SessionInfo]) -> Self {
+        let mut sessions: Vec<SessionEntry> = sessions
+            .iter()
+            .map(|s| SessionEntry {
+                path: s.path.clone(),
+                name: (s.name != default_session_name(&s.path)).then(|| s.name.clone()),
+            })
+            .collect();
+        sessions.sort_by(|a, b| a.path.cmp(&b.path));
+        SessionsConfig { sessions }
+    }
+}
+
+/// Default display name for a session: the last path component
+pub fn default_session_name(path: &str) -> String {
+    PathBuf::from(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SessionsConfig, SessionInfo};
+
+    #[test]
+    fn sessions_config_save_and_load_round_trip() {
+        let dir = std::env::temp_dir().join(format!("maestro-config-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+
+        let infos = vec![
+            SessionInfo {
+                path: "/srv/b/project".to_string(),
+                name: "project".to_string(),
+            },
+            SessionInfo {
+                path: "/srv/a/other".to_string(),
+                name: "Renamed".to_string(),
+            },
+        ];
+        SessionsConfig::from_session_infos(&infos)
+            .save(&dir)
+            .expect("save sessions");
+
+        let loaded = SessionsConfig::load(&dir).expect("load sessions");
+        assert_eq!(loaded.sessions.len(), 2);
+        assert_eq!(loaded.sessions[0].path, "/srv/a/other");
+        assert_eq!(loaded.sessions[0].name.as_deref(), Some("Renamed"));
+        assert_eq!(loaded.sessions[1].name, None);
+        assert!(!dir.join("sessions.json.tmp").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
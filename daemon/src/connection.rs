@@ -1,30 +1,104 @@
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::UnixStream;
 use tokio::sync::mpsc;
 use tokio::time::timeout;
 use tracing::{debug, error, info, warn};
 
+use crate::access::Principal;
 use crate::handlers;
-use crate::protocol::{ErrorResponse, Request, AUTH_REQUIRED};
-use crate::state::{ClientId, DaemonState};
+use crate::auth::AuthOutcome;
+use crate::protocol::{ErrorResponse, Request, AUTH_REQUIRED, METHOD_AUTH};
+use crate::state::{ClientId, ClientReceiver, DaemonState};
+use crate::transport::{LineTransport, Transport, WsTransport};
 
 const AUTH_TIMEOUT: Duration = Duration::from_secs(30);
 
-/// Handle a single client connection
-pub async fn handle_client(stream: TcpStream, state: Arc<DaemonState>) {
-    let peer = stream
-        .peer_addr()
-        .map(|a| a.to_string())
-        .unwrap_or_else(|_| "unknown".to_string());
+/// Requests read ahead of the one being processed. When full, reading from the
+/// client pauses until the worker catches up.
+const REQUEST_QUEUE: usize = 64;
 
+/// Handle a newline-delimited JSON client connection (plain TCP or TLS)
+pub async fn handle_client<S>(stream: S, peer: String, state: Arc<DaemonState>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    serve_client(LineTransport::new(stream), peer, state, false).await;
+}
+
+/// Handle a WebSocket client connection (plain or TLS)
+pub async fn handle_ws_client<S>(stream: S, peer: String, state: Arc<DaemonState>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    match WsTransport::accept(stream).await {
+        Ok(transport) => serve_client(transport, peer, state, false).await,
+        Err(e) => warn!("Client {peer}: {e}"),
+    }
+}
+
+/// Handle a Unix socket client connection.
+///
+/// Peers running as the daemon's own user skip the token handshake unless
+/// `require_token` is set; other users must authenticate with the token.
+pub async fn handle_unix_client(stream: UnixStream, require_token: bool, state: Arc<DaemonState>) {
+    let cred = match stream.peer_cred() {
+        Ok(cred) => cred,
+        Err(e) => {
+            warn!("Unix socket client rejected: failed to read peer credentials: {e}");
+            return;
+        }
+    };
+
+    let peer = match cred.pid() {
+        Some(pid) => format!("unix:uid={},pid={pid}", cred.uid()),
+        None => format!("unix:uid={}", cred.uid()),
+    };
+
+    // SAFETY: geteuid has no preconditions and cannot fail
+    let same_user = cred.uid() == unsafe { libc::geteuid() };
+    let preauthenticated = same_user && !require_token;
+
+    if !preauthenticated && !state.auth_required() {
+        warn!("Client {peer} rejected: not the daemon user and no token is configured");
+        return;
+    }
+
+    serve_client(LineTransport::new(stream), peer, state, preauthenticated).await;
+}
+
+/// Run a client session over any transport
+async fn serve_client<T: Transport>(
+    mut transport: T,
+    peer: String,
+    state: Arc<DaemonState>,
+    preauthenticated: bool,
+) {
     info!("Client connected: {peer}");
 
-    let (client_id, event_rx) = state.register_client().await;
+    let (client_id, events) = state.register_client(&peer).await;
     debug!("Assigned client_id={client_id} to {peer}");
 
-    let result = handle_client_inner(stream, state.clone(), client_id, event_rx).await;
+    // Connections that skip the token handshake get full access
+    if preauthenticated {
+        state
+            .set_client_principal(client_id, Principal::unrestricted(peer.clone()))
+            .await;
+    } else if !state.auth_required() {
+        state
+            .set_client_principal(client_id, Principal::unrestricted("anonymous"))
+            .await;
+    }
+
+    let result = handle_client_inner(
+        &mut transport,
+        state.clone(),
+        client_id,
+        events,
+        preauthenticated,
+    )
+    .await;
 
     if let Err(e) = result {
         debug!("Client {peer} error: {e}");
@@ -34,23 +108,19 @@ pub async fn handle_client(stream: TcpStream, state: Arc<DaemonState>) {
     state.unregister_client(client_id).await;
 }
 
-async fn handle_client_inner(
-    stream: TcpStream,
+async fn handle_client_inner<T: Transport>(
+    transport: &mut T,
     state: Arc<DaemonState>,
     client_id: ClientId,
-    mut event_rx: mpsc::UnboundedReceiver<String>,
+    mut events: ClientReceiver,
+    preauthenticated: bool,
 ) -> Result<(), String> {
-    let (reader, mut writer) = stream.into_split();
-    let mut reader = BufReader::new(reader);
-    let mut line = String::new();
-
     // Auth phase
-    let authenticated = if state.token.is_some() {
-        match timeout(AUTH_TIMEOUT, wait_for_auth(&mut reader, &mut writer, &state)).await {
+    let authenticated = if state.auth_required() && !preauthenticated {
+        match timeout(AUTH_TIMEOUT, wait_for_auth(transport, &state, client_id)).await {
             Ok(Ok(true)) => true,
             Ok(Ok(false)) => {
-                // Auth failed but timeout not exceeded, keep trying
-                // Actually, wait_for_auth returns true only on success
+                warn!("Client {client_id} locked out after repeated auth failures");
                 false
             }
             Ok(Err(e)) => {
@@ -71,47 +141,70 @@ async fn handle_client_inner(
         return Err("Authentication failed".to_string());
     }
 
-    // Main loop: read requests and forward events
+    // Requests run in order on their own task so a slow one does not stall events
+    let (request_tx, mut request_rx) = mpsc::channel::<String>(REQUEST_QUEUE);
+    let (response_tx, mut responses) = mpsc::unbounded_channel::<String>();
+    let worker_state = state.clone();
+    tokio::spawn(async move {
+        while let Some(frame) = request_rx.recv().await {
+            if let Some(response) = process_request(&frame, worker_state.clone(), client_id).await {
+                if response_tx.send(response).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    // A request waiting for room in the queue; responses and events keep flowing
+    let mut pending: Option<String> = None;
+
+    // Main loop: read requests and forward responses and events
     loop {
-        tokio::select! {
+        let outgoing = tokio::select! {
             // Read request from client
-            result = reader.read_line(&mut line) => {
+            result = transport.recv(), if pending.is_none() => {
                 match result {
-                    Ok(0) => break, // EOF
-                    Ok(_) => {
-                        let trimmed = line.trim();
-                        if !trimmed.is_empty() {
-                            if let Some(response) = process_request(trimmed, state.clone(), client_id).await {
-                                if let Err(e) = writer.write_all(response.as_bytes()).await {
-                                    error!("Failed to write response: {e}");
-                                    break;
-                                }
-                                if let Err(e) = writer.write_all(b"\n").await {
-                                    error!("Failed to write newline: {e}");
-                                    break;
-                                }
-                            }
-                        }
-                        line.clear();
+                    Ok(None) => break, // EOF
+                    Ok(Some(frame)) => {
+                        pending = Some(frame);
+                        continue;
                     }
                     Err(e) => {
-                        debug!("Read error: {e}");
+                        debug!("{e}");
                         break;
                     }
                 }
             }
 
+            permit = request_tx.reserve(), if pending.is_some() => {
+                let Ok(permit) = permit else {
+                    break;
+                };
+                permit.send(pending.take().unwrap());
+                continue;
+            }
+
+            Some(response) = responses.recv() => response,
+
             // Forward events to client
-            Some(event) = event_rx.recv() => {
-                if let Err(e) = writer.write_all(event.as_bytes()).await {
-                    error!("Failed to write event: {e}");
+            event = events.events.recv() => {
+                let Some(event) = event else {
                     break;
-                }
-                if let Err(e) = writer.write_all(b"\n").await {
-                    error!("Failed to write newline: {e}");
+                };
+                event
+            }
+
+            output = events.output.recv() => {
+                let Some(output) = output else {
                     break;
-                }
+                };
+                output
             }
+        };
+
+        if let Err(e) = transport.send(&outgoing).await {
+            error!("Failed to write to client: {e}");
+            break;
         }
     }
 
@@ -119,58 +212,47 @@ async fn handle_client_inner(
 }
 
 /// Wait for auth request within timeout
-async fn wait_for_auth(
-    reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>,
-    writer: &mut tokio::net::tcp::OwnedWriteHalf,
+async fn wait_for_auth<T: Transport>(
+    transport: &mut T,
     state: &DaemonState,
+    client_id: ClientId,
 ) -> Result<bool, String> {
-    let mut line = String::new();
-
     loop {
-        line.clear();
-        match reader.read_line(&mut line).await {
-            Ok(0) => return Err("Connection closed".to_string()),
-            Ok(_) => {
-                let trimmed = line.trim();
-                if trimmed.is_empty() {
-                    continue;
-                }
-
-                // Parse request
-                let request: Request = match serde_json::from_str(trimmed) {
-                    Ok(r) => r,
-                    Err(e) => {
-                        let resp = ErrorResponse::new(0, "invalid_params", format!("Invalid JSON: {e}"));
-                        let json = serde_json::to_string(&resp).unwrap();
-                        let _ = writer.write_all(json.as_bytes()).await;
-                        let _ = writer.write_all(b"\n").await;
-                        continue;
-                    }
-                };
+        let frame = match transport.recv().await? {
+            Some(frame) => frame,
+            None => return Err("Connection closed".to_string()),
+        };
+
+        // Parse request
+        let request: Request = match serde_json::from_str(&frame) {
+            Ok(r) => r,
+            Err(e) => {
+                let resp = ErrorResponse::new(0, "invalid_params", format!("Invalid JSON: {e}"));
+                let json = serde_json::to_string(&resp).unwrap();
+                let _ = transport.send(&json).await;
+                continue;
+            }
+        };
 
-                if request.method == "auth" {
-                    let response = handlers::auth::handle(&request, state).await;
-                    let _ = writer.write_all(response.as_bytes()).await;
-                    let _ = writer.write_all(b"\n").await;
+        if request.method == METHOD_AUTH {
+            let (outcome, response) = handlers::auth::authenticate(&request, state, client_id).await;
+            let _ = transport.send(&response).await;
 
-                    // Check if auth succeeded
-                    if response.contains("\"ok\":true") || response.contains("\"ok\": true") {
-                        return Ok(true);
-                    }
-                    // Auth failed, but allow retry (loop continues)
-                } else {
-                    // Non-auth request before authentication
-                    let resp = ErrorResponse::new(
-                        request.id,
-                        AUTH_REQUIRED,
-                        "Authentication required. Send auth request first.",
-                    );
-                    let json = serde_json::to_string(&resp).unwrap();
-                    let _ = writer.write_all(json.as_bytes()).await;
-                    let _ = writer.write_all(b"\n").await;
-                }
+            match outcome {
+                AuthOutcome::Authenticated(_) => return Ok(true),
+                // Auth failed, but allow retry until locked out (loop continues)
+                AuthOutcome::Rejected => {}
+                AuthOutcome::LockedOut(_) => return Ok(false),
             }
-            Err(e) => return Err(format!("Read error: {e}")),
+        } else {
+            // Non-auth request before authentication
+            let resp = ErrorResponse::new(
+                request.id,
+                AUTH_REQUIRED,
+                "Authentication required. Send auth request first.",
+            );
+            let json = serde_json::to_string(&resp).unwrap();
+            let _ = transport.send(&json).await;
         }
     }
 }
@@ -194,14 +276,18 @@ async fn process_request(
 
 #[cfg(test)]
 mod tests {
-    use super::handle_client;
+    use super::{handle_client, handle_unix_client, handle_ws_client};
+    use crate::access::ApiToken;
     use crate::protocol::SessionInfo;
-    use crate::state::DaemonState;
+    use crate::state::{DaemonState, CLIENT_QUEUE};
+    use futures::{SinkExt, StreamExt};
     use serde_json::Value;
     use std::sync::Arc;
     use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-    use tokio::net::{TcpListener, TcpStream};
+    use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
     use tokio::time::{timeout, Duration};
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::WebSocketStream;
 
     const TEST_TIMEOUT: Duration = Duration::from_secs(2);
 
@@ -215,13 +301,26 @@ mod tests {
         line
     }
 
+    async fn read_frame(ws: &mut WebSocketStream<TcpStream>) -> Value {
+        let message = timeout(TEST_TIMEOUT, ws.next())
+            .await
+            .expect("read timeout")
+            .expect("ws frame")
+            .expect("ws message");
+        serde_json::from_str(message.to_text().expect("text frame")).expect("frame json")
+    }
+
     #[tokio::test]
     async fn rpc_auth_and_list_sessions_round_trip() {
         let sessions = vec![SessionInfo {
             path: "/tmp/project".to_string(),
             name: "project".to_string(),
         }];
-        let state = Arc::new(DaemonState::new(Some("secret".to_string()), sessions));
+        let state = Arc::new(DaemonState::new(
+            vec![ApiToken::admin("default", "secret")],
+            sessions,
+            std::env::temp_dir(),
+        ));
 
         let listener = TcpListener::bind("127.0.0.1:0")
             .await
@@ -230,8 +329,8 @@ mod tests {
         let state_clone = state.clone();
 
         tokio::spawn(async move {
-            if let Ok((stream, _)) = listener.accept().await {
-                handle_client(stream, state_clone).await;
+            if let Ok((stream, peer)) = listener.accept().await {
+                handle_client(stream, peer.to_string(), state_clone).await;
             }
         });
 
@@ -265,4 +364,185 @@ mod tests {
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].get("path"), Some(&Value::String("/tmp/project".to_string())));
     }
+
+    #[tokio::test]
+    async fn ws_auth_and_list_sessions_round_trip() {
+        let sessions = vec![SessionInfo {
+            path: "/tmp/project".to_string(),
+            name: "project".to_string(),
+        }];
+        let state = Arc::new(DaemonState::new(
+            vec![ApiToken::admin("default", "secret")],
+            sessions,
+            std::env::temp_dir(),
+        ));
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind listener");
+        let addr = listener.local_addr().expect("local addr");
+        let state_clone = state.clone();
+
+        tokio::spawn(async move {
+            if let Ok((stream, peer)) = listener.accept().await {
+                handle_ws_client(stream, peer.to_string(), state_clone).await;
+            }
+        });
+
+        let stream = timeout(TEST_TIMEOUT, TcpStream::connect(addr))
+            .await
+            .expect("connect timeout")
+            .expect("connect");
+        let (mut ws, _) = tokio_tungstenite::client_async(format!("ws://{addr}/"), stream)
+            .await
+            .expect("ws handshake");
+
+        let auth = r#"{"id":1,"method":"auth","params":{"token":"secret"}}"#;
+        ws.send(Message::text(auth)).await.expect("send auth");
+        let auth_value = read_frame(&mut ws).await;
+        assert!(auth_value.get("result").is_some());
+
+        let list = r#"{"id":2,"method":"list_sessions","params":{}}"#;
+        ws.send(Message::text(list)).await.expect("send list_sessions");
+        let list_value = read_frame(&mut ws).await;
+        let result = list_value
+            .get("result")
+            .and_then(|value| value.as_array())
+            .expect("session list");
+        assert_eq!(result.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn reliable_events_survive_a_full_output_queue() {
+        let state = Arc::new(DaemonState::new(
+            vec![ApiToken::admin("default", "secret")],
+            vec![],
+            std::env::temp_dir(),
+        ));
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind listener");
+        let addr = listener.local_addr().expect("local addr");
+        let state_clone = state.clone();
+
+        tokio::spawn(async move {
+            if let Ok((stream, peer)) = listener.accept().await {
+                handle_client(stream, peer.to_string(), state_clone).await;
+            }
+        });
+
+        let stream = timeout(TEST_TIMEOUT, TcpStream::connect(addr))
+            .await
+            .expect("connect timeout")
+            .expect("connect");
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        writer
+            .write_all(b"{\"id\":1,\"method\":\"auth\",\"params\":{\"token\":\"secret\"}}\n")
+            .await
+            .expect("write auth");
+        read_line(&mut reader).await;
+
+        let count = CLIENT_QUEUE * 2;
+        for i in 0..count {
+            state.send_to_clients(&[1], format!("{{\"event\":{i}}}")).await;
+        }
+
+        writer
+            .write_all(b"{\"id\":2,\"method\":\"list_sessions\",\"params\":{}}\n")
+            .await
+            .expect("write list_sessions");
+
+        let mut events = 0;
+        let mut responded = false;
+        while events < count || !responded {
+            let value: Value = serde_json::from_str(read_line(&mut reader).await.trim()).expect("json");
+            if value.get("id") == Some(&Value::from(2)) {
+                responded = true;
+            } else {
+                events += 1;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn unix_same_user_skips_token_handshake() {
+        let state = Arc::new(DaemonState::new(
+            vec![ApiToken::admin("default", "secret")],
+            vec![],
+            std::env::temp_dir(),
+        ));
+
+        let socket_path =
+            std::env::temp_dir().join(format!("maestro-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path).expect("bind unix listener");
+        let state_clone = state.clone();
+
+        tokio::spawn(async move {
+            if let Ok((stream, _)) = listener.accept().await {
+                handle_unix_client(stream, false, state_clone).await;
+            }
+        });
+
+        let stream = timeout(TEST_TIMEOUT, UnixStream::connect(&socket_path))
+            .await
+            .expect("connect timeout")
+            .expect("connect");
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        writer
+            .write_all(b"{\"id\":1,\"method\":\"list_sessions\",\"params\":{}}\n")
+            .await
+            .expect("write list_sessions");
+
+        let mut line = String::new();
+        timeout(TEST_TIMEOUT, reader.read_line(&mut line))
+            .await
+            .expect("read timeout")
+            .expect("read line");
+        let value: Value = serde_json::from_str(line.trim()).expect("list json");
+        assert!(value.get("result").is_some());
+
+        let _ = std::fs::remove_file(&socket_path);
+    }
+
+    #[tokio::test]
+    async fn pipelined_requests_beyond_the_queue_are_all_answered_in_order() {
+        let state = Arc::new(DaemonState::new(vec![], vec![], std::env::temp_dir()));
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind listener");
+        let addr = listener.local_addr().expect("local addr");
+        let state_clone = state.clone();
+
+        tokio::spawn(async move {
+            if let Ok((stream, peer)) = listener.accept().await {
+                handle_client(stream, peer.to_string(), state_clone).await;
+            }
+        });
+
+        let stream = timeout(TEST_TIMEOUT, TcpStream::connect(addr))
+            .await
+            .expect("connect timeout")
+            .expect("connect");
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        // Written before reading any response, so the request queue fills up
+        let count = super::REQUEST_QUEUE * 4;
+        let requests: String = (1..=count)
+            .map(|id| format!("{{\"id\":{id},\"method\":\"list_sessions\",\"params\":{{}}}}\n"))
+            .collect();
+        writer.write_all(requests.as_bytes()).await.expect("write requests");
+
+        for id in 1..=count {
+            let value: Value = serde_json::from_str(read_line(&mut reader).await.trim()).expect("json");
+            assert_eq!(value.get("id"), Some(&Value::from(id)));
+        }
+    }
 }
@@ -1,11 +1,28 @@
 use std::collections::HashMap;
-use std::path::Path;
-use std::process::Command;
+use std::io::{Read, Write};
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::process::{Command, Output, Stdio};
+use std::time::UNIX_EPOCH;
 
-use crate::protocol::{GitDiffResult, GitFileDiff, GitFileStatus, GitLogEntry, GitLogResult, GitStatusResult};
+use sha2::{Digest, Sha256};
+
+use crate::protocol::{
+    GitBranch, GitBranchesResult, GitCommitResult, GitDiffFileInfo, GitDiffFileResult,
+    GitDiffOptions, GitDiffResult, GitFileDiff, GitFileStatus,
+    GitHunkSelection, GitLogEntry, GitLogResult, GitShowResult, GitStatusResult,
+    GitConflictFile, GitConflictRegion, GitConflictsResult, GitProgress, GitPullMode,
+    GitResolution, GitSubmoduleStatus, WhitespaceMode,
+};
 
 /// Max diff size before truncation (1MB)
 const MAX_DIFF_SIZE: usize = 1_000_000;
+/// Larger diff snapshots are not kept in the cache
+const MAX_CACHED_DIFF_SIZE: usize = 16 * 1024 * 1024;
+/// Diff specs cached per session (e.g. a client's options plus the default spec)
+const CACHED_SPECS: usize = 4;
 
 /// Check if path is a git repository
 pub fn is_git_repo(path: &Path) -> bool {
@@ -40,32 +57,32 @@ pub fn get_status(path: &Path) -> Result<GitStatusResult, String> {
         .trim()
         .to_string();
 
-    // Get status with porcelain format
+    // Get status with NUL-delimited porcelain v2 format (paths unquoted)
     let status_output = Command::new("git")
-        .args(["status", "--porcelain=v1"])
+        .args(["status", "--porcelain=v2", "-z"])
         .current_dir(path)
         .output()
         .map_err(|e| format!("Failed to get status: {e}"))?;
 
-    let (mut staged_files, mut unstaged_files) = parse_porcelain_status(&status_output.stdout);
+    let (mut staged_files, mut unstaged_files) = parse_porcelain_v2(&status_output.stdout);
 
     // Get diff stats for staged files
     let staged_stats_output = Command::new("git")
-        .args(["diff", "--cached", "--numstat"])
+        .args(["diff", "--cached", "--numstat", "-z"])
         .current_dir(path)
         .output()
         .map_err(|e| format!("Failed to get staged stats: {e}"))?;
 
-    let staged_stats = parse_numstat(&staged_stats_output.stdout);
+    let staged_stats = parse_numstat_z(&staged_stats_output.stdout);
 
     // Get diff stats for unstaged files
     let unstaged_stats_output = Command::new("git")
-        .args(["diff", "--numstat"])
+        .args(["diff", "--numstat", "-z"])
         .current_dir(path)
         .output()
         .map_err(|e| format!("Failed to get unstaged stats: {e}"))?;
 
-    let unstaged_stats = parse_numstat(&unstaged_stats_output.stdout);
+    let unstaged_stats = parse_numstat_z(&unstaged_stats_output.stdout);
 
     // Apply stats to file lists
     for file in &mut staged_files {
@@ -104,165 +121,556 @@ pub fn get_status(path: &Path) -> Result<GitStatusResult, String> {
     })
 }
 
-/// Get git diffs with truncation for large files
-pub fn get_diff(path: &Path) -> Result<GitDiffResult, String> {
+/// List changed files with diff metadata; diffs themselves are fetched per file with
+/// `get_file_diff`. A file with both staged and unstaged changes is listed twice.
+pub fn get_diff(path: &Path, cache: &DiffCache, spec: &DiffSpec) -> Result<GitDiffResult, String> {
     if !is_git_repo(path) {
-        return Ok(GitDiffResult {
-            files: vec![],
-            truncated: false,
-            truncated_files: vec![],
-        });
+        return Ok(GitDiffResult { files: vec![] });
     }
 
-    let mut files = Vec::new();
-    let mut truncated_files = Vec::new();
-    let mut total_size = 0usize;
-    let mut truncated = false;
+    let snapshot = cache.snapshot(path, spec)?;
+    let files = snapshot
+        .staged
+        .iter()
+        .chain(&snapshot.unstaged)
+        .map(|file| file.info.clone())
+        .collect();
+    Ok(GitDiffResult { files })
+}
 
-    // Get staged changed files
-    let staged_files_output = Command::new("git")
-        .args(["diff", "--cached", "--name-only"])
-        .current_dir(path)
-        .output()
-        .map_err(|e| format!("Failed to list staged files: {e}"))?;
+/// One page of a file's diff: the file header followed by up to `hunk_limit` hunks from
+/// `hunk_offset` (by default as many as fit in `MAX_DIFF_SIZE`, at least one)
+#[allow(clippy::too_many_arguments)]
+pub fn get_file_diff(
+    path: &Path,
+    cache: &DiffCache,
+    spec: &DiffSpec,
+    file_path: &str,
+    staged: bool,
+    hunk_offset: usize,
+    hunk_limit: Option<usize>,
+) -> Result<Option<GitDiffFileResult>, String> {
+    let snapshot = cache.snapshot(path, spec)?;
+    let staged = staged && spec.commits.is_none();
+    let side = if staged { &snapshot.staged } else { &snapshot.unstaged };
+    let Some(file) = side.iter().find(|f| f.info.path == file_path) else {
+        return Ok(None);
+    };
 
-    let staged_paths: Vec<String> = String::from_utf8_lossy(&staged_files_output.stdout)
-        .lines()
-        .filter(|s| !s.is_empty())
-        .map(|s| s.to_string())
-        .collect();
+    let (header, hunks) = split_hunks(&file.diff);
+    let mut diff = header.to_string();
+    let mut end = hunk_offset.min(hunks.len());
+    while end < hunks.len() {
+        let fits = match hunk_limit {
+            Some(limit) => end - hunk_offset < limit,
+            None => end == hunk_offset || diff.len() + hunks[end].len() <= MAX_DIFF_SIZE,
+        };
+        if !fits {
+            break;
+        }
+        diff.push_str(hunks[end]);
+        end += 1;
+    }
 
-    // Get diff for each staged file
-    for file_path in staged_paths {
-        if truncated {
-            truncated_files.push(file_path);
-            continue;
+    Ok(Some(GitDiffFileResult {
+        path: file.info.path.clone(),
+        staged,
+        hash: file.info.hash.clone(),
+        diff,
+        total_hunks: hunks.len(),
+        hunk_offset,
+        next_hunk_offset: (end < hunks.len()).then_some(end),
+    }))
+}
+
+/// A file in a diff snapshot
+#[derive(Debug)]
+pub struct SnapshotFile {
+    pub info: GitDiffFileInfo,
+    pub diff: String,
+}
+
+/// Untruncated per-file diffs of a working tree
+#[derive(Debug, Default)]
+pub struct DiffSnapshot {
+    /// HEAD → index
+    pub staged: Vec<SnapshotFile>,
+    /// Index → worktree, including untracked files and conflicted files (or, when
+    /// comparing commits, every file)
+    pub unstaged: Vec<SnapshotFile>,
+}
+
+/// What a diff compares and the extra `git diff` flags it is produced with
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffSpec {
+    /// Resolved base commit and target commit (the working tree if none), replacing
+    /// the HEAD → index → worktree diffs
+    commits: Option<(String, Option<String>)>,
+    flags: Vec<String>,
+}
+
+/// Check that a diff fetched with `options` can be staged or unstaged by hunk: it must
+/// compare the index with HEAD or the worktree and keep every changed byte
+pub fn validate_hunk_options(options: &GitDiffOptions) -> Result<(), String> {
+    if options.base.is_some() || options.target.is_some() {
+        return Err("Hunks can only be staged from the index or worktree diff".to_string());
+    }
+    if options.word_diff {
+        return Err("Hunks cannot be staged from a word diff".to_string());
+    }
+    if options.whitespace != WhitespaceMode::Show {
+        return Err("Hunks cannot be staged from a diff that ignores whitespace".to_string());
+    }
+    if options.context_lines == Some(0) {
+        return Err("Hunks need at least one line of context".to_string());
+    }
+    Ok(())
+}
+
+/// Check option values that do not depend on the repository
+pub fn validate_diff_options(options: &GitDiffOptions) -> Result<(), String> {
+    if options.target.is_some() {
+        match &options.base {
+            None => return Err("target requires base".to_string()),
+            Some(base) if base.contains("..") => {
+                return Err("target cannot be combined with a base range".to_string())
+            }
+            Some(_) => {}
         }
+    }
+    if options.rename_threshold.is_some_and(|t| t > 100) {
+        return Err("rename_threshold must be between 0 and 100".to_string());
+    }
+    Ok(())
+}
 
-        let diff_output = Command::new("git")
-            .args(["diff", "--cached", "--", &file_path])
-            .current_dir(path)
-            .output()
-            .map_err(|e| format!("Failed to get diff for {file_path}: {e}"))?;
+/// Resolve the refs of validated options into a `DiffSpec`
+pub fn resolve_diff_spec(path: &Path, options: &GitDiffOptions) -> Result<DiffSpec, String> {
+    let commits = match &options.base {
+        None => None,
+        Some(base) => {
+            let resolve = |rev: &str| {
+                let rev = if rev.is_empty() { "HEAD" } else { rev };
+                resolve_commit(path, rev).ok_or_else(|| format!("Commit not found: {rev}"))
+            };
+            if let Some((from, to)) = base.split_once("...") {
+                let to = resolve(to)?;
+                let output = run_git(path, &["merge-base", &resolve(from)?, &to])
+                    .map_err(|_| format!("No merge base for {base}"))?;
+                let merge_base = String::from_utf8_lossy(&output).trim().to_string();
+                Some((merge_base, Some(to)))
+            } else if let Some((from, to)) = base.split_once("..") {
+                Some((resolve(from)?, Some(resolve(to)?)))
+            } else {
+                let target = options.target.as_deref().map(resolve).transpose()?;
+                Some((resolve(base)?, target))
+            }
+        }
+    };
 
-        let diff = String::from_utf8_lossy(&diff_output.stdout).to_string();
-        let diff_size = diff.len();
+    let mut flags = Vec::new();
+    if let Some(lines) = options.context_lines {
+        flags.push(format!("-U{lines}"));
+    }
+    match options.whitespace {
+        WhitespaceMode::Show => {}
+        WhitespaceMode::IgnoreAll => flags.push("-w".to_string()),
+        WhitespaceMode::IgnoreChange => flags.push("-b".to_string()),
+        WhitespaceMode::IgnoreAtEol => flags.push("--ignore-space-at-eol".to_string()),
+    }
+    match options.rename_threshold {
+        None => {}
+        Some(0) => flags.push("--no-renames".to_string()),
+        Some(threshold) => flags.push(format!("-M{threshold}%")),
+    }
+    if options.word_diff {
+        flags.push("--word-diff=porcelain".to_string());
+    }
 
-        if total_size + diff_size > MAX_DIFF_SIZE {
-            truncated = true;
-            truncated_files.push(file_path);
-        } else {
-            total_size += diff_size;
-            files.push(GitFileDiff {
-                path: file_path,
-                diff,
-            });
+    Ok(DiffSpec { commits, flags })
+}
+
+impl SnapshotFile {
+    fn new(path: String, orig_path: Option<String>, status: &str, staged: bool, diff: String) -> Self {
+        let (_, hunks) = split_hunks(&diff);
+        let (mut additions, mut deletions) = (0, 0);
+        for line in hunks.iter().flat_map(|hunk| hunk.lines().skip(1)) {
+            match line.as_bytes().first() {
+                Some(b'+') => additions += 1,
+                Some(b'-') => deletions += 1,
+                _ => {}
+            }
+        }
+        let binary = hunks.is_empty()
+            && diff
+                .lines()
+                .any(|line| line.starts_with("Binary files ") || line == "GIT binary patch");
+
+        Self {
+            info: GitDiffFileInfo {
+                path,
+                orig_path,
+                status: status.to_string(),
+                staged,
+                binary,
+                size: diff.len(),
+                hunks: hunks.len(),
+                additions,
+                deletions,
+                hash: diff_hash(&diff),
+            },
+            diff,
         }
     }
+}
 
-    // Get unstaged changed files
-    let unstaged_files_output = Command::new("git")
-        .args(["diff", "--name-only"])
-        .current_dir(path)
-        .output()
-        .map_err(|e| format!("Failed to list unstaged files: {e}"))?;
+/// Diff snapshots per session, reused while the working tree is unchanged. Up to
+/// `CACHED_SPECS` specs are kept per session, least recently used evicted first.
+///
+/// Validity is checked with one `git status`: its output carries HEAD and the index
+/// blob of every changed path, and the size and mtime of each listed worktree file are
+/// added, so staging, committing or editing any changed file invalidates the entry.
+/// Diffs between two commits never change and skip the check.
+#[derive(Default)]
+pub struct DiffCache {
+    /// Per session, most recently used last
+    entries: Mutex<HashMap<PathBuf, Vec<CachedDiff>>>,
+}
 
-    let unstaged_paths: Vec<String> = String::from_utf8_lossy(&unstaged_files_output.stdout)
-        .lines()
-        .filter(|s| !s.is_empty())
-        .map(|s| s.to_string())
-        .collect();
+struct CachedDiff {
+    spec: DiffSpec,
+    key: String,
+    snapshot: Arc<DiffSnapshot>,
+}
 
-    // Get diff for each unstaged file (if not already in staged list)
-    for file_path in unstaged_paths {
-        if files.iter().any(|d| d.path == file_path) {
-            continue;
+impl DiffCache {
+    /// Current diffs of the repository at `path`, computed only if it changed
+    pub fn snapshot(&self, path: &Path, spec: &DiffSpec) -> Result<Arc<DiffSnapshot>, String> {
+        let key = match &spec.commits {
+            Some((_, Some(_))) => String::new(),
+            _ => worktree_fingerprint(path)?,
+        };
+        if let Some(cached) = self.entries.lock().unwrap().get_mut(path) {
+            if let Some(index) = cached.iter().position(|c| c.spec == *spec && c.key == key) {
+                let hit = cached.remove(index);
+                let snapshot = hit.snapshot.clone();
+                cached.push(hit);
+                return Ok(snapshot);
+            }
         }
 
-        if truncated {
-            if !truncated_files.contains(&file_path) {
-                truncated_files.push(file_path);
+        let snapshot = Arc::new(collect_diffs(path, spec)?);
+        let size: usize = snapshot
+            .staged
+            .iter()
+            .chain(&snapshot.unstaged)
+            .map(|f| f.diff.len())
+            .sum();
+        let mut entries = self.entries.lock().unwrap();
+        let cached = entries.entry(path.to_path_buf()).or_default();
+        cached.retain(|c| c.spec != *spec);
+        if size <= MAX_CACHED_DIFF_SIZE {
+            if cached.len() >= CACHED_SPECS {
+                cached.remove(0);
             }
+            cached.push(CachedDiff {
+                spec: spec.clone(),
+                key,
+                snapshot: snapshot.clone(),
+            });
+        }
+        Ok(snapshot)
+    }
+}
+
+/// Run one `git diff` per side and split the output into per-file diffs
+fn collect_diffs(path: &Path, spec: &DiffSpec) -> Result<DiffSnapshot, String> {
+    let mut snapshot = DiffSnapshot::default();
+    let diff = |extra: &[&str]| {
+        let mut args = vec!["-c", "core.quotePath=false", "diff"];
+        args.extend(spec.flags.iter().map(String::as_str));
+        args.extend(extra);
+        run_git(path, &args)
+    };
+    let raw_patch = ["--patch-with-raw", "-z", "--no-color", "--no-ext-diff"];
+    // Word diffs do not mark each changed line with +/-, so counts come from --numstat
+    let word_diff = spec.flags.iter().any(|flag| flag.starts_with("--word-diff"));
+
+    if let Some((base, target)) = &spec.commits {
+        let mut args = raw_patch.to_vec();
+        args.push(base);
+        args.extend(target.as_deref());
+        for (file_path, orig_path, status, diff) in split_raw_patch(&diff(&args)?)? {
+            let status = status_char_to_string(status);
+            snapshot.unstaged.push(SnapshotFile::new(file_path, orig_path, &status, false, diff));
+        }
+        if word_diff {
+            let mut args = vec!["--numstat", "-z", base.as_str()];
+            args.extend(target.as_deref());
+            set_numstat(&mut snapshot.unstaged, &diff(&args)?);
+        }
+        return Ok(snapshot);
+    }
+
+    let staged = diff(&[&["--cached"][..], &raw_patch].concat())?;
+    for (file_path, orig_path, status, file_diff) in split_raw_patch(&staged)? {
+        if status == 'U' {
+            // Conflicted: show the combined worktree diff with conflict markers
+            let diff = diff(&["--no-color", "--no-ext-diff", "--", &file_path])?;
+            let diff = String::from_utf8_lossy(&diff).into_owned();
+            snapshot.unstaged.push(SnapshotFile::new(file_path, None, "unmerged", false, diff));
             continue;
         }
+        let status = status_char_to_string(status);
+        snapshot.staged.push(SnapshotFile::new(file_path, orig_path, &status, true, file_diff));
+    }
+
+    let unstaged = diff(&raw_patch)?;
+    for (file_path, orig_path, status, diff) in split_raw_patch(&unstaged)? {
+        let status = status_char_to_string(status);
+        snapshot.unstaged.push(SnapshotFile::new(file_path, orig_path, &status, false, diff));
+    }
+    if word_diff {
+        set_numstat(&mut snapshot.staged, &diff(&["--cached", "--numstat", "-z"])?);
+        set_numstat(&mut snapshot.unstaged, &diff(&["--numstat", "-z"])?);
+    }
 
-        let diff_output = Command::new("git")
-            .args(["diff", "--", &file_path])
-            .current_dir(path)
-            .output()
-            .map_err(|e| format!("Failed to get diff for {file_path}: {e}"))?;
+    let untracked = run_git(path, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+    for file_path in split_nul(&untracked) {
+        if let Some(diff) = untracked_diff(path, &file_path) {
+            snapshot.unstaged.push(SnapshotFile::new(file_path, None, "untracked", false, diff));
+        }
+    }
 
-        let diff = String::from_utf8_lossy(&diff_output.stdout).to_string();
-        let diff_size = diff.len();
+    Ok(snapshot)
+}
 
-        if total_size + diff_size > MAX_DIFF_SIZE {
-            truncated = true;
-            truncated_files.push(file_path);
-        } else {
-            total_size += diff_size;
-            files.push(GitFileDiff {
-                path: file_path,
-                diff,
-            });
+/// Replace the line counts of `files` with those from `--numstat -z` output
+fn set_numstat(files: &mut [SnapshotFile], output: &[u8]) {
+    let stats = parse_numstat_z(output);
+    for file in files {
+        if let Some(&(additions, deletions)) = stats.get(&file.info.path) {
+            file.info.additions = additions;
+            file.info.deletions = deletions;
         }
     }
+}
 
-    // Get untracked files and show their full content as additions
-    let untracked_output = Command::new("git")
-        .args(["ls-files", "--others", "--exclude-standard"])
-        .current_dir(path)
-        .output()
-        .map_err(|e| format!("Failed to list untracked files: {e}"))?;
+/// Split `git diff --patch-with-raw -z` output into (path, rename source, status, patch)
+/// per file.
+///
+/// The raw records (":modes shas status\0path\0[new path\0]") come first, then an
+/// empty record, then the patches in the same order. Combined ("::") records of
+/// conflicted files have no patch here and are skipped. Files whose changes are all
+/// hidden by whitespace options have no patch either and are dropped, which requires
+/// matching patch headers (produced with `core.quotePath=false`) to records.
+#[allow(clippy::type_complexity)]
+fn split_raw_patch(output: &[u8]) -> Result<Vec<(String, Option<String>, char, String)>, String> {
+    let mut records = Vec::new();
+    let mut fields = output.split(|&b| b == 0);
+    let mut consumed = 0;
+    while let Some(meta) = fields.next() {
+        consumed += meta.len() + 1;
+        if meta.is_empty() {
+            break;
+        }
+        let meta = String::from_utf8_lossy(meta);
+        let status = meta.rsplit(' ').next().and_then(|s| s.chars().next()).unwrap_or(' ');
+        let mut file_path = fields.next().unwrap_or_default();
+        consumed += file_path.len() + 1;
+        let mut orig_path = None;
+        if matches!(status, 'R' | 'C') {
+            orig_path = Some(String::from_utf8_lossy(file_path).into_owned());
+            file_path = fields.next().unwrap_or_default();
+            consumed += file_path.len() + 1;
+        }
+        if !meta.starts_with("::") {
+            records.push((String::from_utf8_lossy(file_path).into_owned(), orig_path, status));
+        }
+    }
 
-    let untracked_paths: Vec<String> = String::from_utf8_lossy(&untracked_output.stdout)
-        .lines()
-        .filter(|s| !s.is_empty())
-        .map(|s| s.to_string())
-        .collect();
+    let patch = String::from_utf8_lossy(output.get(consumed..).unwrap_or_default());
+    let mut chunks: Vec<String> = Vec::new();
+    for line in patch.split_inclusive('\n') {
+        let starts_file = ["diff --git ", "diff --cc ", "diff --combined ", "* Unmerged path "]
+            .iter()
+            .any(|prefix| line.starts_with(prefix));
+        match chunks.last_mut() {
+            Some(chunk) if !starts_file => chunk.push_str(line),
+            _ => chunks.push(line.to_string()),
+        }
+    }
+
+    if chunks.len() == records.len() {
+        return Ok(records
+            .into_iter()
+            .zip(chunks)
+            .map(|((file_path, orig_path, status), diff)| (file_path, orig_path, status, diff))
+            .collect());
+    }
+
+    let mut chunks = chunks.into_iter().peekable();
+    let mut files = Vec::new();
+    for (file_path, orig_path, status) in records {
+        let header = if status == 'U' {
+            format!("* Unmerged path {}", quote_path(&file_path))
+        } else {
+            let source = orig_path.as_deref().unwrap_or(&file_path);
+            format!(
+                "diff --git {} {}",
+                quote_path(&format!("a/{source}")),
+                quote_path(&format!("b/{file_path}"))
+            )
+        };
+        if chunks.peek().is_some_and(|chunk| chunk.lines().next() == Some(header.as_str())) {
+            files.push((file_path, orig_path, status, chunks.next().unwrap()));
+        }
+    }
+    if chunks.next().is_some() {
+        return Err("Unexpected git diff output: patches do not match files".to_string());
+    }
+    Ok(files)
+}
 
-    for file_path in untracked_paths {
-        if truncated {
-            if !truncated_files.contains(&file_path) {
-                truncated_files.push(file_path);
+/// Quote a path the way git does in patch headers with `core.quotePath=false`
+fn quote_path(path: &str) -> String {
+    if !path.chars().any(|c| c.is_ascii_control() || c == '"' || c == '\\') {
+        return path.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in path.chars() {
+        match c {
+            '\x07' => quoted.push_str("\\a"),
+            '\x08' => quoted.push_str("\\b"),
+            '\t' => quoted.push_str("\\t"),
+            '\n' => quoted.push_str("\\n"),
+            '\x0b' => quoted.push_str("\\v"),
+            '\x0c' => quoted.push_str("\\f"),
+            '\r' => quoted.push_str("\\r"),
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
             }
-            continue;
+            c if c.is_ascii_control() => quoted.push_str(&format!("\\{:03o}", c as u32)),
+            c => quoted.push(c),
         }
+    }
+    quoted.push('"');
+    quoted
+}
 
-        // Read file content and format as a diff (all additions)
-        let full_path = path.join(&file_path);
-        if let Ok(content) = std::fs::read_to_string(&full_path) {
-            let line_count = content.lines().count();
-            let diff_lines: Vec<String> = content
-                .lines()
-                .map(|line| format!("+{}", line))
-                .collect();
-
-            let diff = format!(
-                "diff --git a/{0} b/{0}\nnew file mode 100644\nindex 0000000..0000000\n--- /dev/null\n+++ b/{0}\n@@ -0,0 +1,{1} @@\n{2}",
-                file_path,
-                line_count,
-                diff_lines.join("\n")
-            );
-
-            let diff_size = diff.len();
-            if total_size + diff_size > MAX_DIFF_SIZE {
-                truncated = true;
-                truncated_files.push(file_path);
-            } else {
-                total_size += diff_size;
-                files.push(GitFileDiff {
-                    path: file_path,
-                    diff,
-                });
+/// Fingerprint of everything a diff snapshot depends on (see `DiffCache`)
+fn worktree_fingerprint(path: &Path) -> Result<String, String> {
+    let status = run_git(
+        path,
+        &["status", "--porcelain=v2", "-z", "--branch", "--untracked-files=all"],
+    )?;
+    let mut hasher = Sha256::new();
+    hasher.update(&status);
+
+    let mut records = status.split(|&b| b == 0);
+    while let Some(record) = records.next() {
+        let record = String::from_utf8_lossy(record);
+        let file_path = match record.get(..2) {
+            Some("1 ") => record.splitn(9, ' ').nth(8),
+            Some("2 ") => {
+                records.next();
+                record.splitn(10, ' ').nth(9)
             }
+            Some("u ") => record.splitn(11, ' ').nth(10),
+            Some("? ") => record.get(2..),
+            _ => None,
+        };
+        let Some(file_path) = file_path else {
+            continue;
+        };
+        if let Ok(meta) = std::fs::symlink_metadata(path.join(file_path)) {
+            let mtime = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .unwrap_or_default();
+            hasher.update(meta.len().to_le_bytes());
+            hasher.update(mtime.as_nanos().to_le_bytes());
         }
     }
+    Ok(short_hex(&hasher.finalize()))
+}
 
-    Ok(GitDiffResult {
-        files,
-        truncated,
-        truncated_files,
-    })
+/// Format an untracked file as a diff (all additions) the way git would: symlinks
+/// show their target path, and binary or oversized files get git's "Binary files"
+/// line instead of a patch. Oversized files are not read.
+fn untracked_diff(path: &Path, file_path: &str) -> Option<String> {
+    let full_path = path.join(file_path);
+    let metadata = std::fs::symlink_metadata(&full_path).ok()?;
+    let (mode, content) = if metadata.file_type().is_symlink() {
+        let target = std::fs::read_link(&full_path).ok()?;
+        ("120000", Some(target.into_os_string().into_vec()))
+    } else if metadata.is_file() {
+        let mode = if metadata.permissions().mode() & 0o111 != 0 { "100755" } else { "100644" };
+        let fits = metadata.len() <= MAX_DIFF_SIZE as u64;
+        (mode, if fits { Some(std::fs::read(&full_path).ok()?) } else { None })
+    } else {
+        return None;
+    };
+
+    let mut diff = format!("diff --git a/{0} b/{0}\nnew file mode {mode}\n", file_path);
+    if content.as_ref().is_some_and(|content| content.is_empty()) {
+        diff.push_str("index 0000000..e69de29\n");
+        return Some(diff);
+    }
+    diff.push_str("index 0000000..0000000\n");
+
+    let text = content
+        .as_deref()
+        .and_then(|content| std::str::from_utf8(content).ok())
+        .filter(|text| !text.contains('\0'));
+    let Some(text) = text else {
+        diff.push_str(&format!("Binary files /dev/null and b/{file_path} differ\n"));
+        return Some(diff);
+    };
+
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let range = match lines.len() {
+        1 => "1".to_string(),
+        n => format!("1,{n}"),
+    };
+    diff.push_str(&format!("--- /dev/null\n+++ b/{file_path}\n@@ -0,0 +{range} @@\n"));
+    for line in &lines {
+        diff.push('+');
+        diff.push_str(line);
+    }
+    if !text.ends_with('\n') {
+        diff.push_str("\n\\ No newline at end of file\n");
+    }
+    Some(diff)
+}
+
+/// Fingerprint of a file diff, used to check a client's view is still current
+pub fn diff_hash(diff: &str) -> String {
+    short_hex(&Sha256::digest(diff.as_bytes()))
+}
+
+fn short_hex(digest: &[u8]) -> String {
+    digest[..16].iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Current diff of one file: HEAD → index with `staged`, otherwise index → worktree
+/// (including untracked and conflicted files). Empty if the file has no such changes.
+pub fn file_diff(
+    path: &Path,
+    cache: &DiffCache,
+    spec: &DiffSpec,
+    file_path: &str,
+    staged: bool,
+) -> Result<String, String> {
+    let snapshot = cache.snapshot(path, spec)?;
+    let side = if staged { &snapshot.staged } else { &snapshot.unstaged };
+    Ok(side
+        .iter()
+        .find(|f| f.info.path == file_path)
+        .map(|f| f.diff.clone())
+        .unwrap_or_default())
 }
 
 /// Get git log with upstream status
@@ -300,140 +708,1892 @@ pub fn get_log(path: &Path, limit: u32) -> Result<GitLogResult, String> {
     })
 }
 
-// --- Internal helpers ---
+/// Check that pathspecs are relative paths that stay inside the session directory
+pub fn validate_paths(paths: &[String]) -> Result<(), String> {
+    if paths.is_empty() {
+        return Err("paths must not be empty".to_string());
+    }
+    for p in paths {
+        let path = Path::new(p);
+        if p.is_empty()
+            || path
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(format!("Invalid path: {p}"));
+        }
+    }
+    Ok(())
+}
 
-fn parse_porcelain_status(output: &[u8]) -> (Vec<GitFileStatus>, Vec<GitFileStatus>) {
-    let mut staged = Vec::new();
-    let mut unstaged = Vec::new();
+/// Stage changes (including deletions and new files) under `paths`
+pub fn stage(path: &Path, paths: &[String]) -> Result<(), String> {
+    let mut args = vec!["add", "-A", "--"];
+    args.extend(paths.iter().map(String::as_str));
+    run_git(path, &args).map(|_| ())
+}
 
-    let output_str = String::from_utf8_lossy(output);
-    for line in output_str.lines() {
-        if line.len() < 3 {
-            continue;
-        }
+/// Remove changes under `paths` from the index, keeping the working tree
+pub fn unstage(path: &Path, paths: &[String]) -> Result<(), String> {
+    // Before the first commit there is no HEAD to reset to
+    let mut args = if has_head(path) {
+        vec!["reset", "-q", "--"]
+    } else {
+        vec!["rm", "-r", "-q", "--cached", "--ignore-unmatch", "--"]
+    };
+    args.extend(paths.iter().map(String::as_str));
+    run_git(path, &args).map(|_| ())
+}
 
-        let index_status = line.chars().next().unwrap_or(' ');
-        let worktree_status = line.chars().nth(1).unwrap_or(' ');
-        let path = line[3..].to_string();
+/// Files `discard` would touch: (tracked files with unstaged changes, untracked files)
+pub fn discard_candidates(
+    path: &Path,
+    paths: &[String],
+    include_untracked: bool,
+) -> Result<(Vec<String>, Vec<String>), String> {
+    let mut args = vec!["diff", "--name-only", "--relative", "-z", "--"];
+    args.extend(paths.iter().map(String::as_str));
+    let tracked = split_nul(&run_git(path, &args)?);
 
-        // Staged changes (index column)
-        if index_status != ' ' && index_status != '?' {
-            staged.push(GitFileStatus {
-                path: path.clone(),
-                status: status_char_to_string(index_status),
-                additions: 0,
-                deletions: 0,
-            });
-        }
+    let untracked = if include_untracked {
+        let mut args = vec!["ls-files", "--others", "--exclude-standard", "-z", "--"];
+        args.extend(paths.iter().map(String::as_str));
+        split_nul(&run_git(path, &args)?)
+    } else {
+        Vec::new()
+    };
 
-        // Unstaged changes (worktree column) or untracked files
-        if worktree_status != ' ' {
-            unstaged.push(GitFileStatus {
-                path,
-                status: if index_status == '?' {
-                    "untracked".to_string()
-                } else {
-                    status_char_to_string(worktree_status)
-                },
-                additions: 0,
-                deletions: 0,
-            });
+    Ok((tracked, untracked))
+}
+
+/// Token identifying the current content of the files a discard would touch.
+/// Any change to those files between preview and confirmation changes the token.
+pub fn discard_token(path: &Path, tracked: &[String], untracked: &[String]) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    hasher.update(path.as_os_str().as_encoded_bytes());
+    if !tracked.is_empty() {
+        let mut args = vec!["diff", "--binary", "--relative", "--"];
+        args.extend(tracked.iter().map(String::as_str));
+        hasher.update(run_git(path, &args)?);
+    }
+    for file in untracked {
+        hasher.update(b"\0");
+        hasher.update(file.as_bytes());
+        if let Ok(meta) = std::fs::metadata(path.join(file)) {
+            let mtime = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .unwrap_or_default();
+            hasher.update(meta.len().to_le_bytes());
+            hasher.update(mtime.as_nanos().to_le_bytes());
         }
     }
+    Ok(short_hex(&hasher.finalize()))
+}
 
-    (staged, unstaged)
+/// Restore tracked files to their staged content and delete untracked files
+pub fn discard(path: &Path, tracked: &[String], untracked: &[String]) -> Result<(), String> {
+    if !tracked.is_empty() {
+        let mut args = vec!["checkout", "-q", "--"];
+        args.extend(tracked.iter().map(String::as_str));
+        run_git(path, &args)?;
+    }
+    if !untracked.is_empty() {
+        let mut args = vec!["clean", "-f", "-q", "--"];
+        args.extend(untracked.iter().map(String::as_str));
+        run_git(path, &args)?;
+    }
+    Ok(())
 }
 
-fn status_char_to_string(c: char) -> String {
-    match c {
-        'M' => "modified",
-        'A' => "added",
-        'D' => "deleted",
-        'R' => "renamed",
-        'C' => "copied",
-        'U' => "unmerged",
-        '?' => "untracked",
-        _ => "unknown",
+/// Whether the index differs from HEAD
+pub fn has_staged_changes(path: &Path) -> Result<bool, String> {
+    let output = git_command(path)
+        .args(["diff", "--cached", "--quiet"])
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    match output.status.code() {
+        Some(0) => Ok(false),
+        Some(1) => Ok(true),
+        _ => Err(git_error(&output)),
     }
-    .to_string()
 }
 
-fn parse_numstat(output: &[u8]) -> HashMap<String, (i32, i32)> {
-    let mut stats = HashMap::new();
-    let output_str = String::from_utf8_lossy(output);
+/// Create a commit from the index. An empty message is only allowed with `amend`.
+pub fn commit(
+    path: &Path,
+    message: &str,
+    amend: bool,
+    signoff: bool,
+    author: Option<&str>,
+) -> Result<GitCommitResult, String> {
+    let mut cmd = git_command(path);
+    cmd.arg("commit").arg("-q");
+    if amend {
+        cmd.arg("--amend");
+    }
+    if signoff {
+        cmd.arg("--signoff");
+    }
+    if let Some(author) = author {
+        cmd.arg(format!("--author={author}"));
+    }
+    if message.is_empty() {
+        cmd.arg("--no-edit");
+    } else {
+        // Message via stdin so it is never parsed as options
+        cmd.args(["-F", "-"]);
+    }
 
-    for line in output_str.lines() {
-        let parts: Vec<&str> = line.split('\t').collect();
-        if parts.len() >= 3 {
-            let additions = parts[0].parse::<i32>().unwrap_or(0);
-            let deletions = parts[1].parse::<i32>().unwrap_or(0);
-            let path = parts[2].to_string();
-            stats.insert(path, (additions, deletions));
-        }
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(message.as_bytes())
+            .map_err(|e| format!("Failed to write commit message: {e}"))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if !output.status.success() {
+        return Err(git_error(&output));
     }
 
-    stats
+    let head = run_git(path, &["log", "-1", "--format=%H%x00%s"])?;
+    let head = String::from_utf8_lossy(&head);
+    let (sha, summary) = head.trim_end().split_once('\0').unwrap_or((head.trim_end(), ""));
+    Ok(GitCommitResult {
+        sha: sha.to_string(),
+        summary: summary.to_string(),
+    })
 }
 
-fn get_upstream_status(path: &Path) -> (i32, i32, Option<String>) {
-    // Get upstream branch name
-    let upstream_output = Command::new("git")
-        .args(["rev-parse", "--abbrev-ref", "@{upstream}"])
-        .current_dir(path)
-        .output();
+/// Build a patch containing only the selected hunks (and lines) of a file diff.
+///
+/// For `reverse` (unstaging from a staged diff) the patch is later applied with
+/// `--reverse`, so unselected lines are kept as they are in the index: unselected
+/// additions become context and unselected removals are dropped. Forward patches do the
+/// opposite.
+pub fn build_partial_patch(
+    diff: &str,
+    selections: &[GitHunkSelection],
+    reverse: bool,
+) -> Result<String, String> {
+    let mut lines = diff.lines();
+    let mut patch = String::new();
+    let mut hunks: Vec<(&str, Vec<&str>)> = Vec::new();
 
-    let upstream = match upstream_output {
-        Ok(output) if output.status.success() => {
-            let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            if name.is_empty() {
-                None
-            } else {
-                Some(name)
-            }
+    for line in lines.by_ref() {
+        if line.starts_with("@@") {
+            hunks.push((line, Vec::new()));
+            break;
         }
-        _ => return (0, 0, None),
-    };
+        if line.starts_with("Binary files ") {
+            return Err("Binary files cannot be staged by hunk".to_string());
+        }
+        patch.push_str(line);
+        patch.push('\n');
+    }
+    for line in lines {
+        if line.starts_with("@@") {
+            hunks.push((line, Vec::new()));
+        } else if let Some((_, body)) = hunks.last_mut() {
+            body.push(line);
+        }
+    }
+    if hunks.is_empty() {
+        return Err("Diff has no hunks".to_string());
+    }
 
-    // Get ahead/behind counts
-    let count_output = Command::new("git")
-        .args(["rev-list", "--left-right", "--count", "HEAD...@{upstream}"])
-        .current_dir(path)
-        .output();
+    for selection in selections {
+        if selection.hunk >= hunks.len() {
+            return Err(format!("Hunk {} out of range (diff has {})", selection.hunk, hunks.len()));
+        }
+    }
 
-    let (ahead, behind) = match count_output {
-        Ok(output) if output.status.success() => {
-            let counts = String::from_utf8_lossy(&output.stdout);
-            let parts: Vec<&str> = counts.trim().split('\t').collect();
-            if parts.len() == 2 {
-                (
-                    parts[0].parse::<i32>().unwrap_or(0),
-                    parts[1].parse::<i32>().unwrap_or(0),
-                )
-            } else {
-                (0, 0)
+    // Shift of the side being rewritten relative to the side kept fixed
+    let mut delta: i64 = 0;
+    let mut included = 0;
+    for (index, (header, body)) in hunks.iter().enumerate() {
+        let Some(selection) = selections.iter().find(|s| s.hunk == index) else {
+            continue;
+        };
+        let (old_start, new_start, section) = parse_hunk_header(header)
+            .ok_or_else(|| format!("Malformed hunk header: {header}"))?;
+
+        let mut out = Vec::new();
+        let (mut old_count, mut new_count, mut changes) = (0i64, 0i64, 0);
+        let mut last_kept = false;
+        for (offset, line) in body.iter().enumerate() {
+            let selected = selection.lines.as_ref().is_none_or(|l| l.contains(&offset));
+            let (kept_as, line) = match line.chars().next() {
+                Some('\\') => {
+                    // "\ No newline at end of file" belongs to the previous line
+                    if last_kept {
+                        out.push(line.to_string());
+                    }
+                    continue;
+                }
+                Some('+') if selected => ('+', &line[1..]),
+                Some('-') if selected => ('-', &line[1..]),
+                Some('+') if reverse => (' ', &line[1..]),
+                Some('-') if !reverse => (' ', &line[1..]),
+                Some('+') | Some('-') => {
+                    last_kept = false;
+                    continue;
+                }
+                _ => (' ', line.get(1..).unwrap_or("")),
+            };
+            match kept_as {
+                '+' => new_count += 1,
+                '-' => old_count += 1,
+                _ => {
+                    old_count += 1;
+                    new_count += 1;
+                }
             }
+            if kept_as != ' ' {
+                changes += 1;
+            }
+            out.push(format!("{kept_as}{line}"));
+            last_kept = true;
+        }
+        if changes == 0 {
+            continue;
         }
-        _ => (0, 0),
-    };
 
-    (ahead, behind, upstream)
+        let (old_start, new_start) = if reverse {
+            (shifted_start(new_start, new_count, old_count, -delta), new_start)
+        } else {
+            (old_start, shifted_start(old_start, old_count, new_count, delta))
+        };
+        delta += new_count - old_count;
+        included += 1;
+
+        patch.push_str(&format!("@@ -{old_start},{old_count} +{new_start},{new_count} @@{section}\n"));
+        for line in out {
+            patch.push_str(&line);
+            patch.push('\n');
+        }
+    }
+
+    if included == 0 {
+        return Err("No changes selected".to_string());
+    }
+    Ok(patch)
 }
 
-fn parse_log_output(output: &[u8]) -> Vec<GitLogEntry> {
-    let mut entries = Vec::new();
-    let output_str = String::from_utf8_lossy(output);
+/// Apply a patch to the index only (`git apply --cached`)
+pub fn apply_to_index(path: &Path, patch: &str, reverse: bool) -> Result<(), String> {
+    let mut cmd = git_command(path);
+    cmd.args(["apply", "--cached"]);
+    if reverse {
+        cmd.arg("--reverse");
+    }
+    let mut child = cmd
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(patch.as_bytes())
+            .map_err(|e| format!("Failed to write patch: {e}"))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(git_error(&output))
+    }
+}
 
-    for line in output_str.lines() {
-        let parts: Vec<&str> = line.split('\0').collect();
-        if parts.len() >= 4 {
-            entries.push(GitLogEntry {
-                sha: parts[0].to_string(),
-                summary: parts[1].to_string(),
-                author: parts[2].to_string(),
-                timestamp: parts[3].parse::<i64>().unwrap_or(0),
-            });
+/// Whether an author override has the "Name <email>" form git requires
+/// (anything else makes git search history for a matching author)
+pub fn is_valid_author(author: &str) -> bool {
+    match author.split_once('<') {
+        Some((name, rest)) => {
+            !name.trim().is_empty() && rest.ends_with('>') && !rest[..rest.len() - 1].contains(['<', '>'])
+        }
+        None => false,
+    }
+}
+
+/// Resolve a revision to a full commit sha, or None if it names no commit
+pub fn resolve_commit(path: &Path, rev: &str) -> Option<String> {
+    let spec = format!("{rev}^{{commit}}");
+    let output = run_git(path, &["rev-parse", "--verify", "-q", "--end-of-options", &spec]).ok()?;
+    let sha = String::from_utf8_lossy(&output).trim().to_string();
+    (!sha.is_empty()).then_some(sha)
+}
+
+/// Metadata, file list and per-file diffs of a commit (against its first parent, or
+/// the empty tree for a root commit). Diffs past `MAX_DIFF_SIZE` are truncated.
+pub fn show_commit(path: &Path, sha: &str) -> Result<GitShowResult, String> {
+    let meta = run_git(
+        path,
+        &["show", "-s", "--format=%H%x00%P%x00%an%x00%ae%x00%at%x00%cn%x00%ce%x00%ct%x00%B", sha],
+    )?;
+    let meta = String::from_utf8_lossy(&meta);
+    let fields: Vec<&str> = meta.splitn(9, '\0').collect();
+    if fields.len() < 9 {
+        return Err(format!("Unexpected git show output for {sha}"));
+    }
+    let parents: Vec<String> = fields[1].split_whitespace().map(String::from).collect();
+
+    let base = match parents.first() {
+        Some(parent) => parent.clone(),
+        None => empty_tree(path)?,
+    };
+
+    let numstat = run_git(path, &["diff", "--numstat", "--no-renames", "-z", &base, sha])?;
+    let stats = parse_numstat_z(&numstat);
+    // One pass for every file's patch, split per file like the working tree diffs
+    let flags = ["--patch-with-raw", "-z", "--no-renames", "--no-color", "--no-ext-diff"];
+    let raw_patch = run_git(
+        path,
+        &[&["-c", "core.quotePath=false", "diff"][..], &flags, &[&base, sha]].concat(),
+    )?;
+
+    let mut files = Vec::new();
+    let mut diffs = Vec::new();
+    let mut truncated_files = Vec::new();
+    let mut total_size = 0usize;
+    let mut truncated = false;
+    for (file_path, _, status, diff) in split_raw_patch(&raw_patch)? {
+        let (additions, deletions) = stats.get(&file_path).copied().unwrap_or((0, 0));
+        files.push(GitFileStatus {
+            status: status_char_to_string(status),
+            path: file_path.clone(),
+            additions,
+            deletions,
+            ..Default::default()
+        });
+
+        if truncated || total_size + diff.len() > MAX_DIFF_SIZE {
+            truncated = true;
+            truncated_files.push(file_path);
+        } else {
+            total_size += diff.len();
+            diffs.push(GitFileDiff {
+                path: file_path,
+                diff,
+            });
+        }
+    }
+
+    Ok(GitShowResult {
+        sha: fields[0].to_string(),
+        parents,
+        author: fields[2].to_string(),
+        author_email: fields[3].to_string(),
+        author_timestamp: fields[4].parse().unwrap_or(0),
+        committer: fields[5].to_string(),
+        committer_email: fields[6].to_string(),
+        committer_timestamp: fields[7].parse().unwrap_or(0),
+        message: fields[8].trim_end().to_string(),
+        files,
+        diffs,
+        truncated,
+        truncated_files,
+    })
+}
+
+/// Local and remote branches with their last commit and upstream tracking
+pub fn list_branches(path: &Path) -> Result<GitBranchesResult, String> {
+    let output = run_git(
+        path,
+        &[
+            "for-each-ref",
+            "--format=%(refname)%00%(refname:short)%00%(objectname)%00%(subject)%00%(committerdate:unix)%00%(upstream:short)%00%(upstream:track,nobracket)%00%(HEAD)%00%(symref)",
+            "refs/heads",
+            "refs/remotes",
+        ],
+    )?;
+
+    let mut branches = Vec::new();
+    for line in String::from_utf8_lossy(&output).lines() {
+        let fields: Vec<&str> = line.split('\0').collect();
+        if fields.len() < 9 || !fields[8].is_empty() {
+            // Malformed, or a symbolic ref such as origin/HEAD
+            continue;
+        }
+        let (ahead, behind, upstream_gone) = parse_track(fields[6]);
+        branches.push(GitBranch {
+            name: fields[1].to_string(),
+            remote: fields[0].starts_with("refs/remotes/"),
+            current: fields[7] == "*",
+            sha: fields[2].to_string(),
+            summary: fields[3].to_string(),
+            timestamp: fields[4].parse().unwrap_or(0),
+            upstream: (!fields[5].is_empty()).then(|| fields[5].to_string()),
+            ahead,
+            behind,
+            upstream_gone,
+        });
+    }
+
+    // Also covers an unborn branch, which has no ref yet
+    let current = run_git(path, &["symbolic-ref", "-q", "--short", "HEAD"])
+        .ok()
+        .map(|o| String::from_utf8_lossy(&o).trim().to_string())
+        .filter(|name| !name.is_empty());
+
+    Ok(GitBranchesResult { current, branches })
+}
+
+/// Whether `name` is a valid new branch name
+pub fn is_valid_branch_name(path: &Path, name: &str) -> bool {
+    !name.starts_with('-') && run_git(path, &["check-ref-format", "--branch", name]).is_ok()
+}
+
+/// Whether a local branch exists
+pub fn branch_exists(path: &Path, name: &str) -> bool {
+    run_git(path, &["rev-parse", "--verify", "-q", &format!("refs/heads/{name}")]).is_ok()
+}
+
+/// Whether a remote branch ("origin/name") exists
+pub fn remote_branch_exists(path: &Path, name: &str) -> bool {
+    run_git(path, &["rev-parse", "--verify", "-q", &format!("refs/remotes/{name}")]).is_ok()
+}
+
+/// Remote branches named "<remote>/<name>" for a local branch name
+fn remote_branches_named(path: &Path, name: &str) -> Vec<String> {
+    list_branches(path)
+        .map(|result| {
+            result
+                .branches
+                .into_iter()
+                .filter(|b| b.remote && b.name.split_once('/').is_some_and(|(_, n)| n == name))
+                .map(|b| b.name)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Create a branch at `start_point` (default HEAD), optionally tracking it
+pub fn create_branch(path: &Path, name: &str, start_point: Option<&str>, track: bool) -> Result<(), String> {
+    let mut args = vec!["branch", if track { "--track" } else { "--no-track" }, name];
+    args.extend(start_point);
+    run_git(path, &args).map(|_| ())
+}
+
+/// Whether tracked files have staged or unstaged changes
+pub fn has_uncommitted_changes(path: &Path) -> Result<bool, String> {
+    let output = run_git(path, &["status", "--porcelain", "--untracked-files=no"])?;
+    Ok(!output.is_empty())
+}
+
+/// Switch to a local branch. A name that only exists on one remote creates a local
+/// branch tracking it.
+pub fn checkout(path: &Path, branch: &str) -> Result<(), String> {
+    if branch_exists(path, branch) {
+        return run_git(path, &["checkout", "-q", branch, "--"]).map(|_| ());
+    }
+    match remote_branches_named(path, branch).as_slice() {
+        [remote] => run_git(path, &["checkout", "-q", "-b", branch, "--track", remote]).map(|_| ()),
+        [] => Err(format!("Branch not found: {branch}")),
+        _ => Err(format!("Branch {branch} exists on several remotes; create it with a start point")),
+    }
+}
+
+/// Whether `checkout` can find a branch by this name
+pub fn checkout_target_exists(path: &Path, branch: &str) -> bool {
+    branch_exists(path, branch) || !remote_branches_named(path, branch).is_empty()
+}
+
+/// Delete a local branch (`-d`, or `-D` with `force`)
+pub fn delete_branch(path: &Path, name: &str, force: bool) -> Result<(), String> {
+    run_git(path, &["branch", if force { "-D" } else { "-d" }, name]).map(|_| ())
+}
+
+/// Merge, rebase, cherry-pick or revert in progress, if any
+pub fn operation_in_progress(path: &Path) -> Option<&'static str> {
+    [
+        ("rebase-merge", "rebase"),
+        ("rebase-apply", "rebase"),
+        ("MERGE_HEAD", "merge"),
+        ("CHERRY_PICK_HEAD", "cherry_pick"),
+        ("REVERT_HEAD", "revert"),
+    ]
+    .into_iter()
+    .find(|(marker, _)| {
+        run_git(path, &["rev-parse", "--git-path", marker])
+            .is_ok_and(|output| path.join(String::from_utf8_lossy(&output).trim()).exists())
+    })
+    .map(|(_, operation)| operation)
+}
+
+/// Conflicted files and their index stages (1 base, 2 ours, 3 theirs)
+fn unmerged_stages(path: &Path) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let output = run_git(path, &["ls-files", "--unmerged", "-z"])?;
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    for record in split_nul(&output) {
+        // "<mode> <sha> <stage>\t<path>"
+        let Some((meta, file_path)) = record.split_once('\t') else {
+            continue;
+        };
+        let Some(stage) = meta.rsplit(' ').next().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        match files.last_mut() {
+            Some((last, stages)) if *last == file_path => stages.push(stage),
+            _ => files.push((file_path.to_string(), vec![stage])),
+        }
+    }
+    Ok(files)
+}
+
+/// Index stages of a conflicted file (empty if it is not conflicted)
+pub fn conflict_stages(path: &Path, file_path: &str) -> Result<Vec<u8>, String> {
+    Ok(unmerged_stages(path)?
+        .into_iter()
+        .find(|(f, _)| f == file_path)
+        .map(|(_, stages)| stages)
+        .unwrap_or_default())
+}
+
+/// Conflicted files with each side's contents and the conflict regions of the
+/// working tree file
+pub fn get_conflicts(path: &Path) -> Result<GitConflictsResult, String> {
+    let mut files = Vec::new();
+    for (file_path, stages) in unmerged_stages(path)? {
+        let xy = match stages.as_slice() {
+            [1, 2, 3] => "UU",
+            [2, 3] => "AA",
+            [1, 2] => "UD",
+            [1, 3] => "DU",
+            [2] => "AU",
+            [3] => "UA",
+            _ => "DD",
+        };
+
+        // "./" makes the index path relative to the session directory
+        let blob = |stage: u8| {
+            stages.contains(&stage).then(|| {
+                run_git(path, &["cat-file", "blob", &format!(":{stage}:./{file_path}")])
+            })
+        };
+        let blobs = [blob(1), blob(2), blob(3)]
+            .into_iter()
+            .map(Option::transpose)
+            .collect::<Result<Vec<_>, _>>()?;
+        let worktree = std::fs::read(path.join(&file_path)).ok();
+        let omitted = blobs
+            .iter()
+            .flatten()
+            .chain(&worktree)
+            .any(|content| content.len() > MAX_DIFF_SIZE || content.contains(&0));
+
+        let text = |content: &Option<Vec<u8>>| {
+            content
+                .as_ref()
+                .filter(|_| !omitted)
+                .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+        };
+        let regions = match text(&worktree) {
+            Some(content) => parse_conflict_regions(&content),
+            None => Vec::new(),
+        };
+        files.push(GitConflictFile {
+            conflict: conflict_kind(xy).to_string(),
+            base: text(&blobs[0]),
+            ours: text(&blobs[1]),
+            theirs: text(&blobs[2]),
+            omitted,
+            regions,
+            path: file_path,
+        });
+    }
+
+    Ok(GitConflictsResult {
+        operation: operation_in_progress(path),
+        files,
+    })
+}
+
+/// Find "<<<<<<< ours / [||||||| base /] ======= / >>>>>>> theirs" regions
+fn parse_conflict_regions(content: &str) -> Vec<GitConflictRegion> {
+    fn marker<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+        let rest = line.trim_end_matches(['\n', '\r']).strip_prefix(marker)?;
+        match rest.strip_prefix(' ') {
+            Some(label) => Some(label),
+            None => rest.is_empty().then_some(""),
+        }
+    }
+
+    let mut regions = Vec::new();
+    let mut current: Option<(GitConflictRegion, u8)> = None;
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        let line_number = index + 1;
+        let Some((region, section)) = current.as_mut() else {
+            if let Some(label) = marker(line, "<<<<<<<") {
+                let region = GitConflictRegion {
+                    start_line: line_number,
+                    end_line: line_number,
+                    ours_label: label.to_string(),
+                    theirs_label: String::new(),
+                    ours: String::new(),
+                    base: None,
+                    theirs: String::new(),
+                };
+                current = Some((region, 0));
+            }
+            continue;
+        };
+
+        // Sections: 0 ours, 1 base, 2 theirs
+        match *section {
+            0 | 1 if marker(line, "=======").is_some_and(str::is_empty) => *section = 2,
+            0 if marker(line, "|||||||").is_some() => {
+                region.base = Some(String::new());
+                *section = 1;
+            }
+            0 => region.ours.push_str(line),
+            1 => region.base.get_or_insert_with(String::new).push_str(line),
+            _ => match marker(line, ">>>>>>>") {
+                Some(label) => {
+                    let (mut region, _) = current.take().unwrap();
+                    region.end_line = line_number;
+                    region.theirs_label = label.to_string();
+                    regions.push(region);
+                }
+                None => region.theirs.push_str(line),
+            },
+        }
+    }
+    regions
+}
+
+/// Resolve a conflicted file with one side or new content and mark it resolved. Taking
+/// a side that deleted the file removes it.
+pub fn resolve_conflict(
+    path: &Path,
+    file_path: &str,
+    stages: &[u8],
+    resolution: GitResolution,
+    content: Option<&str>,
+) -> Result<(), String> {
+    let (side, stage) = match resolution {
+        GitResolution::Content => {
+            let content = content.ok_or("content is required to resolve with content")?;
+            std::fs::write(path.join(file_path), content)
+                .map_err(|e| format!("Failed to write {file_path}: {e}"))?;
+            return run_git(path, &["add", "--", file_path]).map(|_| ());
+        }
+        GitResolution::Ours => ("--ours", 2),
+        GitResolution::Theirs => ("--theirs", 3),
+    };
+    if stages.contains(&stage) {
+        run_git(path, &["checkout", side, "--", file_path])?;
+        run_git(path, &["add", "--", file_path]).map(|_| ())
+    } else {
+        run_git(path, &["rm", "-q", "--", file_path]).map(|_| ())
+    }
+}
+
+/// `git merge --abort` or `git rebase --abort`
+pub fn abort_operation(path: &Path, operation: &str) -> Result<(), String> {
+    run_git(path, &[operation, "--abort"]).map(|_| ())
+}
+
+/// Continue a rebase whose conflicts are resolved. Stopping at the next conflicting
+/// commit is not an error: the new conflicts are returned.
+pub fn rebase_continue(path: &Path) -> Result<GitConflictsResult, String> {
+    let output = git_command(path)
+        .args(["rebase", "--continue"])
+        .env("GIT_EDITOR", "true")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    let conflicts = get_conflicts(path)?;
+    if output.status.success() || !conflicts.files.is_empty() {
+        Ok(conflicts)
+    } else {
+        Err(git_error(&output))
+    }
+}
+
+// --- Internal helpers ---
+
+/// Parse `%(upstream:track,nobracket)`: "ahead 1, behind 2" or "gone"
+fn parse_track(track: &str) -> (i32, i32, bool) {
+    if track == "gone" {
+        return (0, 0, true);
+    }
+    let (mut ahead, mut behind) = (0, 0);
+    for part in track.split(", ") {
+        if let Some(n) = part.strip_prefix("ahead ") {
+            ahead = n.parse().unwrap_or(0);
+        } else if let Some(n) = part.strip_prefix("behind ") {
+            behind = n.parse().unwrap_or(0);
+        }
+    }
+    (ahead, behind, false)
+}
+
+/// Id of the empty tree in this repository's hash format
+fn empty_tree(path: &Path) -> Result<String, String> {
+    let output = run_git(path, &["hash-object", "-t", "tree", "/dev/null"])?;
+    Ok(String::from_utf8_lossy(&output).trim().to_string())
+}
+
+/// Parse `--numstat -z` output: "add\tdel\tpath\0", or "add\tdel\t\0old\0new\0" for
+/// renames and copies (keyed by the new path). Binary files count as 0/0.
+fn parse_numstat_z(output: &[u8]) -> HashMap<String, (i32, i32)> {
+    let mut stats = HashMap::new();
+    let mut records = output
+        .split(|&b| b == 0)
+        .map(|r| String::from_utf8_lossy(r).into_owned());
+    while let Some(record) = records.next() {
+        let mut parts = record.splitn(3, '\t');
+        let (Some(additions), Some(deletions), Some(path)) = (parts.next(), parts.next(), parts.next()) else {
+            continue;
+        };
+        let path = if path.is_empty() {
+            records.next();
+            match records.next() {
+                Some(new_path) => new_path,
+                None => break,
+            }
+        } else {
+            path.to_string()
+        };
+        stats.insert(path, (additions.parse().unwrap_or(0), deletions.parse().unwrap_or(0)));
+    }
+    stats
+}
+
+/// Whether `name` is a configured remote
+pub fn remote_exists(path: &Path, name: &str) -> bool {
+    run_git(path, &["remote"])
+        .map(|output| String::from_utf8_lossy(&output).lines().any(|remote| remote == name))
+        .unwrap_or(false)
+}
+
+/// `git fetch` arguments
+pub fn fetch_args(remote: Option<&str>, prune: bool) -> Vec<String> {
+    let mut args = vec!["fetch".to_string(), "--progress".to_string()];
+    if prune {
+        args.push("--prune".to_string());
+    }
+    args.extend(remote.map(str::to_string));
+    args
+}
+
+/// `git pull` arguments; `branch` is only used with `remote`
+pub fn pull_args(remote: Option<&str>, branch: Option<&str>, mode: GitPullMode) -> Vec<String> {
+    let mode = match mode {
+        GitPullMode::FfOnly => "--ff-only",
+        GitPullMode::Rebase => "--rebase",
+    };
+    let mut args = vec!["pull".to_string(), "--progress".to_string(), mode.to_string()];
+    if let Some(remote) = remote {
+        args.push(remote.to_string());
+        args.extend(branch.map(str::to_string));
+    }
+    args
+}
+
+/// `git push` arguments; `branch` and `set_upstream` are only used with `remote`
+pub fn push_args(
+    remote: Option<&str>,
+    branch: Option<&str>,
+    set_upstream: bool,
+    force_with_lease: bool,
+) -> Vec<String> {
+    let mut args = vec!["push".to_string(), "--progress".to_string()];
+    if force_with_lease {
+        args.push("--force-with-lease".to_string());
+    }
+    if let Some(remote) = remote {
+        if set_upstream {
+            args.push("--set-upstream".to_string());
+        }
+        args.push(remote.to_string());
+        args.push(branch.unwrap_or("HEAD").to_string());
+    }
+    args
+}
+
+/// Run a network git command (fetch, pull, push), passing each progress line to
+/// `on_progress`. Prompts are disabled so missing credentials fail instead of hanging;
+/// the error is git's non-progress output.
+pub fn run_remote(path: &Path, args: &[String], mut on_progress: impl FnMut(GitProgress)) -> Result<(), String> {
+    let mut child = git_command(path)
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("LC_ALL", "C")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+
+    // Progress lines are rewritten in place with '\r'
+    let mut messages = Vec::new();
+    let mut handle_line = |line: &[u8]| {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim();
+        match parse_progress(line) {
+            Some(progress) => on_progress(progress),
+            None if !line.is_empty() => messages.push(line.to_string()),
+            None => {}
+        }
+    };
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let mut buffer = [0u8; 4096];
+    let mut line = Vec::new();
+    loop {
+        let count = match stderr.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(count) => count,
+        };
+        for &byte in &buffer[..count] {
+            if byte == b'\r' || byte == b'\n' {
+                handle_line(&line);
+                line.clear();
+            } else {
+                line.push(byte);
+            }
+        }
+    }
+    handle_line(&line);
+
+    let status = child.wait().map_err(|e| format!("Failed to run git: {e}"))?;
+    if status.success() {
+        Ok(())
+    } else if messages.is_empty() {
+        Err(format!("git {} failed ({status})", args[0]))
+    } else {
+        Err(messages.join("\n"))
+    }
+}
+
+/// Parse "[remote: ]Phase: 45% (9/20), ..." or "Phase: 12, done." progress lines
+fn parse_progress(line: &str) -> Option<GitProgress> {
+    let line = line.strip_prefix("remote: ").unwrap_or(line);
+    let (phase, rest) = line.split_once(": ")?;
+    let rest = rest.trim_start();
+    if !rest.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let number = |s: &str| -> Option<u64> {
+        s.trim().trim_end_matches([',', '.']).parse().ok()
+    };
+    let mut progress = GitProgress {
+        phase: phase.to_string(),
+        percent: None,
+        current: None,
+        total: None,
+    };
+    match rest.split_once('%') {
+        Some((percent, counts)) => {
+            progress.percent = percent.trim().parse().ok();
+            let counts = counts.trim_start().strip_prefix('(')?.split(')').next()?;
+            let (current, total) = counts.split_once('/')?;
+            progress.current = number(current);
+            progress.total = number(total);
+        }
+        None => progress.current = number(rest.split_whitespace().next()?),
+    }
+    (progress.percent.is_some() || progress.current.is_some()).then_some(progress)
+}
+
+/// Whether a failed network git command was refused for missing or bad credentials
+pub fn is_auth_failure(message: &str) -> bool {
+    let message = message.to_ascii_lowercase();
+    [
+        "authentication failed",
+        "could not read username",
+        "could not read password",
+        "terminal prompts disabled",
+        "permission denied (publickey",
+        "invalid username or password",
+        "http basic: access denied",
+        "the requested url returned error: 401",
+        "the requested url returned error: 403",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+/// A git command for `path` with pathspecs taken literally (no globs or magic)
+fn git_command(path: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(path).env("GIT_LITERAL_PATHSPECS", "1");
+    cmd
+}
+
+/// Run git and return stdout, or stderr as the error
+fn run_git(path: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = git_command(path)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(git_error(&output))
+    }
+}
+
+fn git_error(output: &Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match stderr.trim() {
+        "" => format!("git exited with {}", output.status),
+        message => message.to_string(),
+    }
+}
+
+/// Split a file diff into its header and hunks (each starting with its "@@" line)
+fn split_hunks(diff: &str) -> (&str, Vec<&str>) {
+    let mut starts = Vec::new();
+    let mut offset = 0;
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("@@") {
+            starts.push(offset);
+        }
+        offset += line.len();
+    }
+    let Some(&first) = starts.first() else {
+        return (diff, Vec::new());
+    };
+    let hunks = starts
+        .iter()
+        .zip(starts.iter().skip(1).chain(std::iter::once(&diff.len())))
+        .map(|(&start, &end)| &diff[start..end])
+        .collect();
+    (&diff[..first], hunks)
+}
+
+/// Parse "@@ -a[,b] +c[,d] @@ section" into (a, c, " section")
+fn parse_hunk_header(header: &str) -> Option<(i64, i64, &str)> {
+    let rest = header.strip_prefix("@@ -")?;
+    let (ranges, section) = rest.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let start = |range: &str| range.split(',').next()?.parse::<i64>().ok();
+    Some((start(old)?, start(new)?, section))
+}
+
+/// Start line of the rewritten side of a hunk, given the fixed side. An empty side
+/// names the line before the hunk, so it is one less than a non-empty one.
+fn shifted_start(fixed_start: i64, fixed_count: i64, other_count: i64, delta: i64) -> i64 {
+    let mut start = fixed_start + delta;
+    if fixed_count == 0 && other_count > 0 {
+        start += 1;
+    } else if other_count == 0 && fixed_count > 0 {
+        start -= 1;
+    }
+    start
+}
+
+fn has_head(path: &Path) -> bool {
+    run_git(path, &["rev-parse", "--verify", "-q", "HEAD"]).is_ok()
+}
+
+fn split_nul(output: &[u8]) -> Vec<String> {
+    output
+        .split(|&b| b == 0)
+        .filter(|s| !s.is_empty())
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+        .collect()
+}
+
+/// Parse `git status --porcelain=v2 -z` into (staged, unstaged) files. Unmerged paths
+/// are reported once, as unstaged "unmerged" entries with their conflict kind.
+fn parse_porcelain_v2(output: &[u8]) -> (Vec<GitFileStatus>, Vec<GitFileStatus>) {
+    let mut staged = Vec::new();
+    let mut unstaged = Vec::new();
+
+    let mut records = output
+        .split(|&b| b == 0)
+        .map(|r| String::from_utf8_lossy(r).into_owned());
+    while let Some(record) = records.next() {
+        let (kind, rest) = record.split_at(record.len().min(2));
+        match kind {
+            "1 " | "2 " => {
+                // XY sub mH mI mW hH hI [Xscore] path, then origPath for renames/copies
+                let renamed = kind == "2 ";
+                let field_count = if renamed { 9 } else { 8 };
+                let fields: Vec<&str> = rest.splitn(field_count, ' ').collect();
+                if fields.len() < field_count {
+                    continue;
+                }
+                let path = fields[field_count - 1];
+                let orig_path = if renamed { records.next() } else { None };
+                let mut xy = fields[0].chars();
+                let (index_status, worktree_status) = (xy.next().unwrap_or('.'), xy.next().unwrap_or('.'));
+                let submodule = parse_submodule(fields[1]);
+
+                if index_status != '.' {
+                    staged.push(GitFileStatus {
+                        path: path.to_string(),
+                        status: status_char_to_string(index_status),
+                        orig_path: orig_path.clone().filter(|_| matches!(index_status, 'R' | 'C')),
+                        submodule: submodule.clone(),
+                        ..Default::default()
+                    });
+                }
+                if worktree_status != '.' {
+                    unstaged.push(GitFileStatus {
+                        path: path.to_string(),
+                        status: status_char_to_string(worktree_status),
+                        orig_path: orig_path.filter(|_| matches!(worktree_status, 'R' | 'C')),
+                        submodule,
+                        ..Default::default()
+                    });
+                }
+            }
+            "u " => {
+                // XY sub m1 m2 m3 mW h1 h2 h3 path
+                let fields: Vec<&str> = rest.splitn(10, ' ').collect();
+                if fields.len() < 10 {
+                    continue;
+                }
+                unstaged.push(GitFileStatus {
+                    path: fields[9].to_string(),
+                    status: "unmerged".to_string(),
+                    submodule: parse_submodule(fields[1]),
+                    conflict: Some(conflict_kind(fields[0]).to_string()),
+                    ..Default::default()
+                });
+            }
+            "? " => unstaged.push(GitFileStatus {
+                path: rest.to_string(),
+                status: "untracked".to_string(),
+                ..Default::default()
+            }),
+            // Headers ("# branch.*"), ignored files and the trailing empty record
+            _ => {}
+        }
+    }
+
+    (staged, unstaged)
+}
+
+/// Porcelain v2 submodule field: "N..." for non-submodules, else "S<c><m><u>"
+fn parse_submodule(field: &str) -> Option<GitSubmoduleStatus> {
+    let flags = field.strip_prefix('S')?.as_bytes();
+    Some(GitSubmoduleStatus {
+        commit_changed: flags.first() == Some(&b'C'),
+        modified: flags.get(1) == Some(&b'M'),
+        untracked: flags.get(2) == Some(&b'U'),
+    })
+}
+
+/// Name of an unmerged XY state
+fn conflict_kind(xy: &str) -> &'static str {
+    match xy {
+        "DD" => "both_deleted",
+        "AU" => "added_by_us",
+        "UD" => "deleted_by_them",
+        "UA" => "added_by_them",
+        "DU" => "deleted_by_us",
+        "AA" => "both_added",
+        _ => "both_modified",
+    }
+}
+
+fn status_char_to_string(c: char) -> String {
+    match c {
+        'M' => "modified",
+        'A' => "added",
+        'D' => "deleted",
+        'R' => "renamed",
+        'C' => "copied",
+        'U' => "unmerged",
+        '?' => "untracked",
+        _ => "unknown",
+    }
+    .to_string()
+}
+
+fn get_upstream_status(path: &Path) -> (i32, i32, Option<String>) {
+    // Get upstream branch name
+    let upstream_output = Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "@{upstream}"])
+        .current_dir(path)
+        .output();
+
+    let upstream = match upstream_output {
+        Ok(output) if output.status.success() => {
+            let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if name.is_empty() {
+                None
+            } else {
+                Some(name)
+            }
+        }
+        _ => return (0, 0, None),
+    };
+
+    // Get ahead/behind counts
+    let count_output = Command::new("git")
+        .args(["rev-list", "--left-right", "--count", "HEAD...@{upstream}"])
+        .current_dir(path)
+        .output();
+
+    let (ahead, behind) = match count_output {
+        Ok(output) if output.status.success() => {
+            let counts = String::from_utf8_lossy(&output.stdout);
+            let parts: Vec<&str> = counts.trim().split('\t').collect();
+            if parts.len() == 2 {
+                (
+                    parts[0].parse::<i32>().unwrap_or(0),
+                    parts[1].parse::<i32>().unwrap_or(0),
+                )
+            } else {
+                (0, 0)
+            }
+        }
+        _ => (0, 0),
+    };
+
+    (ahead, behind, upstream)
+}
+
+fn parse_log_output(output: &[u8]) -> Vec<GitLogEntry> {
+    let mut entries = Vec::new();
+    let output_str = String::from_utf8_lossy(output);
+
+    for line in output_str.lines() {
+        let parts: Vec<&str> = line.split('\0').collect();
+        if parts.len() >= 4 {
+            entries.push(GitLogEntry {
+                sha: parts[0].to_string(),
+                summary: parts[1].to_string(),
+                author: parts[2].to_string(),
+                timestamp: parts[3].parse::<i64>().unwrap_or(0),
+            });
         }
     }
 
     entries
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Fresh repository with a committer identity configured
+    fn temp_repo(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("maestro-git-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        run_git(&dir, &["init", "-q"]).unwrap();
+        run_git(&dir, &["config", "user.name", "Test"]).unwrap();
+        run_git(&dir, &["config", "user.email", "test@example.com"]).unwrap();
+        dir
+    }
+
+    #[test]
+    fn stage_commit_and_discard() {
+        let repo = temp_repo("write");
+        fs::write(repo.join("a.txt"), "one\n").unwrap();
+
+        // Unstaging before the first commit has no HEAD to reset to
+        stage(&repo, &["a.txt".to_string()]).unwrap();
+        unstage(&repo, &["a.txt".to_string()]).unwrap();
+        assert!(!has_staged_changes(&repo).unwrap());
+
+        stage(&repo, &[".".to_string()]).unwrap();
+        assert!(has_staged_changes(&repo).unwrap());
+        let first = commit(&repo, "Add a", false, true, Some("Ada <ada@example.com>")).unwrap();
+        assert_eq!(first.summary, "Add a");
+        let body = run_git(&repo, &["log", "-1", "--format=%an%n%b"]).unwrap();
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.starts_with("Ada\n") && body.contains("Signed-off-by: Test"));
+
+        assert_eq!(commit(&repo, "", true, false, None).unwrap().summary, "Add a");
+        let amended = commit(&repo, "Add a.txt", true, false, None).unwrap();
+        assert_eq!(amended.summary, "Add a.txt");
+        assert_ne!(amended.sha, first.sha);
+
+        fs::write(repo.join("a.txt"), "two\n").unwrap();
+        fs::write(repo.join("new.txt"), "new\n").unwrap();
+        let all = [".".to_string()];
+        let (tracked, untracked) = discard_candidates(&repo, &all, true).unwrap();
+        assert_eq!(tracked, ["a.txt"]);
+        assert_eq!(untracked, ["new.txt"]);
+
+        let token = discard_token(&repo, &tracked, &untracked).unwrap();
+        fs::write(repo.join("a.txt"), "three\n").unwrap();
+        assert_ne!(discard_token(&repo, &tracked, &untracked).unwrap(), token);
+
+        discard(&repo, &tracked, &untracked).unwrap();
+        assert_eq!(fs::read_to_string(repo.join("a.txt")).unwrap(), "one\n");
+        assert!(!repo.join("new.txt").exists());
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn stage_and_unstage_hunks_and_lines() {
+        let repo = temp_repo("hunks");
+        let original: String = (1..=12).map(|n| format!("line {n}\n")).collect();
+        fs::write(repo.join("f.txt"), &original).unwrap();
+        stage(&repo, &["f.txt".to_string()]).unwrap();
+        commit(&repo, "Add f", false, false, None).unwrap();
+
+        let edited = original.replace("line 2\n", "LINE 2\n").replace("line 11\n", "LINE 11\n");
+        fs::write(repo.join("f.txt"), &edited).unwrap();
+        let staged_text = || String::from_utf8(run_git(&repo, &["show", ":f.txt"]).unwrap()).unwrap();
+        let select = |hunk, lines: Option<Vec<usize>>| vec![GitHunkSelection { hunk, lines }];
+        let cache = DiffCache::default();
+
+        // Second hunk only
+        let diff = file_diff(&repo, &cache, &DiffSpec::default(), "f.txt", false).unwrap();
+        let patch = build_partial_patch(&diff, &select(1, None), false).unwrap();
+        apply_to_index(&repo, &patch, false).unwrap();
+        assert_eq!(staged_text(), original.replace("line 11\n", "LINE 11\n"));
+
+        // Only the added line of the first hunk: body is " line 1", "-line 2", "+LINE 2", ...
+        let diff = file_diff(&repo, &cache, &DiffSpec::default(), "f.txt", false).unwrap();
+        let patch = build_partial_patch(&diff, &select(0, Some(vec![2])), false).unwrap();
+        apply_to_index(&repo, &patch, false).unwrap();
+        assert!(staged_text().starts_with("line 1\nline 2\nLINE 2\nline 3\n"));
+
+        // Unstage everything staged in the first hunk, keeping the second
+        let diff = file_diff(&repo, &cache, &DiffSpec::default(), "f.txt", true).unwrap();
+        let patch = build_partial_patch(&diff, &select(0, None), true).unwrap();
+        apply_to_index(&repo, &patch, true).unwrap();
+        assert_eq!(staged_text(), original.replace("line 11\n", "LINE 11\n"));
+
+        assert!(build_partial_patch(&diff, &select(5, None), false).is_err());
+        assert!(build_partial_patch(&diff, &select(0, Some(vec![0])), false).is_err());
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn hunks_staged_from_a_diff_with_options() {
+        let repo = temp_repo("hunk-options");
+        let original: String = (1..=12).map(|n| format!("line {n}\n")).collect();
+        fs::write(repo.join("f.txt"), &original).unwrap();
+        stage(&repo, &["f.txt".to_string()]).unwrap();
+        commit(&repo, "Add f", false, false, None).unwrap();
+
+        // One hunk with the default context, two with one line of context
+        let edited = original.replace("line 2\n", "LINE 2\n").replace("line 6\n", "LINE 6\n");
+        fs::write(repo.join("f.txt"), &edited).unwrap();
+        let options = GitDiffOptions {
+            context_lines: Some(1),
+            ..Default::default()
+        };
+        validate_hunk_options(&options).unwrap();
+        let spec = resolve_diff_spec(&repo, &options).unwrap();
+        let cache = DiffCache::default();
+
+        let page = get_file_diff(&repo, &cache, &spec, "f.txt", false, 0, None).unwrap().unwrap();
+        assert_eq!(page.total_hunks, 2);
+        let diff = file_diff(&repo, &cache, &spec, "f.txt", false).unwrap();
+        assert_eq!(diff_hash(&diff), page.hash);
+        assert_ne!(diff, file_diff(&repo, &cache, &DiffSpec::default(), "f.txt", false).unwrap());
+
+        let selection = [GitHunkSelection { hunk: 1, lines: None }];
+        let patch = build_partial_patch(&diff, &selection, false).unwrap();
+        apply_to_index(&repo, &patch, false).unwrap();
+        let staged = String::from_utf8(run_git(&repo, &["show", ":f.txt"]).unwrap()).unwrap();
+        assert_eq!(staged, original.replace("line 6\n", "LINE 6\n"));
+
+        for rejected in [
+            GitDiffOptions { word_diff: true, ..Default::default() },
+            GitDiffOptions { whitespace: WhitespaceMode::IgnoreAll, ..Default::default() },
+            GitDiffOptions { base: Some("HEAD".to_string()), ..Default::default() },
+            GitDiffOptions { context_lines: Some(0), ..Default::default() },
+        ] {
+            assert!(validate_hunk_options(&rejected).is_err());
+        }
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn show_commit_against_parent_and_root() {
+        let repo = temp_repo("show");
+        fs::write(repo.join("a.txt"), "one\n").unwrap();
+        stage(&repo, &["a.txt".to_string()]).unwrap();
+        let root = commit(&repo, "Root\n\nBody text", false, false, None).unwrap();
+
+        fs::write(repo.join("a.txt"), "one\ntwo\n").unwrap();
+        fs::write(repo.join("b.txt"), "b\n").unwrap();
+        stage(&repo, &[".".to_string()]).unwrap();
+        let second = commit(&repo, "Second", false, false, None).unwrap();
+
+        let head = resolve_commit(&repo, "HEAD").unwrap();
+        assert_eq!(head, second.sha);
+        assert!(resolve_commit(&repo, "no-such-branch").is_none());
+        assert!(resolve_commit(&repo, "--all").is_none());
+
+        let shown = show_commit(&repo, &head).unwrap();
+        assert_eq!(shown.parents, [root.sha.as_str()]);
+        assert_eq!(shown.committer_email, "test@example.com");
+        let files: Vec<_> = shown
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.status.as_str(), f.additions))
+            .collect();
+        assert_eq!(files, [("a.txt", "modified", 1), ("b.txt", "added", 1)]);
+        assert_eq!(shown.diffs.len(), 2);
+        assert!(shown.diffs[0].diff.contains("+two"));
+        assert!(shown.diffs[1].diff.starts_with("diff --git a/b.txt b/b.txt\nnew file mode"));
+
+        let shown = show_commit(&repo, &root.sha).unwrap();
+        assert!(shown.parents.is_empty());
+        assert_eq!(shown.message, "Root\n\nBody text");
+        assert_eq!(shown.files[0].status, "added");
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn branches_create_checkout_track_and_delete() {
+        let origin = temp_repo("branches-origin");
+        fs::write(origin.join("a.txt"), "a\n").unwrap();
+        stage(&origin, &["a.txt".to_string()]).unwrap();
+        commit(&origin, "Initial", false, false, None).unwrap();
+        create_branch(&origin, "feature", None, false).unwrap();
+        assert!(!is_valid_branch_name(&origin, "bad..name"));
+        assert!(!is_valid_branch_name(&origin, "-f"));
+
+        let clone = origin.with_file_name(format!("maestro-git-branches-clone-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&clone);
+        run_git(&origin, &["clone", "-q", origin.to_str().unwrap(), clone.to_str().unwrap()]).unwrap();
+
+        let listed = list_branches(&clone).unwrap();
+        let default_branch = listed.current.clone().unwrap();
+        let names: Vec<_> = listed.branches.iter().map(|b| (b.name.as_str(), b.remote)).collect();
+        assert!(names.contains(&("origin/feature", true)));
+        assert!(!names.iter().any(|(n, _)| n.ends_with("/HEAD")));
+
+        // A remote-only name creates a tracking branch
+        assert!(checkout_target_exists(&clone, "feature"));
+        checkout(&clone, "feature").unwrap();
+        let listed = list_branches(&clone).unwrap();
+        let feature = listed.branches.iter().find(|b| b.name == "feature").unwrap();
+        assert!(feature.current);
+        assert_eq!(feature.upstream.as_deref(), Some("origin/feature"));
+        assert_eq!((feature.ahead, feature.behind), (0, 0));
+
+        fs::write(clone.join("a.txt"), "changed\n").unwrap();
+        assert!(has_uncommitted_changes(&clone).unwrap());
+        checkout(&clone, &default_branch).unwrap();
+        assert!(delete_branch(&clone, "missing", false).is_err());
+        delete_branch(&clone, "feature", false).unwrap();
+        assert!(!branch_exists(&clone, "feature"));
+        assert!(checkout(&clone, "nope").is_err());
+
+        assert_eq!(parse_track("ahead 2, behind 1"), (2, 1, false));
+        assert_eq!(parse_track("gone"), (0, 0, true));
+
+        let _ = fs::remove_dir_all(&origin);
+        let _ = fs::remove_dir_all(&clone);
+    }
+
+    #[test]
+    fn porcelain_v2_renames_spaces_conflicts_and_submodules() {
+        let output = concat!(
+            "# branch.oid abc\0# branch.head main\0",
+            "1 M. N... 100644 100644 100644 aaa bbb src/with space.rs\0",
+            "1 .M N... 100644 100644 100644 aaa bbb caf\u{e9}.txt\0",
+            "2 R. N... 100644 100644 100644 aaa bbb R100 new name.rs\0old name.rs\0",
+            "1 .M SC.U 160000 160000 160000 aaa bbb vendor/lib\0",
+            "u UD N... 100644 100644 000000 100644 aaa bbb ccc conflicted.rs\0",
+            "? notes -> todo.md\0",
+        );
+        let (staged, unstaged) = parse_porcelain_v2(output.as_bytes());
+
+        let staged: Vec<_> = staged
+            .iter()
+            .map(|f| (f.path.as_str(), f.status.as_str(), f.orig_path.as_deref()))
+            .collect();
+        assert_eq!(
+            staged,
+            [
+                ("src/with space.rs", "modified", None),
+                ("new name.rs", "renamed", Some("old name.rs")),
+            ]
+        );
+
+        assert_eq!(unstaged[0].path, "caf\u{e9}.txt");
+        let submodule = unstaged[1].submodule.as_ref().unwrap();
+        assert!(submodule.commit_changed && !submodule.modified && submodule.untracked);
+        assert_eq!(unstaged[2].status, "unmerged");
+        assert_eq!(unstaged[2].conflict.as_deref(), Some("deleted_by_them"));
+        assert_eq!((unstaged[3].path.as_str(), unstaged[3].status.as_str()), ("notes -> todo.md", "untracked"));
+
+        let stats = parse_numstat_z(b"3\t1\tsrc/with space.rs\x002\t0\t\x00old name.rs\x00new name.rs\x00-\t-\timg.png\x00");
+        assert_eq!(stats["src/with space.rs"], (3, 1));
+        assert_eq!(stats["new name.rs"], (2, 0));
+        assert_eq!(stats["img.png"], (0, 0));
+    }
+
+    #[test]
+    fn status_reports_renamed_and_unicode_paths() {
+        let repo = temp_repo("status");
+        fs::write(repo.join("old name.txt"), "one\ntwo\n").unwrap();
+        stage(&repo, &[".".to_string()]).unwrap();
+        commit(&repo, "Initial", false, false, None).unwrap();
+
+        run_git(&repo, &["mv", "old name.txt", "new n\u{e4}me.txt"]).unwrap();
+        fs::write(repo.join("new n\u{e4}me.txt"), "one\ntwo\nthree\n").unwrap();
+        stage(&repo, &[".".to_string()]).unwrap();
+
+        let status = get_status(&repo).unwrap();
+        let renamed = &status.staged_files[0];
+        assert_eq!(renamed.path, "new n\u{e4}me.txt");
+        assert_eq!(renamed.orig_path.as_deref(), Some("old name.txt"));
+        assert_eq!((renamed.additions, renamed.deletions), (1, 0));
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn diff_is_split_per_file_and_cached() {
+        let repo = temp_repo("diff");
+        fs::write(repo.join("a b.txt"), "one\n").unwrap();
+        fs::write(repo.join("old.txt"), "keep\nthis\ncontent\n").unwrap();
+        stage(&repo, &[".".to_string()]).unwrap();
+        commit(&repo, "Initial", false, false, None).unwrap();
+
+        run_git(&repo, &["mv", "old.txt", "n\u{e9}w.txt"]).unwrap();
+        fs::write(repo.join("a b.txt"), "one\ntwo\n").unwrap();
+        stage(&repo, &["a b.txt".to_string()]).unwrap();
+        fs::write(repo.join("a b.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(repo.join("untracked.txt"), "u\n").unwrap();
+
+        let cache = DiffCache::default();
+        let snapshot = cache.snapshot(&repo, &DiffSpec::default()).unwrap();
+        let staged: Vec<_> = snapshot.staged.iter().map(|f| f.info.path.as_str()).collect();
+        let unstaged: Vec<_> = snapshot.unstaged.iter().map(|f| f.info.path.as_str()).collect();
+        assert_eq!(staged, ["a b.txt", "n\u{e9}w.txt"]);
+        assert_eq!(unstaged, ["a b.txt", "untracked.txt"]);
+        assert!(snapshot.staged[0].diff.starts_with("diff --git") && snapshot.staged[0].diff.contains("+two\n"));
+        assert!(!snapshot.staged[0].diff.contains("rename"));
+        assert!(snapshot.staged[1].diff.contains("rename from old.txt"));
+        assert!(snapshot.unstaged[0].diff.contains("+three\n"));
+
+        // Mixed files are listed once per side
+        let result = get_diff(&repo, &cache, &DiffSpec::default()).unwrap();
+        let files: Vec<_> = result
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.status.as_str(), f.staged))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                ("a b.txt", "modified", true),
+                ("n\u{e9}w.txt", "renamed", true),
+                ("a b.txt", "modified", false),
+                ("untracked.txt", "untracked", false),
+            ]
+        );
+        assert_eq!(result.files[1].orig_path.as_deref(), Some("old.txt"));
+        assert_eq!((result.files[0].additions, result.files[0].deletions), (1, 0));
+        assert_eq!(result.files[0].size, snapshot.staged[0].diff.len());
+
+        assert!(Arc::ptr_eq(&snapshot, &cache.snapshot(&repo, &DiffSpec::default()).unwrap()));
+
+        // Clients with different options do not evict each other
+        let options = GitDiffOptions {
+            context_lines: Some(1),
+            ..Default::default()
+        };
+        let context = resolve_diff_spec(&repo, &options).unwrap();
+        let with_context = cache.snapshot(&repo, &context).unwrap();
+        assert!(Arc::ptr_eq(&snapshot, &cache.snapshot(&repo, &DiffSpec::default()).unwrap()));
+        assert!(Arc::ptr_eq(&with_context, &cache.snapshot(&repo, &context).unwrap()));
+
+        fs::write(repo.join("untracked.txt"), "changed size\n").unwrap();
+        let refreshed = cache.snapshot(&repo, &DiffSpec::default()).unwrap();
+        assert!(!Arc::ptr_eq(&snapshot, &refreshed));
+        assert!(refreshed.unstaged[1].diff.contains("+changed size"));
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn untracked_binary_empty_and_unterminated_files() {
+        let repo = temp_repo("untracked-kinds");
+        fs::write(repo.join("image.bin"), [0x89u8, b'P', b'N', b'G', 0, 0xff]).unwrap();
+        fs::write(repo.join("empty.txt"), "").unwrap();
+        fs::write(repo.join("tail.txt"), "one\r\ntwo").unwrap();
+
+        let cache = DiffCache::default();
+        let list = get_diff(&repo, &cache, &DiffSpec::default()).unwrap();
+        let image = list.files.iter().find(|f| f.path == "image.bin").unwrap();
+        assert!(image.binary && image.hunks == 0);
+        let empty = list.files.iter().find(|f| f.path == "empty.txt").unwrap();
+        assert!(!empty.binary && empty.hunks == 0 && empty.additions == 0);
+        let tail = list.files.iter().find(|f| f.path == "tail.txt").unwrap();
+        assert_eq!((tail.additions, tail.deletions), (2, 0));
+
+        let diff = file_diff(&repo, &cache, &DiffSpec::default(), "tail.txt", false).unwrap();
+        assert!(diff.ends_with("@@ -0,0 +1,2 @@\n+one\r\n+two\n\\ No newline at end of file\n"));
+
+        // The patch applies as-is
+        fs::remove_file(repo.join("tail.txt")).unwrap();
+        let mut apply = git_command(&repo)
+            .args(["apply", "-"])
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        apply.stdin.take().unwrap().write_all(diff.as_bytes()).unwrap();
+        assert!(apply.wait().unwrap().success());
+        assert_eq!(fs::read(repo.join("tail.txt")).unwrap(), b"one\r\ntwo");
+
+        // Symlinks show their target path, never the target's contents
+        let secret = repo.with_file_name(format!("maestro-git-secret-{}", std::process::id()));
+        fs::write(&secret, "password\n").unwrap();
+        std::os::unix::fs::symlink(&secret, repo.join("link")).unwrap();
+        fs::write(repo.join("run.sh"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(repo.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::File::create(repo.join("huge.log")).unwrap().set_len(MAX_DIFF_SIZE as u64 + 1).unwrap();
+
+        let cache = DiffCache::default();
+        let link = file_diff(&repo, &cache, &DiffSpec::default(), "link", false).unwrap();
+        assert!(link.contains("new file mode 120000\n"));
+        assert!(link.contains(&format!("+{}\n\\ No newline", secret.display())));
+        assert!(!link.contains("password"));
+        let script = file_diff(&repo, &cache, &DiffSpec::default(), "run.sh", false).unwrap();
+        assert!(script.contains("new file mode 100755\n"));
+        let list = get_diff(&repo, &cache, &DiffSpec::default()).unwrap();
+        let huge = list.files.iter().find(|f| f.path == "huge.log").unwrap();
+        assert!(huge.binary && huge.hunks == 0);
+
+        let _ = fs::remove_file(&secret);
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn file_diffs_are_paged_by_hunk() {
+        let repo = temp_repo("diff-pages");
+        let lines: Vec<String> = (1..=60).map(|n| format!("line {n}\n")).collect();
+        fs::write(repo.join("big.txt"), lines.concat()).unwrap();
+        fs::write(repo.join("image.bin"), [0u8, 1, 2, 0]).unwrap();
+        stage(&repo, &[".".to_string()]).unwrap();
+        commit(&repo, "Initial", false, false, None).unwrap();
+
+        let mut edited = lines.clone();
+        for n in [2, 20, 40, 58] {
+            edited[n] = format!("changed {n}\n");
+        }
+        fs::write(repo.join("big.txt"), edited.concat()).unwrap();
+        fs::write(repo.join("image.bin"), [0u8, 3, 0]).unwrap();
+
+        let cache = DiffCache::default();
+        let list = get_diff(&repo, &cache, &DiffSpec::default()).unwrap();
+        let big = list.files.iter().find(|f| f.path == "big.txt").unwrap();
+        assert_eq!((big.hunks, big.additions, big.deletions, big.binary), (4, 4, 4, false));
+        let image = list.files.iter().find(|f| f.path == "image.bin").unwrap();
+        assert!(image.binary && image.hunks == 0);
+
+        let first = get_file_diff(&repo, &cache, &DiffSpec::default(), "big.txt", false, 0, Some(3)).unwrap().unwrap();
+        assert_eq!((first.total_hunks, first.next_hunk_offset), (4, Some(3)));
+        assert_eq!(first.hash, big.hash);
+        assert!(first.diff.starts_with("diff --git") && first.diff.contains("+changed 40\n"));
+        assert!(!first.diff.contains("changed 58"));
+
+        let rest = get_file_diff(&repo, &cache, &DiffSpec::default(), "big.txt", false, 3, None).unwrap().unwrap();
+        assert_eq!(rest.next_hunk_offset, None);
+        assert!(rest.diff.starts_with("diff --git") && rest.diff.contains("+changed 58\n"));
+        assert!(!rest.diff.contains("changed 2\n"));
+
+        // The full diff is still available for hunk staging
+        let all = get_file_diff(&repo, &cache, &DiffSpec::default(), "big.txt", false, 0, None).unwrap().unwrap();
+        assert_eq!(all.diff, file_diff(&repo, &cache, &DiffSpec::default(), "big.txt", false).unwrap());
+        assert!(get_file_diff(&repo, &cache, &DiffSpec::default(), "big.txt", true, 0, None).unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn diff_options_select_refs_context_whitespace_and_words() {
+        let repo = temp_repo("diff-options");
+        let lines: Vec<String> = (1..=20).map(|n| format!("line {n}\n")).collect();
+        fs::write(repo.join("a.txt"), lines.concat()).unwrap();
+        fs::write(repo.join("tab\there.txt"), "x y\n").unwrap();
+        fs::write(repo.join("old.txt"), "same\ncontent\nhere\n").unwrap();
+        stage(&repo, &[".".to_string()]).unwrap();
+        commit(&repo, "Initial", false, false, None).unwrap();
+        run_git(&repo, &["branch", "main-base"]).unwrap();
+
+        let mut edited = lines.clone();
+        edited[10] = "line eleven\n".to_string();
+        fs::write(repo.join("a.txt"), edited.concat()).unwrap();
+        fs::write(repo.join("tab\there.txt"), "x  y\n").unwrap();
+        run_git(&repo, &["mv", "old.txt", "new.txt"]).unwrap();
+        stage(&repo, &[".".to_string()]).unwrap();
+        commit(&repo, "Agent work", false, false, None).unwrap();
+        fs::write(repo.join("a.txt"), lines.concat()).unwrap();
+
+        let cache = DiffCache::default();
+        let spec = |options: GitDiffOptions| resolve_diff_spec(&repo, &options).unwrap();
+        let branch = spec(GitDiffOptions {
+            base: Some("main-base...HEAD".to_string()),
+            ..Default::default()
+        });
+        let files = get_diff(&repo, &cache, &branch).unwrap().files;
+        let paths: Vec<_> = files.iter().map(|f| (f.path.as_str(), f.staged)).collect();
+        assert_eq!(paths, [("a.txt", false), ("new.txt", false), ("tab\there.txt", false)]);
+        let page = get_file_diff(&repo, &cache, &branch, "a.txt", true, 0, None).unwrap().unwrap();
+        assert!(page.diff.contains("+line eleven\n") && page.diff.contains(" line 8\n"));
+        assert!(!page.diff.contains("\n line 7\n"));
+
+        // Against the worktree the edit to a.txt is reverted
+        let worktree = spec(GitDiffOptions {
+            base: Some("main-base".to_string()),
+            ..Default::default()
+        });
+        let files = get_diff(&repo, &cache, &worktree).unwrap().files;
+        assert!(!files.iter().any(|f| f.path == "a.txt"));
+
+        let tuned = spec(GitDiffOptions {
+            base: Some("main-base".to_string()),
+            target: Some("HEAD".to_string()),
+            context_lines: Some(0),
+            whitespace: WhitespaceMode::IgnoreAll,
+            rename_threshold: Some(0),
+            word_diff: true,
+        });
+        let files = get_diff(&repo, &cache, &tuned).unwrap().files;
+        let paths: Vec<_> = files.iter().map(|f| (f.path.as_str(), f.status.as_str())).collect();
+        assert_eq!(paths, [("a.txt", "modified"), ("new.txt", "added"), ("old.txt", "deleted")]);
+        let page = get_file_diff(&repo, &cache, &tuned, "a.txt", false, 0, None).unwrap().unwrap();
+        assert!(page.diff.ends_with("@@ -11 +11 @@ line 10\n line \n-11\n+eleven\n~\n"));
+
+        // Line counts under word diff are per line, not per changed word
+        let mut reworded = lines.clone();
+        reworded[1] = "LINE 2 more\n".to_string();
+        fs::write(repo.join("a.txt"), reworded.concat()).unwrap();
+        let words = spec(GitDiffOptions {
+            word_diff: true,
+            ..Default::default()
+        });
+        let files = get_diff(&repo, &cache, &words).unwrap().files;
+        let a = files.iter().find(|f| f.path == "a.txt").unwrap();
+        assert_eq!((a.additions, a.deletions), (2, 2));
+
+        assert!(validate_diff_options(&GitDiffOptions {
+            target: Some("HEAD".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+        assert!(validate_diff_options(&GitDiffOptions {
+            rename_threshold: Some(101),
+            ..Default::default()
+        })
+        .is_err());
+        assert!(resolve_diff_spec(
+            &repo,
+            &GitDiffOptions {
+                base: Some("missing...HEAD".to_string()),
+                ..Default::default()
+            }
+        )
+        .is_err());
+        assert_eq!(quote_path("b/tab\there \"q\""), "\"b/tab\\there \\\"q\\\"\"");
+        assert_eq!(quote_path("b/n\u{e9}w file"), "b/n\u{e9}w file");
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn fetch_pull_and_push_against_bare_remote() {
+        let bare = std::env::temp_dir().join(format!("maestro-git-bare-{}.git", std::process::id()));
+        let _ = fs::remove_dir_all(&bare);
+        fs::create_dir_all(&bare).unwrap();
+        run_git(&bare, &["init", "-q", "--bare"]).unwrap();
+        let origin = bare.to_string_lossy().into_owned();
+
+        let repo = temp_repo("push");
+        fs::write(repo.join("a.txt"), "a\n").unwrap();
+        stage(&repo, &["a.txt".to_string()]).unwrap();
+        commit(&repo, "First", false, false, None).unwrap();
+        run_git(&repo, &["remote", "add", "origin", &origin]).unwrap();
+        assert!(remote_exists(&repo, "origin") && !remote_exists(&repo, "orig"));
+        run_remote(&repo, &push_args(Some("origin"), None, true, false), |_| {}).unwrap();
+        assert!(get_upstream_status(&repo).2.is_some());
+
+        let other = temp_repo("push-other");
+        run_git(&other, &["pull", "-q", &origin, "HEAD"]).unwrap();
+        run_git(&other, &["remote", "add", "origin", &origin]).unwrap();
+        fs::write(other.join("b.txt"), "b\n".repeat(1000)).unwrap();
+        stage(&other, &["b.txt".to_string()]).unwrap();
+        commit(&other, "Second", false, false, None).unwrap();
+        run_remote(&other, &push_args(Some("origin"), None, false, false), |_| {}).unwrap();
+
+        let mut progress = Vec::new();
+        run_remote(&repo, &fetch_args(None, true), |p| progress.push(p)).unwrap();
+        assert!(progress.iter().any(|p| p.percent == Some(100)), "{progress:?}");
+        assert_eq!(get_upstream_status(&repo).1, 1);
+        run_remote(&repo, &pull_args(None, None, GitPullMode::FfOnly), |_| {}).unwrap();
+        assert!(repo.join("b.txt").exists());
+
+        // Diverge: a plain push is rejected, a lease on a stale ref too
+        fs::write(repo.join("a.txt"), "mine\n").unwrap();
+        stage(&repo, &["a.txt".to_string()]).unwrap();
+        commit(&repo, "Mine", false, false, None).unwrap();
+        fs::write(other.join("b.txt"), "theirs\n").unwrap();
+        stage(&other, &["b.txt".to_string()]).unwrap();
+        commit(&other, "Theirs", false, false, None).unwrap();
+        run_remote(&other, &push_args(Some("origin"), None, false, false), |_| {}).unwrap();
+
+        let rejected = run_remote(&repo, &push_args(None, None, false, false), |_| {}).unwrap_err();
+        assert!(rejected.contains("rejected") && !is_auth_failure(&rejected), "{rejected}");
+        assert!(run_remote(&repo, &push_args(None, None, false, true), |_| {}).is_err());
+        run_remote(&repo, &fetch_args(Some("origin"), false), |_| {}).unwrap();
+        run_remote(&repo, &push_args(None, None, false, true), |_| {}).unwrap();
+
+        for dir in [&bare, &repo, &other] {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+
+    #[test]
+    fn remote_progress_and_auth_failures_are_recognised() {
+        let progress = parse_progress("remote: Counting objects:  45% (9/20), 1.20 MiB | 2.00 MiB/s").unwrap();
+        assert_eq!(progress.phase, "Counting objects");
+        assert_eq!((progress.percent, progress.current, progress.total), (Some(45), Some(9), Some(20)));
+        let progress = parse_progress("Enumerating objects: 12, done.").unwrap();
+        assert_eq!((progress.percent, progress.current), (None, Some(12)));
+        assert!(parse_progress("fatal: Authentication failed for 'https://host/repo.git/'").is_none());
+        assert!(parse_progress("remote: Total 3 (delta 0), reused 0 (delta 0)").is_none());
+
+        assert!(is_auth_failure("fatal: could not read Username for 'https://host': terminal prompts disabled"));
+        assert!(is_auth_failure("git@host: Permission denied (publickey).\nfatal: Could not read from remote repository."));
+        assert!(!is_auth_failure("! [rejected]        main -> main (fetch first)"));
+    }
+
+    /// Commit `content` to `file` on a new branch from the current HEAD, then return
+    fn commit_on_branch(repo: &Path, branch: &str, file: &str, content: &str) {
+        let current = run_git(repo, &["branch", "--show-current"]).unwrap();
+        let current = String::from_utf8_lossy(&current).trim().to_string();
+        run_git(repo, &["checkout", "-q", "-b", branch]).unwrap();
+        fs::write(repo.join(file), content).unwrap();
+        stage(repo, &[file.to_string()]).unwrap();
+        commit(repo, branch, false, false, None).unwrap();
+        run_git(repo, &["checkout", "-q", &current]).unwrap();
+    }
+
+    #[test]
+    fn merge_conflicts_are_listed_resolved_and_aborted() {
+        let repo = temp_repo("conflicts");
+        run_git(&repo, &["config", "merge.conflictStyle", "diff3"]).unwrap();
+        fs::write(repo.join("a.txt"), "top\nbase\nbottom\n").unwrap();
+        fs::write(repo.join("gone.txt"), "keep\n").unwrap();
+        stage(&repo, &[".".to_string()]).unwrap();
+        commit(&repo, "Base", false, false, None).unwrap();
+
+        commit_on_branch(&repo, "theirs", "a.txt", "top\ntheirs\nbottom\n");
+        run_git(&repo, &["checkout", "-q", "theirs"]).unwrap();
+        run_git(&repo, &["rm", "-q", "gone.txt"]).unwrap();
+        commit(&repo, "Remove", false, false, None).unwrap();
+        run_git(&repo, &["checkout", "-q", "-"]).unwrap();
+        fs::write(repo.join("a.txt"), "top\nours\nbottom\n").unwrap();
+        fs::write(repo.join("gone.txt"), "changed\n").unwrap();
+        stage(&repo, &[".".to_string()]).unwrap();
+        commit(&repo, "Ours", false, false, None).unwrap();
+
+        assert!(run_git(&repo, &["merge", "-q", "theirs"]).is_err());
+        let conflicts = get_conflicts(&repo).unwrap();
+        assert_eq!(conflicts.operation, Some("merge"));
+        let kinds: Vec<_> = conflicts.files.iter().map(|f| (f.path.as_str(), f.conflict.as_str())).collect();
+        assert_eq!(kinds, [("a.txt", "both_modified"), ("gone.txt", "deleted_by_them")]);
+
+        let a = &conflicts.files[0];
+        assert_eq!(a.base.as_deref(), Some("top\nbase\nbottom\n"));
+        assert_eq!(a.theirs.as_deref(), Some("top\ntheirs\nbottom\n"));
+        assert_eq!(a.regions.len(), 1);
+        let region = &a.regions[0];
+        assert_eq!((region.start_line, region.end_line), (2, 8));
+        assert_eq!((region.ours.as_str(), region.theirs.as_str()), ("ours\n", "theirs\n"));
+        assert_eq!(region.base.as_deref(), Some("base\n"));
+        assert_eq!((region.ours_label.as_str(), region.theirs_label.as_str()), ("HEAD", "theirs"));
+        assert!(conflicts.files[1].theirs.is_none());
+
+        resolve_conflict(&repo, "a.txt", &[1, 2, 3], GitResolution::Content, Some("merged\n")).unwrap();
+        resolve_conflict(&repo, "gone.txt", &[1, 2], GitResolution::Theirs, None).unwrap();
+        assert!(get_conflicts(&repo).unwrap().files.is_empty());
+        assert!(!repo.join("gone.txt").exists());
+        assert_eq!(fs::read_to_string(repo.join("a.txt")).unwrap(), "merged\n");
+
+        abort_operation(&repo, "merge").unwrap();
+        assert_eq!(operation_in_progress(&repo), None);
+        assert_eq!(fs::read_to_string(repo.join("a.txt")).unwrap(), "top\nours\nbottom\n");
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn rebase_continues_through_conflicting_commits() {
+        let repo = temp_repo("rebase");
+        fs::write(repo.join("a.txt"), "base\n").unwrap();
+        stage(&repo, &["a.txt".to_string()]).unwrap();
+        commit(&repo, "Base", false, false, None).unwrap();
+        commit_on_branch(&repo, "upstream", "a.txt", "upstream\n");
+
+        fs::write(repo.join("a.txt"), "one\n").unwrap();
+        stage(&repo, &["a.txt".to_string()]).unwrap();
+        commit(&repo, "One", false, false, None).unwrap();
+        fs::write(repo.join("a.txt"), "two\n").unwrap();
+        stage(&repo, &["a.txt".to_string()]).unwrap();
+        commit(&repo, "Two", false, false, None).unwrap();
+
+        assert!(run_git(&repo, &["rebase", "-q", "upstream"]).is_err());
+        let conflicts = get_conflicts(&repo).unwrap();
+        assert_eq!(conflicts.operation, Some("rebase"));
+        assert_eq!(conflicts.files[0].ours.as_deref(), Some("upstream\n"));
+        assert_eq!(conflicts.files[0].regions[0].base, None);
+
+        // Keeping upstream's line makes the second commit conflict too
+        resolve_conflict(&repo, "a.txt", &[1, 2, 3], GitResolution::Ours, None).unwrap();
+        let next = rebase_continue(&repo).unwrap();
+        assert_eq!(next.operation, Some("rebase"));
+        assert_eq!(next.files[0].theirs.as_deref(), Some("two\n"));
+
+        resolve_conflict(&repo, "a.txt", &[1, 2, 3], GitResolution::Theirs, None).unwrap();
+        let done = rebase_continue(&repo).unwrap();
+        assert!(done.operation.is_none() && done.files.is_empty());
+        assert_eq!(fs::read_to_string(repo.join("a.txt")).unwrap(), "two\n");
+
+        let _ = fs::remove_dir_all(&repo);
+    }
+
+    #[test]
+    fn conflict_regions_are_parsed() {
+        let content = "a\n<<<<<<< HEAD\nx\n=======\ny\n>>>>>>> branch\nb\n<<<<<<<\n=======\nz\n>>>>>>>\n<<<<<<< open\n";
+        let regions = parse_conflict_regions(content);
+        assert_eq!(regions.len(), 2);
+        assert_eq!((regions[0].start_line, regions[0].end_line), (2, 6));
+        assert_eq!((regions[0].ours.as_str(), regions[0].theirs.as_str()), ("x\n", "y\n"));
+        assert_eq!((regions[1].ours.as_str(), regions[1].theirs.as_str()), ("", "z\n"));
+        assert_eq!(regions[1].theirs_label, "");
+    }
+
+    #[test]
+    fn paths_and_authors_are_validated() {
+        assert!(validate_paths(&["src/main.rs".to_string(), ".".to_string()]).is_ok());
+        assert!(validate_paths(&[]).is_err());
+        assert!(validate_paths(&["../other".to_string()]).is_err());
+        assert!(validate_paths(&["/etc/passwd".to_string()]).is_err());
+
+        assert!(is_valid_author("Ada Lovelace <ada@example.com>"));
+        assert!(!is_valid_author("ada"));
+        assert!(!is_valid_author("<ada@example.com>"));
+    }
+}
@@ -0,0 +1,38 @@
+use crate::access::Principal;
+use crate::audit::{DEFAULT_QUERY_LIMIT, MAX_QUERY_LIMIT};
+use crate::protocol::*;
+use crate::state::DaemonState;
+
+pub async fn handle_query(request: &Request, state: &DaemonState, principal: &Principal) -> String {
+    let params: AuditQueryParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    let limit = params.limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT);
+
+    // Session-restricted tokens only see entries for their sessions
+    let principal = principal.clone();
+    let visible = move |e: &AuditEntry| match &e.session_id {
+        Some(path) => principal.allows_session(path),
+        None => principal.sessions.is_none(),
+    };
+
+    match state
+        .audit_log
+        .query(limit, params.method, params.session_id, visible)
+        .await
+    {
+        Ok(entries) => {
+            let resp = SuccessResponse::new(request.id, AuditQueryResult { entries });
+            serde_json::to_string(&resp).unwrap()
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INTERNAL_ERROR, e);
+            serde_json::to_string(&resp).unwrap()
+        }
+    }
+}
@@ -1,29 +1,64 @@
+use tracing::{info, warn};
+
+use crate::access::Principal;
+use crate::auth::AuthOutcome;
 use crate::protocol::*;
-use crate::state::DaemonState;
+use crate::state::{ClientId, DaemonState};
+
+pub async fn handle(request: &Request, state: &DaemonState, client_id: ClientId) -> String {
+    authenticate(request, state, client_id).await.1
+}
 
-pub async fn handle(request: &Request, state: &DaemonState) -> String {
+/// Handle an auth request, returning the outcome alongside the JSON response
+pub async fn authenticate(
+    request: &Request,
+    state: &DaemonState,
+    client_id: ClientId,
+) -> (AuthOutcome, String) {
     let params: AuthParams = match serde_json::from_value(request.params.clone()) {
         Ok(p) => p,
         Err(e) => {
             let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
-            return serde_json::to_string(&resp).unwrap();
+            return (AuthOutcome::Rejected, serde_json::to_string(&resp).unwrap());
         }
     };
 
-    // Check token
-    match &state.token {
-        Some(expected) if params.token == *expected => {
+    let outcome = if state.auth_required() {
+        let peer = state.client_peer(client_id).await.unwrap_or_default();
+        state.authenticate(&peer, &params.token)
+    } else {
+        // Auth not required, always succeed
+        AuthOutcome::Authenticated(Principal::unrestricted("anonymous"))
+    };
+
+    let response = match &outcome {
+        AuthOutcome::Authenticated(principal) => {
+            info!(
+                "Client {client_id} authenticated as '{}' ({})",
+                principal.name,
+                principal.scope.as_str()
+            );
+            state.set_client_principal(client_id, principal.clone()).await;
             let resp = SuccessResponse::new(request.id, AuthResult { ok: true });
             serde_json::to_string(&resp).unwrap()
         }
-        Some(_) => {
+        AuthOutcome::Rejected => {
             let resp = ErrorResponse::new(request.id, AUTH_FAILED, "Invalid token");
             serde_json::to_string(&resp).unwrap()
         }
-        None => {
-            // Auth not required, always succeed
-            let resp = SuccessResponse::new(request.id, AuthResult { ok: true });
+        AuthOutcome::LockedOut(remaining) => {
+            warn!("Client {client_id} auth refused: locked out");
+            let resp = ErrorResponse::new(
+                request.id,
+                AUTH_LOCKED,
+                format!(
+                    "Too many failed attempts; retry in {}s",
+                    remaining.as_secs().max(1)
+                ),
+            );
             serde_json::to_string(&resp).unwrap()
         }
-    }
+    };
+
+    (outcome, response)
 }
@@ -0,0 +1,297 @@
+use std::os::unix::process::ExitStatusExt;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::{Child, Command};
+use tokio::sync::{mpsc, Notify};
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use crate::protocol::*;
+use crate::state::{ClientId, ClientSender, DaemonState};
+use crate::terminal::Utf8Decoder;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const MAX_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_MAX_OUTPUT: usize = 4 * 1024 * 1024;
+const MAX_OUTPUT: usize = 32 * 1024 * 1024;
+const READ_CHUNK: usize = 64 * 1024;
+/// How long pipes may stay open after the process group is killed. A descendant
+/// that left the group (e.g. via `setsid`) can hold them open indefinitely.
+const KILL_GRACE: Duration = Duration::from_secs(2);
+
+/// Start a process. Replies with the exec id once spawned; output and the exit status
+/// arrive as `exec_output`/`exec_exited` events.
+pub async fn handle_exec(request: &Request, state: Arc<DaemonState>, client_id: ClientId) -> String {
+    let params: ExecParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if state.get_session(&params.session_id).await.is_none() {
+        let resp = ErrorResponse::new(
+            request.id,
+            SESSION_NOT_FOUND,
+            format!("Session not found: {}", params.session_id),
+        );
+        return serde_json::to_string(&resp).unwrap();
+    }
+
+    let Some((program, args)) = params.argv.split_first() else {
+        let resp = ErrorResponse::new(request.id, INVALID_PARAMS, "argv must not be empty");
+        return serde_json::to_string(&resp).unwrap();
+    };
+
+    let key = (client_id, params.exec_id.clone());
+    let mut execs = state.execs.write().await;
+    if execs.contains_key(&key) {
+        let resp = ErrorResponse::new(
+            request.id,
+            EXEC_EXISTS,
+            format!("Exec already running: {}", params.exec_id),
+        );
+        return serde_json::to_string(&resp).unwrap();
+    }
+
+    let timeout = params
+        .timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_TIMEOUT)
+        .min(MAX_TIMEOUT);
+    let max_output = params.max_output_bytes.unwrap_or(DEFAULT_MAX_OUTPUT).min(MAX_OUTPUT);
+
+    // Own process group so timeout/cancel also kill anything the command spawns
+    let child = Command::new(program)
+        .args(args)
+        .current_dir(&params.session_id)
+        .envs(&params.env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .kill_on_drop(true)
+        .spawn();
+
+    let child = match child {
+        Ok(c) => c,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, EXEC_ERROR, format!("Failed to spawn {program}: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    info!("Client {client_id} exec {}: {:?} in {}", params.exec_id, params.argv, params.session_id);
+
+    let cancel = Arc::new(Notify::new());
+    execs.insert(key, cancel.clone());
+    drop(execs);
+
+    tokio::spawn(run_exec(
+        child,
+        state,
+        client_id,
+        params.exec_id.clone(),
+        timeout,
+        max_output,
+        cancel,
+    ));
+
+    let resp = SuccessResponse::new(request.id, serde_json::json!({ "exec_id": params.exec_id }));
+    serde_json::to_string(&resp).unwrap()
+}
+
+pub async fn handle_cancel(request: &Request, state: &DaemonState, client_id: ClientId) -> String {
+    let params: ExecCancelParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    match state.execs.read().await.get(&(client_id, params.exec_id.clone())) {
+        Some(cancel) => {
+            cancel.notify_one();
+            let resp = SuccessResponse::new(request.id, serde_json::json!({}));
+            serde_json::to_string(&resp).unwrap()
+        }
+        None => {
+            let resp = ErrorResponse::new(
+                request.id,
+                EXEC_NOT_FOUND,
+                format!("Exec not found: {}", params.exec_id),
+            );
+            serde_json::to_string(&resp).unwrap()
+        }
+    }
+}
+
+async fn run_exec(
+    mut child: Child,
+    state: Arc<DaemonState>,
+    client_id: ClientId,
+    exec_id: String,
+    timeout: Duration,
+    max_output: usize,
+    cancel: Arc<Notify>,
+) {
+    let started = Instant::now();
+    let (tx, mut rx) = mpsc::channel::<(ExecStream, Vec<u8>)>(16);
+    let mut readers = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(tokio::spawn(read_stream(stdout, ExecStream::Stdout, tx.clone())));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(tokio::spawn(read_stream(stderr, ExecStream::Stderr, tx.clone())));
+    }
+    drop(tx);
+
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+
+    let mut stdout_decoder = Utf8Decoder::default();
+    let mut stderr_decoder = Utf8Decoder::default();
+    let mut output_bytes = 0;
+    let mut timed_out = false;
+    let mut cancelled = false;
+    let mut output_truncated = false;
+    let mut killed_at: Option<Instant> = None;
+
+    // Output waits for room in the client's queue; while it does, the pipe readers
+    // fill their channel and stop reading, which pauses the process.
+    let sender = state.clients.read().await.get(&client_id).cloned();
+    let mut pending: Option<String> = None;
+
+    // Runs until both pipes close (the process and anything holding them exited),
+    // or for at most KILL_GRACE once the process group has been killed
+    loop {
+        tokio::select! {
+            chunk = rx.recv(), if pending.is_none() => {
+                let Some((stream, mut bytes)) = chunk else {
+                    break;
+                };
+                if output_truncated {
+                    continue;
+                }
+                if output_bytes + bytes.len() > max_output {
+                    bytes.truncate(max_output - output_bytes);
+                    output_truncated = true;
+                    kill_group(&child);
+                    killed_at.get_or_insert_with(Instant::now);
+                }
+                output_bytes += bytes.len();
+
+                let decoder = match stream {
+                    ExecStream::Stdout => &mut stdout_decoder,
+                    ExecStream::Stderr => &mut stderr_decoder,
+                };
+                let data = decoder.decode(&bytes);
+                if data.is_empty() {
+                    continue;
+                }
+                let event = Event::new(
+                    EVENT_EXEC_OUTPUT,
+                    ExecOutputParams {
+                        exec_id: exec_id.clone(),
+                        stream,
+                        data,
+                    },
+                );
+                if sender.is_some() {
+                    pending = Some(serde_json::to_string(&event).unwrap());
+                }
+            }
+            permit = reserve(sender.as_ref()), if pending.is_some() => {
+                match permit {
+                    Some(permit) => permit.send(pending.take().unwrap()),
+                    None => pending = None,
+                }
+            }
+            _ = &mut deadline, if !timed_out => {
+                debug!("Exec {exec_id} timed out after {timeout:?}");
+                timed_out = true;
+                kill_group(&child);
+                killed_at.get_or_insert_with(Instant::now);
+            }
+            _ = cancel.notified(), if !cancelled => {
+                debug!("Exec {exec_id} cancelled");
+                cancelled = true;
+                kill_group(&child);
+                killed_at.get_or_insert_with(Instant::now);
+            }
+            _ = tokio::time::sleep_until(killed_at.unwrap_or_else(Instant::now) + KILL_GRACE),
+                if killed_at.is_some() =>
+            {
+                debug!("Exec {exec_id}: pipes still open {KILL_GRACE:?} after kill, abandoning them");
+                break;
+            }
+        }
+    }
+    // Closes the pipes if a surviving descendant still holds them
+    for reader in readers {
+        reader.abort();
+    }
+
+    let (exit_code, signal) = match child.wait().await {
+        Ok(status) => (status.code(), status.signal()),
+        Err(_) => (None, None),
+    };
+
+    state.execs.write().await.remove(&(client_id, exec_id.clone()));
+
+    let event = Event::new(
+        EVENT_EXEC_EXITED,
+        ExecExitedParams {
+            exec_id,
+            exit_code,
+            signal,
+            timed_out,
+            cancelled,
+            output_truncated,
+            duration_ms: started.elapsed().as_millis() as u64,
+        },
+    );
+    // Same queue as the output so the exit event arrives after it
+    let permit = reserve(sender.as_ref()).await;
+    if let Some(permit) = permit {
+        permit.send(serde_json::to_string(&event).unwrap());
+    }
+}
+
+async fn reserve(sender: Option<&ClientSender>) -> Option<mpsc::Permit<'_, String>> {
+    sender?.reserve().await
+}
+
+async fn read_stream<R: AsyncRead + Unpin>(
+    mut reader: R,
+    stream: ExecStream,
+    tx: mpsc::Sender<(ExecStream, Vec<u8>)>,
+) {
+    let mut buffer = vec![0u8; READ_CHUNK];
+    loop {
+        match reader.read(&mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(count) => {
+                if tx.send((stream, buffer[..count].to_vec())).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// SIGKILL the process group started for an exec
+fn kill_group(child: &Child) {
+    if let Some(pid) = child.id() {
+        // SAFETY: killpg has no memory-safety preconditions; the group id is the
+        // child's pid because it was spawned with process_group(0)
+        unsafe {
+            libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+        }
+    }
+}
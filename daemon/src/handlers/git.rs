@@ -1,8 +1,17 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::{info, warn};
 
 use crate::git;
 use crate::protocol::*;
-use crate::state::DaemonState;
+use crate::state::{ClientId, DaemonState};
+
+/// Minimum time between `git_progress` events within one phase
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
 
 pub async fn handle_status(request: &Request, state: &DaemonState) -> String {
     let params: SessionIdParams = match serde_json::from_value(request.params.clone()) {
@@ -37,7 +46,7 @@ pub async fn handle_status(request: &Request, state: &DaemonState) -> String {
 }
 
 pub async fn handle_diff(request: &Request, state: &DaemonState) -> String {
-    let params: SessionIdParams = match serde_json::from_value(request.params.clone()) {
+    let params: GitDiffParams = match serde_json::from_value(request.params.clone()) {
         Ok(p) => p,
         Err(e) => {
             let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
@@ -56,7 +65,11 @@ pub async fn handle_diff(request: &Request, state: &DaemonState) -> String {
     }
 
     let path = Path::new(&params.session_id);
-    match git::get_diff(path) {
+    let spec = match diff_spec(request, path, &params.options) {
+        Ok(spec) => spec,
+        Err(resp) => return resp,
+    };
+    match git::get_diff(path, &state.diff_cache, &spec) {
         Ok(result) => {
             let resp = SuccessResponse::new(request.id, result);
             serde_json::to_string(&resp).unwrap()
@@ -101,3 +114,975 @@ pub async fn handle_log(request: &Request, state: &DaemonState) -> String {
         }
     }
 }
+
+/// One file's diff, paged by hunks
+pub async fn handle_diff_file(request: &Request, state: &DaemonState) -> String {
+    let params: GitDiffFileParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(resp) = require_repo(request, state, &params.session_id).await {
+        return resp;
+    }
+
+    let path = Path::new(&params.session_id);
+    let spec = match diff_spec(request, path, &params.options) {
+        Ok(spec) => spec,
+        Err(resp) => return resp,
+    };
+    match git::get_file_diff(
+        path,
+        &state.diff_cache,
+        &spec,
+        &params.path,
+        params.staged,
+        params.hunk_offset,
+        params.hunk_limit,
+    ) {
+        Ok(Some(result)) => {
+            let resp = SuccessResponse::new(request.id, result);
+            serde_json::to_string(&resp).unwrap()
+        }
+        Ok(None) => {
+            let side = if params.staged { "staged" } else { "unstaged" };
+            let resp = ErrorResponse::new(
+                request.id,
+                DIFF_NOT_FOUND,
+                format!("No {side} diff for {}", params.path),
+            );
+            serde_json::to_string(&resp).unwrap()
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+            serde_json::to_string(&resp).unwrap()
+        }
+    }
+}
+
+pub async fn handle_show(request: &Request, state: &DaemonState) -> String {
+    let params: GitShowParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(resp) = require_repo(request, state, &params.session_id).await {
+        return resp;
+    }
+
+    let path = Path::new(&params.session_id);
+    let Some(sha) = git::resolve_commit(path, &params.sha) else {
+        let resp = ErrorResponse::new(
+            request.id,
+            COMMIT_NOT_FOUND,
+            format!("Commit not found: {}", params.sha),
+        );
+        return serde_json::to_string(&resp).unwrap();
+    };
+
+    match git::show_commit(path, &sha) {
+        Ok(result) => {
+            let resp = SuccessResponse::new(request.id, result);
+            serde_json::to_string(&resp).unwrap()
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+            serde_json::to_string(&resp).unwrap()
+        }
+    }
+}
+
+pub async fn handle_branches(request: &Request, state: &DaemonState) -> String {
+    let params: SessionIdParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(resp) = require_repo(request, state, &params.session_id).await {
+        return resp;
+    }
+
+    match git::list_branches(Path::new(&params.session_id)) {
+        Ok(result) => {
+            let resp = SuccessResponse::new(request.id, result);
+            serde_json::to_string(&resp).unwrap()
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+            serde_json::to_string(&resp).unwrap()
+        }
+    }
+}
+
+pub async fn handle_branch_create(request: &Request, state: &DaemonState) -> String {
+    let params: GitBranchCreateParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(resp) = require_repo(request, state, &params.session_id).await {
+        return resp;
+    }
+
+    let path = Path::new(&params.session_id);
+    if !git::is_valid_branch_name(path, &params.name) {
+        let resp = ErrorResponse::new(
+            request.id,
+            INVALID_PARAMS,
+            format!("Invalid branch name: {}", params.name),
+        );
+        return serde_json::to_string(&resp).unwrap();
+    }
+    if git::branch_exists(path, &params.name) {
+        let resp = ErrorResponse::new(
+            request.id,
+            BRANCH_EXISTS,
+            format!("Branch already exists: {}", params.name),
+        );
+        return serde_json::to_string(&resp).unwrap();
+    }
+    let start_point = match &params.start_point {
+        Some(start) if params.track && !git::remote_branch_exists(path, start) => {
+            let resp = ErrorResponse::new(
+                request.id,
+                BRANCH_NOT_FOUND,
+                format!("Remote branch not found: {start}"),
+            );
+            return serde_json::to_string(&resp).unwrap();
+        }
+        // Tracking refers to the branch by name; otherwise pin the resolved commit
+        Some(start) if params.track => Some(start.clone()),
+        Some(start) => match git::resolve_commit(path, start) {
+            Some(sha) => Some(sha),
+            None => {
+                let resp = ErrorResponse::new(
+                    request.id,
+                    COMMIT_NOT_FOUND,
+                    format!("Commit not found: {start}"),
+                );
+                return serde_json::to_string(&resp).unwrap();
+            }
+        },
+        None if params.track => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, "track requires a remote start_point");
+            return serde_json::to_string(&resp).unwrap();
+        }
+        None => None,
+    };
+
+    if let Err(e) = git::create_branch(path, &params.name, start_point.as_deref(), params.track) {
+        let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+        return serde_json::to_string(&resp).unwrap();
+    }
+    if params.checkout {
+        if let Err(e) = git::checkout(path, &params.name) {
+            let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+            return serde_json::to_string(&resp).unwrap();
+        }
+        notify_status_changed(state, &params.session_id).await;
+    }
+    info!("Created branch {} in {}", params.name, params.session_id);
+
+    branches_response(request, path)
+}
+
+pub async fn handle_checkout(request: &Request, state: &DaemonState) -> String {
+    let params: GitCheckoutParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(resp) = require_repo(request, state, &params.session_id).await {
+        return resp;
+    }
+
+    let path = Path::new(&params.session_id);
+    if params.branch.starts_with('-') || !git::checkout_target_exists(path, &params.branch) {
+        let resp = ErrorResponse::new(
+            request.id,
+            BRANCH_NOT_FOUND,
+            format!("Branch not found: {}", params.branch),
+        );
+        return serde_json::to_string(&resp).unwrap();
+    }
+    if !params.force {
+        match git::has_uncommitted_changes(path) {
+            Ok(false) => {}
+            Ok(true) => {
+                let resp = ErrorResponse::new(
+                    request.id,
+                    WORKTREE_DIRTY,
+                    "Uncommitted changes; commit them or pass force to carry them over",
+                );
+                return serde_json::to_string(&resp).unwrap();
+            }
+            Err(e) => {
+                let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+                return serde_json::to_string(&resp).unwrap();
+            }
+        }
+    }
+
+    if let Err(e) = git::checkout(path, &params.branch) {
+        let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+        return serde_json::to_string(&resp).unwrap();
+    }
+    info!("Checked out {} in {}", params.branch, params.session_id);
+    notify_status_changed(state, &params.session_id).await;
+
+    branches_response(request, path)
+}
+
+pub async fn handle_branch_delete(request: &Request, state: &DaemonState) -> String {
+    let params: GitBranchDeleteParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(resp) = require_repo(request, state, &params.session_id).await {
+        return resp;
+    }
+
+    let path = Path::new(&params.session_id);
+    if params.name.starts_with('-') || !git::branch_exists(path, &params.name) {
+        let resp = ErrorResponse::new(
+            request.id,
+            BRANCH_NOT_FOUND,
+            format!("Branch not found: {}", params.name),
+        );
+        return serde_json::to_string(&resp).unwrap();
+    }
+
+    if let Err(e) = git::delete_branch(path, &params.name, params.force) {
+        let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+        return serde_json::to_string(&resp).unwrap();
+    }
+    info!("Deleted branch {} in {}", params.name, params.session_id);
+
+    branches_response(request, path)
+}
+
+/// Reply to a branch mutation with the updated branch list
+fn branches_response(request: &Request, path: &Path) -> String {
+    match git::list_branches(path) {
+        Ok(result) => {
+            let resp = SuccessResponse::new(request.id, result);
+            serde_json::to_string(&resp).unwrap()
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+            serde_json::to_string(&resp).unwrap()
+        }
+    }
+}
+
+/// Start `git fetch`. Progress and the outcome arrive as `git_progress` and
+/// `git_operation_finished` events.
+pub async fn handle_fetch(request: &Request, state: Arc<DaemonState>, client_id: ClientId) -> String {
+    let params: GitFetchParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(resp) = require_repo(request, &state, &params.session_id).await {
+        return resp;
+    }
+    if let Err(resp) = require_remote(request, &params.session_id, params.remote.as_deref(), None) {
+        return resp;
+    }
+
+    let args = git::fetch_args(params.remote.as_deref(), params.prune);
+    start_remote_operation(request, state, client_id, params.session_id, params.operation_id, "fetch", args)
+        .await
+}
+
+/// Start `git pull` (fast-forward only or rebase)
+pub async fn handle_pull(request: &Request, state: Arc<DaemonState>, client_id: ClientId) -> String {
+    let params: GitPullParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(resp) = require_repo(request, &state, &params.session_id).await {
+        return resp;
+    }
+    let (remote, branch) = (params.remote.as_deref(), params.branch.as_deref());
+    if let Err(resp) = require_remote(request, &params.session_id, remote, branch) {
+        return resp;
+    }
+
+    let args = git::pull_args(remote, branch, params.mode);
+    start_remote_operation(request, state, client_id, params.session_id, params.operation_id, "pull", args)
+        .await
+}
+
+/// Start `git push`
+pub async fn handle_push(request: &Request, state: Arc<DaemonState>, client_id: ClientId) -> String {
+    let params: GitPushParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(resp) = require_repo(request, &state, &params.session_id).await {
+        return resp;
+    }
+    if params.set_upstream && params.remote.is_none() {
+        let resp = ErrorResponse::new(request.id, INVALID_PARAMS, "set_upstream requires remote");
+        return serde_json::to_string(&resp).unwrap();
+    }
+    let (remote, branch) = (params.remote.as_deref(), params.branch.as_deref());
+    if let Err(resp) = require_remote(request, &params.session_id, remote, branch) {
+        return resp;
+    }
+
+    let args = git::push_args(remote, branch, params.set_upstream, params.force_with_lease);
+    start_remote_operation(request, state, client_id, params.session_id, params.operation_id, "push", args)
+        .await
+}
+
+/// Check a remote (and branch, which requires it) named in params; the error is a full
+/// response
+fn require_remote(
+    request: &Request,
+    session_id: &str,
+    remote: Option<&str>,
+    branch: Option<&str>,
+) -> Result<(), String> {
+    let path = Path::new(session_id);
+    let error = match (remote, branch) {
+        (None, Some(_)) => Some((INVALID_PARAMS, "branch requires remote".to_string())),
+        (Some(remote), _) if !git::remote_exists(path, remote) => {
+            Some((REMOTE_NOT_FOUND, format!("Remote not found: {remote}")))
+        }
+        (_, Some(branch)) if !git::is_valid_branch_name(path, branch) => {
+            Some((INVALID_PARAMS, format!("Invalid branch name: {branch}")))
+        }
+        _ => None,
+    };
+    match error {
+        Some((code, message)) => {
+            let resp = ErrorResponse::new(request.id, code, message);
+            Err(serde_json::to_string(&resp).unwrap())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Run a network git command in the background, one per session at a time
+async fn start_remote_operation(
+    request: &Request,
+    state: Arc<DaemonState>,
+    client_id: ClientId,
+    session_id: String,
+    operation_id: String,
+    operation: &'static str,
+    args: Vec<String>,
+) -> String {
+    let mut operations = state.git_operations.write().await;
+    if let Some(running) = operations.get(&session_id) {
+        let resp = ErrorResponse::new(
+            request.id,
+            GIT_BUSY,
+            format!("Git operation already running: {running}"),
+        );
+        return serde_json::to_string(&resp).unwrap();
+    }
+    operations.insert(session_id.clone(), operation_id.clone());
+    drop(operations);
+
+    info!("Client {client_id} git {operation} {operation_id} in {session_id}");
+    tokio::spawn(run_remote_operation(
+        state,
+        client_id,
+        session_id,
+        operation_id.clone(),
+        operation,
+        args,
+    ));
+
+    let resp = SuccessResponse::new(request.id, serde_json::json!({ "operation_id": operation_id }));
+    serde_json::to_string(&resp).unwrap()
+}
+
+async fn run_remote_operation(
+    state: Arc<DaemonState>,
+    client_id: ClientId,
+    session_id: String,
+    operation_id: String,
+    operation: &'static str,
+    args: Vec<String>,
+) {
+    let started = Instant::now();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let path = PathBuf::from(&session_id);
+    let task = tokio::task::spawn_blocking(move || {
+        git::run_remote(&path, &args, |progress| {
+            let _ = tx.send(progress);
+        })
+    });
+
+    // Progress is throttled and dropped if the client falls behind; it shares the
+    // output queue with the finished event so that one arrives last
+    let sender = state.clients.read().await.get(&client_id).cloned();
+    let mut last: Option<(GitProgress, Instant)> = None;
+    while let Some(progress) = rx.recv().await {
+        let due = match &last {
+            Some((sent, at)) => {
+                sent.phase != progress.phase
+                    || (progress.percent == Some(100) && sent.percent != Some(100))
+                    || at.elapsed() >= PROGRESS_INTERVAL
+            }
+            None => true,
+        };
+        let Some(sender) = sender.as_ref().filter(|_| due) else {
+            continue;
+        };
+        let event = Event::new(
+            EVENT_GIT_PROGRESS,
+            GitProgressParams {
+                session_id: session_id.clone(),
+                operation_id: operation_id.clone(),
+                progress: progress.clone(),
+            },
+        );
+        sender.offer(serde_json::to_string(&event).unwrap());
+        last = Some((progress, Instant::now()));
+    }
+
+    let result = task
+        .await
+        .unwrap_or_else(|e| Err(format!("git {operation} failed: {e}")));
+    state.git_operations.write().await.remove(&session_id);
+
+    let error = result.err().map(|message| {
+        warn!("git {operation} {operation_id} in {session_id} failed: {message}");
+        let code = if git::is_auth_failure(&message) { GIT_AUTH_FAILED } else { GIT_ERROR };
+        RpcError { code, message }
+    });
+    let event = Event::new(
+        EVENT_GIT_OPERATION_FINISHED,
+        GitOperationFinishedParams {
+            session_id: session_id.clone(),
+            operation_id,
+            operation,
+            success: error.is_none(),
+            error,
+            duration_ms: started.elapsed().as_millis() as u64,
+        },
+    );
+    if let Some(sender) = &sender {
+        if let Some(permit) = sender.reserve().await {
+            permit.send(serde_json::to_string(&event).unwrap());
+        }
+    }
+
+    // A failed pull can still have fetched, merged or left conflicts
+    if operation == "pull" {
+        notify_status_changed(&state, &session_id).await;
+    }
+}
+
+/// Conflicted files with base/ours/theirs contents and parsed conflict regions
+pub async fn handle_conflicts(request: &Request, state: &DaemonState) -> String {
+    let params: SessionIdParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(resp) = require_repo(request, state, &params.session_id).await {
+        return resp;
+    }
+
+    match git::get_conflicts(Path::new(&params.session_id)) {
+        Ok(result) => {
+            let resp = SuccessResponse::new(request.id, result);
+            serde_json::to_string(&resp).unwrap()
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+            serde_json::to_string(&resp).unwrap()
+        }
+    }
+}
+
+/// Resolve a conflicted file with ours, theirs or given content and mark it resolved
+pub async fn handle_resolve(request: &Request, state: &DaemonState) -> String {
+    let params: GitResolveParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(e) = git::validate_paths(std::slice::from_ref(&params.path)) {
+        let resp = ErrorResponse::new(request.id, INVALID_PARAMS, e);
+        return serde_json::to_string(&resp).unwrap();
+    }
+    if matches!(params.resolution, GitResolution::Content) && params.content.is_none() {
+        let resp = ErrorResponse::new(
+            request.id,
+            INVALID_PARAMS,
+            "content is required with resolution \"content\"",
+        );
+        return serde_json::to_string(&resp).unwrap();
+    }
+    if let Err(resp) = require_repo(request, state, &params.session_id).await {
+        return resp;
+    }
+
+    let path = Path::new(&params.session_id);
+    let stages = match git::conflict_stages(path, &params.path) {
+        Ok(stages) if stages.is_empty() => {
+            let resp = ErrorResponse::new(
+                request.id,
+                NOT_CONFLICTED,
+                format!("File is not conflicted: {}", params.path),
+            );
+            return serde_json::to_string(&resp).unwrap();
+        }
+        Ok(stages) => stages,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    let result = git::resolve_conflict(
+        path,
+        &params.path,
+        &stages,
+        params.resolution,
+        params.content.as_deref(),
+    );
+    if let Err(e) = result {
+        let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+        return serde_json::to_string(&resp).unwrap();
+    }
+    info!("Resolved {} ({:?}) in {}", params.path, params.resolution, params.session_id);
+    notify_status_changed(state, &params.session_id).await;
+
+    let resp = SuccessResponse::new(request.id, serde_json::json!({}));
+    serde_json::to_string(&resp).unwrap()
+}
+
+/// Abort a merge or rebase in progress, restoring the pre-operation state
+pub async fn handle_abort(request: &Request, state: &DaemonState, operation: &str) -> String {
+    let params: SessionIdParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(resp) = require_repo(request, state, &params.session_id).await {
+        return resp;
+    }
+
+    let path = Path::new(&params.session_id);
+    if git::operation_in_progress(path) != Some(operation) {
+        let resp = ErrorResponse::new(
+            request.id,
+            NO_OPERATION_IN_PROGRESS,
+            format!("No {operation} in progress"),
+        );
+        return serde_json::to_string(&resp).unwrap();
+    }
+
+    if let Err(e) = git::abort_operation(path, operation) {
+        let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+        return serde_json::to_string(&resp).unwrap();
+    }
+    info!("Aborted {operation} in {}", params.session_id);
+    notify_status_changed(state, &params.session_id).await;
+
+    let resp = SuccessResponse::new(request.id, serde_json::json!({}));
+    serde_json::to_string(&resp).unwrap()
+}
+
+/// Continue a rebase once its conflicts are resolved. Returns the conflicts of the next
+/// stop, or none with no operation once the rebase finished.
+pub async fn handle_rebase_continue(request: &Request, state: &DaemonState) -> String {
+    let params: SessionIdParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(resp) = require_repo(request, state, &params.session_id).await {
+        return resp;
+    }
+
+    let path = Path::new(&params.session_id);
+    if git::operation_in_progress(path) != Some("rebase") {
+        let resp = ErrorResponse::new(request.id, NO_OPERATION_IN_PROGRESS, "No rebase in progress");
+        return serde_json::to_string(&resp).unwrap();
+    }
+    match git::get_conflicts(path) {
+        Ok(conflicts) if !conflicts.files.is_empty() => {
+            let resp = ErrorResponse::new(
+                request.id,
+                UNRESOLVED_CONFLICTS,
+                format!("{} file(s) still conflicted", conflicts.files.len()),
+            );
+            return serde_json::to_string(&resp).unwrap();
+        }
+        Ok(_) => {}
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+            return serde_json::to_string(&resp).unwrap();
+        }
+    }
+
+    let result = git::rebase_continue(path);
+    notify_status_changed(state, &params.session_id).await;
+    match result {
+        Ok(conflicts) => {
+            let resp = SuccessResponse::new(request.id, conflicts);
+            serde_json::to_string(&resp).unwrap()
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+            serde_json::to_string(&resp).unwrap()
+        }
+    }
+}
+
+/// Check the session exists and is a git repository; the error is a full response
+async fn require_repo(request: &Request, state: &DaemonState, session_id: &str) -> Result<(), String> {
+    if state.get_session(session_id).await.is_none() {
+        let resp = ErrorResponse::new(
+            request.id,
+            SESSION_NOT_FOUND,
+            format!("Session not found: {session_id}"),
+        );
+        return Err(serde_json::to_string(&resp).unwrap());
+    }
+    if !git::is_git_repo(Path::new(session_id)) {
+        let resp = ErrorResponse::new(
+            request.id,
+            NOT_A_GIT_REPO,
+            format!("Not a git repository: {session_id}"),
+        );
+        return Err(serde_json::to_string(&resp).unwrap());
+    }
+    Ok(())
+}
+
+/// Validate diff options and resolve their refs; the error is a full response
+fn diff_spec(request: &Request, path: &Path, options: &GitDiffOptions) -> Result<git::DiffSpec, String> {
+    if let Err(e) = git::validate_diff_options(options) {
+        let resp = ErrorResponse::new(request.id, INVALID_PARAMS, e);
+        return Err(serde_json::to_string(&resp).unwrap());
+    }
+    git::resolve_diff_spec(path, options).map_err(|e| {
+        let resp = ErrorResponse::new(request.id, COMMIT_NOT_FOUND, e);
+        serde_json::to_string(&resp).unwrap()
+    })
+}
+
+/// Send the session's new status to every client allowed to see it
+async fn notify_status_changed(state: &DaemonState, session_id: &str) {
+    match git::get_status(Path::new(session_id)) {
+        Ok(status) => {
+            let event = Event::new(
+                EVENT_GIT_STATUS_CHANGED,
+                GitStatusChangedParams {
+                    session_id: session_id.to_string(),
+                    status,
+                },
+            );
+            state
+                .broadcast_to_session_clients(session_id, serde_json::to_string(&event).unwrap())
+                .await;
+        }
+        Err(e) => warn!("Failed to get git status for {session_id}: {e}"),
+    }
+}
+
+pub async fn handle_stage(request: &Request, state: &DaemonState) -> String {
+    handle_index_update(request, state, git::stage).await
+}
+
+pub async fn handle_unstage(request: &Request, state: &DaemonState) -> String {
+    handle_index_update(request, state, git::unstage).await
+}
+
+/// Stage or unstage paths, replying with the new status
+async fn handle_index_update(
+    request: &Request,
+    state: &DaemonState,
+    update: fn(&Path, &[String]) -> Result<(), String>,
+) -> String {
+    let params: GitPathsParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(e) = git::validate_paths(&params.paths) {
+        let resp = ErrorResponse::new(request.id, INVALID_PARAMS, e);
+        return serde_json::to_string(&resp).unwrap();
+    }
+    if let Err(resp) = require_repo(request, state, &params.session_id).await {
+        return resp;
+    }
+
+    let path = Path::new(&params.session_id);
+    if let Err(e) = update(path, &params.paths) {
+        let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+        return serde_json::to_string(&resp).unwrap();
+    }
+    notify_status_changed(state, &params.session_id).await;
+
+    match git::get_status(path) {
+        Ok(result) => {
+            let resp = SuccessResponse::new(request.id, result);
+            serde_json::to_string(&resp).unwrap()
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+            serde_json::to_string(&resp).unwrap()
+        }
+    }
+}
+
+pub async fn handle_stage_hunks(request: &Request, state: &DaemonState) -> String {
+    handle_hunks(request, state, false).await
+}
+
+pub async fn handle_unstage_hunks(request: &Request, state: &DaemonState) -> String {
+    handle_hunks(request, state, true).await
+}
+
+/// Stage hunks of the unstaged diff, or unstage hunks of the staged diff, replying with
+/// the new status. Fails with DIFF_CHANGED if the diff no longer matches `diff_hash`.
+async fn handle_hunks(request: &Request, state: &DaemonState, unstage: bool) -> String {
+    let params: GitHunksParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(e) = git::validate_paths(std::slice::from_ref(&params.path)) {
+        let resp = ErrorResponse::new(request.id, INVALID_PARAMS, e);
+        return serde_json::to_string(&resp).unwrap();
+    }
+    if params.hunks.is_empty() {
+        let resp = ErrorResponse::new(request.id, INVALID_PARAMS, "hunks must not be empty");
+        return serde_json::to_string(&resp).unwrap();
+    }
+    if let Err(e) = git::validate_hunk_options(&params.options) {
+        let resp = ErrorResponse::new(request.id, INVALID_PARAMS, e);
+        return serde_json::to_string(&resp).unwrap();
+    }
+    if let Err(resp) = require_repo(request, state, &params.session_id).await {
+        return resp;
+    }
+
+    let path = Path::new(&params.session_id);
+    let spec = match diff_spec(request, path, &params.options) {
+        Ok(spec) => spec,
+        Err(resp) => return resp,
+    };
+    let diff = match git::file_diff(path, &state.diff_cache, &spec, &params.path, unstage) {
+        Ok(diff) => diff,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+    if git::diff_hash(&diff) != params.diff_hash {
+        let resp = ErrorResponse::new(
+            request.id,
+            DIFF_CHANGED,
+            format!("Diff of {} changed; fetch it again", params.path),
+        );
+        return serde_json::to_string(&resp).unwrap();
+    }
+
+    let patch = match git::build_partial_patch(&diff, &params.hunks, unstage) {
+        Ok(patch) => patch,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, e);
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+    if let Err(e) = git::apply_to_index(path, &patch, unstage) {
+        let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+        return serde_json::to_string(&resp).unwrap();
+    }
+    notify_status_changed(state, &params.session_id).await;
+
+    match git::get_status(path) {
+        Ok(result) => {
+            let resp = SuccessResponse::new(request.id, result);
+            serde_json::to_string(&resp).unwrap()
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+            serde_json::to_string(&resp).unwrap()
+        }
+    }
+}
+
+/// Discard unstaged changes. Without `confirm_token` this only previews the files and
+/// returns a token; the discard happens when the token is sent back unchanged.
+pub async fn handle_discard(request: &Request, state: &DaemonState) -> String {
+    let params: GitDiscardParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if let Err(e) = git::validate_paths(&params.paths) {
+        let resp = ErrorResponse::new(request.id, INVALID_PARAMS, e);
+        return serde_json::to_string(&resp).unwrap();
+    }
+    if let Err(resp) = require_repo(request, state, &params.session_id).await {
+        return resp;
+    }
+
+    let path = Path::new(&params.session_id);
+    let result = git::discard_candidates(path, &params.paths, params.include_untracked)
+        .and_then(|(tracked, untracked)| {
+            let token = git::discard_token(path, &tracked, &untracked)?;
+            Ok((tracked, untracked, token))
+        });
+    let (tracked, untracked, token) = match result {
+        Ok(r) => r,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+    let files: Vec<String> = tracked.iter().chain(&untracked).cloned().collect();
+
+    let Some(confirm_token) = params.confirm_token else {
+        let resp = SuccessResponse::new(request.id, GitDiscardResult {
+            confirm_token: (!files.is_empty()).then_some(token),
+            files,
+            discarded: false,
+        });
+        return serde_json::to_string(&resp).unwrap();
+    };
+
+    if confirm_token != token {
+        let resp = ErrorResponse::new(
+            request.id,
+            CONFIRMATION_REQUIRED,
+            "Files changed since the preview; preview the discard again",
+        );
+        return serde_json::to_string(&resp).unwrap();
+    }
+
+    if let Err(e) = git::discard(path, &tracked, &untracked) {
+        let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+        return serde_json::to_string(&resp).unwrap();
+    }
+    info!("Discarded {} file(s) in {}", files.len(), params.session_id);
+    notify_status_changed(state, &params.session_id).await;
+
+    let resp = SuccessResponse::new(request.id, GitDiscardResult {
+        files,
+        discarded: true,
+        confirm_token: None,
+    });
+    serde_json::to_string(&resp).unwrap()
+}
+
+pub async fn handle_commit(request: &Request, state: &DaemonState) -> String {
+    let params: GitCommitParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if params.message.trim().is_empty() && !params.amend {
+        let resp = ErrorResponse::new(request.id, INVALID_PARAMS, "Commit message must not be empty");
+        return serde_json::to_string(&resp).unwrap();
+    }
+    if let Some(author) = &params.author {
+        if !git::is_valid_author(author) {
+            let resp = ErrorResponse::new(
+                request.id,
+                INVALID_PARAMS,
+                format!("Author must be \"Name <email>\": {author}"),
+            );
+            return serde_json::to_string(&resp).unwrap();
+        }
+    }
+    if let Err(resp) = require_repo(request, state, &params.session_id).await {
+        return resp;
+    }
+
+    let path = Path::new(&params.session_id);
+    if !params.amend {
+        match git::has_staged_changes(path) {
+            Ok(true) => {}
+            Ok(false) => {
+                let resp = ErrorResponse::new(request.id, NOTHING_TO_COMMIT, "No staged changes to commit");
+                return serde_json::to_string(&resp).unwrap();
+            }
+            Err(e) => {
+                let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+                return serde_json::to_string(&resp).unwrap();
+            }
+        }
+    }
+
+    let message = if params.message.trim().is_empty() { "" } else { params.message.as_str() };
+    match git::commit(path, message, params.amend, params.signoff, params.author.as_deref()) {
+        Ok(result) => {
+            info!("Committed {} in {}", result.sha, params.session_id);
+            notify_status_changed(state, &params.session_id).await;
+            let resp = SuccessResponse::new(request.id, result);
+            serde_json::to_string(&resp).unwrap()
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, GIT_ERROR, e);
+            serde_json::to_string(&resp).unwrap()
+        }
+    }
+}
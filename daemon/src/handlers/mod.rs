@@ -1,15 +1,20 @@
+pub mod audit;
 pub mod auth;
 pub mod claude_sdk;
+pub mod exec;
 pub mod git;
 pub mod opencode;
 pub mod sessions;
 pub mod terminal;
 
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Instant, SystemTime};
 
 use tracing::{debug, info, warn};
 
+use crate::access::{self, Principal};
+use crate::audit as audit_log;
+use crate::auth::format_timestamp;
 use crate::protocol::*;
 use crate::state::{ClientId, DaemonState};
 
@@ -25,18 +30,66 @@ pub async fn dispatch(
 
     debug!("[dispatch] → id={} method={} client={}", id, method, client_id);
 
+    if method == METHOD_AUTH {
+        return auth::handle(request, &state, client_id).await;
+    }
+
+    let principal = match authorize(request, &state, client_id).await {
+        Ok(principal) => principal,
+        Err(response) => {
+            info!("[dispatch] ← id={} method={} denied client={}", id, method, client_id);
+            if audit_log::is_audited(method) {
+                let principal = state.get_client_principal(client_id).await;
+                record_audit(request, &state, client_id, principal.as_ref(), &response, start).await;
+            }
+            return response;
+        }
+    };
+
     let response = match method {
-        METHOD_AUTH => auth::handle(request, &state).await,
-        METHOD_LIST_SESSIONS => sessions::handle_list(request, &state).await,
+        METHOD_LIST_SESSIONS => sessions::handle_list(request, &state, &principal).await,
         METHOD_SESSION_INFO => sessions::handle_info(request, &state).await,
-        METHOD_TERMINAL_OPEN => terminal::handle_open(request, state, client_id).await,
-        METHOD_TERMINAL_WRITE => terminal::handle_write(request, &state).await,
-        METHOD_TERMINAL_RESIZE => terminal::handle_resize(request, &state).await,
-        METHOD_TERMINAL_CLOSE => terminal::handle_close(request, &state).await,
+        METHOD_SESSION_ADD => sessions::handle_add(request, &state).await,
+        METHOD_SESSION_REMOVE => sessions::handle_remove(request, &state).await,
+        METHOD_SESSION_RENAME => sessions::handle_rename(request, &state).await,
+        METHOD_AUDIT_QUERY => audit::handle_query(request, &state, &principal).await,
+        METHOD_TERMINAL_OPEN => terminal::handle_open(request, state.clone(), client_id).await,
+        METHOD_TERMINAL_WRITE => terminal::handle_write(request, &state, client_id).await,
+        METHOD_TERMINAL_RESIZE => terminal::handle_resize(request, &state, client_id).await,
+        METHOD_TERMINAL_CLOSE => terminal::handle_close(request, &state, client_id).await,
+        METHOD_TERMINAL_LIST => terminal::handle_list(request, &state, client_id, &principal).await,
+        METHOD_TERMINAL_ATTACH => terminal::handle_attach(request, &state, client_id).await,
+        METHOD_TERMINAL_SUBSCRIBE => terminal::handle_subscribe(request, &state, client_id).await,
+        METHOD_TERMINAL_UNSUBSCRIBE => terminal::handle_unsubscribe(request, state.clone(), client_id).await,
+        METHOD_TERMINAL_PROFILE_LIST => terminal::handle_profile_list(request, &state),
+        METHOD_TERMINAL_RECORDING_LIST => terminal::handle_recording_list(request, &state, &principal).await,
+        METHOD_TERMINAL_RECORDING_GET => terminal::handle_recording_get(request, &state, &principal).await,
+        METHOD_EXEC => exec::handle_exec(request, state.clone(), client_id).await,
+        METHOD_EXEC_CANCEL => exec::handle_cancel(request, &state, client_id).await,
         METHOD_GIT_STATUS => git::handle_status(request, &state).await,
         METHOD_GIT_DIFF => git::handle_diff(request, &state).await,
+        METHOD_GIT_DIFF_FILE => git::handle_diff_file(request, &state).await,
+        METHOD_GIT_FETCH => git::handle_fetch(request, state.clone(), client_id).await,
+        METHOD_GIT_PULL => git::handle_pull(request, state.clone(), client_id).await,
+        METHOD_GIT_PUSH => git::handle_push(request, state.clone(), client_id).await,
+        METHOD_GIT_CONFLICTS => git::handle_conflicts(request, &state).await,
+        METHOD_GIT_RESOLVE => git::handle_resolve(request, &state).await,
+        METHOD_GIT_MERGE_ABORT => git::handle_abort(request, &state, "merge").await,
+        METHOD_GIT_REBASE_CONTINUE => git::handle_rebase_continue(request, &state).await,
+        METHOD_GIT_REBASE_ABORT => git::handle_abort(request, &state, "rebase").await,
         METHOD_GIT_LOG => git::handle_log(request, &state).await,
-        METHOD_OPENCODE_CONNECT_WORKSPACE => opencode::handle_connect(request, state).await,
+        METHOD_GIT_SHOW => git::handle_show(request, &state).await,
+        METHOD_GIT_BRANCHES => git::handle_branches(request, &state).await,
+        METHOD_GIT_BRANCH_CREATE => git::handle_branch_create(request, &state).await,
+        METHOD_GIT_CHECKOUT => git::handle_checkout(request, &state).await,
+        METHOD_GIT_BRANCH_DELETE => git::handle_branch_delete(request, &state).await,
+        METHOD_GIT_STAGE => git::handle_stage(request, &state).await,
+        METHOD_GIT_UNSTAGE => git::handle_unstage(request, &state).await,
+        METHOD_GIT_STAGE_HUNKS => git::handle_stage_hunks(request, &state).await,
+        METHOD_GIT_UNSTAGE_HUNKS => git::handle_unstage_hunks(request, &state).await,
+        METHOD_GIT_DISCARD => git::handle_discard(request, &state).await,
+        METHOD_GIT_COMMIT => git::handle_commit(request, &state).await,
+        METHOD_OPENCODE_CONNECT_WORKSPACE => opencode::handle_connect(request, state.clone()).await,
         METHOD_OPENCODE_DISCONNECT_WORKSPACE => opencode::handle_disconnect(request, &state).await,
         METHOD_OPENCODE_STATUS => opencode::handle_status(request, &state).await,
         METHOD_OPENCODE_SESSION_LIST => opencode::handle_session_list(request, &state).await,
@@ -44,7 +97,7 @@ pub async fn dispatch(
         METHOD_OPENCODE_SESSION_PROMPT => opencode::handle_session_prompt(request, &state).await,
         METHOD_OPENCODE_SESSION_ABORT => opencode::handle_session_abort(request, &state).await,
         METHOD_OPENCODE_SESSION_MESSAGES => opencode::handle_session_messages(request, &state).await,
-        METHOD_CLAUDE_SDK_CONNECT_WORKSPACE => claude_sdk::handle_connect(request, state).await,
+        METHOD_CLAUDE_SDK_CONNECT_WORKSPACE => claude_sdk::handle_connect(request, state.clone()).await,
         METHOD_CLAUDE_SDK_DISCONNECT_WORKSPACE => claude_sdk::handle_disconnect(request, &state).await,
         METHOD_CLAUDE_SDK_STATUS => claude_sdk::handle_status(request, &state).await,
         METHOD_CLAUDE_SDK_SESSION_LIST => claude_sdk::handle_session_list(request, &state).await,
@@ -67,6 +120,10 @@ pub async fn dispatch(
         }
     };
 
+    if audit_log::is_audited(method) {
+        record_audit(request, &state, client_id, Some(&principal), &response, start).await;
+    }
+
     let elapsed = start.elapsed();
     let is_error = response.contains("\"error\"");
 
@@ -78,3 +135,119 @@ pub async fn dispatch(
 
     response
 }
+
+/// Check the client's principal against the method scope and session allowlist
+async fn authorize(
+    request: &Request,
+    state: &DaemonState,
+    client_id: ClientId,
+) -> Result<Principal, String> {
+    let Some(principal) = state.get_client_principal(client_id).await else {
+        let resp = ErrorResponse::new(
+            request.id,
+            AUTH_REQUIRED,
+            "Authentication required. Send auth request first.",
+        );
+        return Err(serde_json::to_string(&resp).unwrap());
+    };
+
+    let required = access::required_scope(&request.method);
+    if principal.scope < required {
+        let resp = ErrorResponse::new(
+            request.id,
+            FORBIDDEN,
+            format!(
+                "Token '{}' has scope {}; {} requires {}",
+                principal.name,
+                principal.scope.as_str(),
+                request.method,
+                required.as_str()
+            ),
+        );
+        return Err(serde_json::to_string(&resp).unwrap());
+    }
+
+    if principal.sessions.is_some() {
+        let denied = match request_session_path(request, state).await {
+            Ok(Some(path)) if !principal.allows_session(&path) => Some(format!("session: {path}")),
+            // Its session cannot be checked, so an unknown workspace is never allowed
+            Err(workspace_id) => Some(format!("unknown workspace: {workspace_id}")),
+            Ok(_) => None,
+        };
+        if let Some(target) = denied {
+            let resp = ErrorResponse::new(
+                request.id,
+                FORBIDDEN,
+                format!("Token '{}' may not access {target}", principal.name),
+            );
+            return Err(serde_json::to_string(&resp).unwrap());
+        }
+    }
+
+    Ok(principal)
+}
+
+/// Session path a request refers to, if any. Fails with the workspace ID if it names
+/// a workspace that is not connected.
+async fn request_session_path(request: &Request, state: &DaemonState) -> Result<Option<String>, String> {
+    let param = |key: &str| {
+        request
+            .params
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+    };
+
+    if access::is_workspace_method(&request.method) {
+        if let Some(path) = param("workspace_path") {
+            return Ok(Some(path));
+        }
+        let Some(workspace_id) = param("workspace_id") else {
+            return Ok(None);
+        };
+        return match state.workspace_path(&workspace_id).await {
+            Some(path) => Ok(Some(path)),
+            None => Err(workspace_id),
+        };
+    }
+
+    Ok(param("session_id").or_else(|| param("path")))
+}
+
+/// Append an audit log entry for a mutating request
+async fn record_audit(
+    request: &Request,
+    state: &DaemonState,
+    client_id: ClientId,
+    principal: Option<&Principal>,
+    response: &str,
+    start: Instant,
+) {
+    let param = |key: &str| {
+        request
+            .params
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+    };
+
+    let (session_id, workspace_id) = if access::is_workspace_method(&request.method) {
+        (param("workspace_path"), param("workspace_id"))
+    } else {
+        (param("session_id").or_else(|| param("path")), None)
+    };
+
+    let entry = AuditEntry {
+        ts: format_timestamp(SystemTime::now()),
+        client_id,
+        peer: state.client_peer(client_id).await.unwrap_or_default(),
+        token_name: principal.map(|p| p.name.clone()),
+        method: request.method.clone(),
+        session_id,
+        workspace_id,
+        params_digest: audit_log::params_digest(&request.params),
+        outcome: audit_log::response_outcome(response),
+        duration_ms: start.elapsed().as_millis() as u64,
+    };
+    state.audit_log.record(&entry);
+}
@@ -1,11 +1,20 @@
 use std::path::Path;
 
+use tracing::info;
+
+use crate::access::Principal;
+use crate::config::default_session_name;
 use crate::git;
 use crate::protocol::*;
 use crate::state::DaemonState;
 
-pub async fn handle_list(request: &Request, state: &DaemonState) -> String {
-    let sessions = state.list_sessions().await;
+pub async fn handle_list(request: &Request, state: &DaemonState, principal: &Principal) -> String {
+    let sessions: Vec<SessionInfo> = state
+        .list_sessions()
+        .await
+        .into_iter()
+        .filter(|s| principal.allows_session(&s.path))
+        .collect();
     let resp = SuccessResponse::new(request.id, sessions);
     serde_json::to_string(&resp).unwrap()
 }
@@ -42,3 +51,149 @@ pub async fn handle_info(request: &Request, state: &DaemonState) -> String {
         }
     }
 }
+
+pub async fn handle_add(request: &Request, state: &DaemonState) -> String {
+    let params: SessionAddParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    // Validate path: absolute, existing directory
+    let requested = Path::new(&params.path);
+    if !requested.is_absolute() {
+        let resp = ErrorResponse::new(
+            request.id,
+            INVALID_PARAMS,
+            format!("Session path must be absolute: {}", params.path),
+        );
+        return serde_json::to_string(&resp).unwrap();
+    }
+
+    let path = match std::fs::canonicalize(requested) {
+        Ok(p) if p.is_dir() => p.to_string_lossy().to_string(),
+        Ok(_) => {
+            let resp = ErrorResponse::new(
+                request.id,
+                INVALID_PARAMS,
+                format!("Session path is not a directory: {}", params.path),
+            );
+            return serde_json::to_string(&resp).unwrap();
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(
+                request.id,
+                INVALID_PARAMS,
+                format!("Session path is not accessible: {}: {e}", params.path),
+            );
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    let name = match params.name.as_deref().map(str::trim) {
+        Some("") => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, "Session name must not be empty");
+            return serde_json::to_string(&resp).unwrap();
+        }
+        Some(name) => name.to_string(),
+        None => default_session_name(&path),
+    };
+
+    let session = SessionInfo { path, name };
+
+    match state.add_session(session.clone()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            let resp = ErrorResponse::new(
+                request.id,
+                SESSION_EXISTS,
+                format!("Session already exists: {}", session.path),
+            );
+            return serde_json::to_string(&resp).unwrap();
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INTERNAL_ERROR, e);
+            return serde_json::to_string(&resp).unwrap();
+        }
+    }
+
+    info!("Session added: {}", session.path);
+    state.broadcast_sessions_changed().await;
+
+    let resp = SuccessResponse::new(request.id, session);
+    serde_json::to_string(&resp).unwrap()
+}
+
+pub async fn handle_remove(request: &Request, state: &DaemonState) -> String {
+    let params: SessionIdParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    match state.remove_session(&params.session_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            let resp = ErrorResponse::new(
+                request.id,
+                SESSION_NOT_FOUND,
+                format!("Session not found: {}", params.session_id),
+            );
+            return serde_json::to_string(&resp).unwrap();
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INTERNAL_ERROR, e);
+            return serde_json::to_string(&resp).unwrap();
+        }
+    }
+
+    state.teardown_session(&params.session_id).await;
+
+    info!("Session removed: {}", params.session_id);
+    state.broadcast_sessions_changed().await;
+
+    let resp = SuccessResponse::new(request.id, serde_json::json!({}));
+    serde_json::to_string(&resp).unwrap()
+}
+
+pub async fn handle_rename(request: &Request, state: &DaemonState) -> String {
+    let params: SessionRenameParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    let name = params.name.trim();
+    if name.is_empty() {
+        let resp = ErrorResponse::new(request.id, INVALID_PARAMS, "Session name must not be empty");
+        return serde_json::to_string(&resp).unwrap();
+    }
+
+    let session = match state.rename_session(&params.session_id, name.to_string()).await {
+        Ok(Some(session)) => session,
+        Ok(None) => {
+            let resp = ErrorResponse::new(
+                request.id,
+                SESSION_NOT_FOUND,
+                format!("Session not found: {}", params.session_id),
+            );
+            return serde_json::to_string(&resp).unwrap();
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INTERNAL_ERROR, e);
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    info!("Session renamed: {} -> {}", session.path, session.name);
+    state.broadcast_sessions_changed().await;
+
+    let resp = SuccessResponse::new(request.id, session);
+    serde_json::to_string(&resp).unwrap()
+}
@@ -1,10 +1,26 @@
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use crate::access::Principal;
 use crate::protocol::*;
+use crate::recording::{self, MAX_RECORDING_CHUNK};
 use crate::state::{ClientId, DaemonState};
-use crate::terminal::TerminalHandle;
+use crate::terminal::{decode_scrollback, resolve_cwd, Subscriber, TerminalHandle, Utf8Decoder};
+
+/// PTY reads queued for forwarding before the reader pauses
+const READ_QUEUE: usize = 64;
+/// Output is held this long to coalesce bursts into one event
+const COALESCE_INTERVAL: Duration = Duration::from_millis(8);
+/// An event is sent as soon as this much output is pending
+const COALESCE_BYTES: usize = 64 * 1024;
 
 pub async fn handle_open(
     request: &Request,
@@ -41,11 +57,40 @@ pub async fn handle_open(
         return serde_json::to_string(&resp).unwrap();
     }
 
+    let profile = match params.profile {
+        None => TerminalProfile::default(),
+        Some(TerminalProfileRef::Inline(profile)) => profile,
+        Some(TerminalProfileRef::Named(name)) => match state.terminal_profiles.get(&name) {
+            Some(profile) => profile.clone(),
+            None => {
+                let resp = ErrorResponse::new(
+                    request.id,
+                    INVALID_PARAMS,
+                    format!("Unknown terminal profile: {name}"),
+                );
+                return serde_json::to_string(&resp).unwrap();
+            }
+        },
+    };
+    if profile.argv.as_ref().is_some_and(|argv| argv.is_empty()) {
+        let resp = ErrorResponse::new(request.id, INVALID_PARAMS, "Profile argv must not be empty");
+        return serde_json::to_string(&resp).unwrap();
+    }
+
+    let cwd = match resolve_cwd(Path::new(&params.session_id), profile.cwd.as_deref()) {
+        Ok(cwd) => cwd,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, e);
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
     // Open PTY
-    let cwd = PathBuf::from(&params.session_id);
     let (handle, reader) = match TerminalHandle::open(
+        params.session_id.clone(),
         params.terminal_id.clone(),
         &cwd,
+        &profile,
         params.cols,
         params.rows,
     ) {
@@ -69,13 +114,30 @@ pub async fn handle_open(
         return serde_json::to_string(&resp).unwrap();
     }
 
-    state.store_terminal(key.clone(), handle, client_id).await;
+    let mut recording_id = None;
+    if params.record.unwrap_or(state.record_terminals) {
+        let dir = recording::recordings_dir(&state.data_dir);
+        match handle.start_recording(&dir, params.cols, params.rows) {
+            Ok(id) => recording_id = Some(id),
+            Err(e) => {
+                handle.kill().await;
+                let resp = ErrorResponse::new(request.id, INTERNAL_ERROR, e);
+                return serde_json::to_string(&resp).unwrap();
+            }
+        }
+    }
+
+    handle.subscribe(client_id, Subscriber {
+        role: TerminalRole::ReadWrite,
+        encoding: params.encoding,
+    });
+    state.store_terminal(key.clone(), handle.clone()).await;
 
-    // Spawn reader thread to stream output to owning client
+    // Spawn reader thread to stream output to subscribed clients
     spawn_terminal_reader(
         reader,
         state,
-        client_id,
+        handle,
         params.session_id,
         params.terminal_id.clone(),
         key,
@@ -83,76 +145,187 @@ pub async fn handle_open(
 
     let resp = SuccessResponse::new(request.id, TerminalOpenResult {
         terminal_id: params.terminal_id,
+        recording_id,
     });
     serde_json::to_string(&resp).unwrap()
 }
 
+/// Read PTY output on a blocking thread and forward it, coalesced, to subscribers.
+///
+/// The reader hands chunks to the forwarder through a bounded queue, so the PTY read
+/// pauses if forwarding falls behind.
 fn spawn_terminal_reader(
     mut reader: Box<dyn std::io::Read + Send>,
     state: Arc<DaemonState>,
-    owner_client_id: ClientId,
+    handle: Arc<TerminalHandle>,
     session_id: String,
     terminal_id: String,
     key: String,
 ) {
+    let (tx, rx) = mpsc::channel::<Vec<u8>>(READ_QUEUE);
+
     std::thread::spawn(move || {
         let mut buffer = [0u8; 8192];
-        let rt = tokio::runtime::Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .unwrap();
-
         loop {
             match reader.read(&mut buffer) {
                 Ok(0) => break,
                 Ok(count) => {
-                    let data = String::from_utf8_lossy(&buffer[..count]).to_string();
-
-                    // Create terminal_output event
-                    let event = Event::new(
-                        EVENT_TERMINAL_OUTPUT,
-                        TerminalOutputParams {
-                            session_id: session_id.clone(),
-                            terminal_id: terminal_id.clone(),
-                            data,
-                        },
-                    );
-                    let json = serde_json::to_string(&event).unwrap();
-
-                    // Send to owning client
-                    rt.block_on(state.send_to_client(owner_client_id, json));
+                    if tx.blocking_send(buffer[..count].to_vec()).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+
+    tokio::spawn(forward_terminal_output(
+        rx,
+        state,
+        handle,
+        session_id,
+        terminal_id,
+        key,
+    ));
+}
+
+async fn forward_terminal_output(
+    mut rx: mpsc::Receiver<Vec<u8>>,
+    state: Arc<DaemonState>,
+    handle: Arc<TerminalHandle>,
+    session_id: String,
+    terminal_id: String,
+    key: String,
+) {
+    let mut decoder = Utf8Decoder::default();
+    let mut pending = Vec::new();
+    let mut eof = false;
+
+    while !eof {
+        // Wait for output, then coalesce until the size cap or the interval elapses
+        match rx.recv().await {
+            Some(chunk) => pending.extend_from_slice(&chunk),
+            None => break,
+        }
+        let deadline = Instant::now() + COALESCE_INTERVAL;
+        while pending.len() < COALESCE_BYTES {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(chunk)) => pending.extend_from_slice(&chunk),
+                Ok(None) => {
+                    eof = true;
+                    break;
                 }
                 Err(_) => break,
             }
         }
 
-        // Terminal exited
-        let exit_code = rt.block_on(async {
-            if let Some(handle) = state.get_terminal(&key).await {
-                handle.try_wait().await.flatten()
-            } else {
-                None
+        let chunk = std::mem::take(&mut pending);
+        // Decode even while detached so split sequences stay in step
+        let text = decoder.decode(&chunk);
+        if !text.is_empty() {
+            handle.record_output(&text);
+        }
+
+        // Buffer for replay; nobody to send to while detached
+        let subscribers = handle.push_output(&chunk);
+
+        // Fan out to all subscribers, one event per encoding
+        for encoding in [TerminalEncoding::Utf8, TerminalEncoding::Base64] {
+            let clients: Vec<ClientId> = subscribers
+                .iter()
+                .filter(|(_, sub)| sub.encoding == encoding)
+                .map(|(id, _)| *id)
+                .collect();
+            if clients.is_empty() {
+                continue;
             }
-        });
 
-        // Send terminal_exited event
+            let data = match encoding {
+                TerminalEncoding::Utf8 if text.is_empty() => continue,
+                TerminalEncoding::Utf8 => text.clone(),
+                TerminalEncoding::Base64 => BASE64.encode(&chunk),
+            };
+
+            // Create terminal_output event
+            let event = Event::new(
+                EVENT_TERMINAL_OUTPUT,
+                TerminalOutputParams {
+                    session_id: session_id.clone(),
+                    terminal_id: terminal_id.clone(),
+                    data,
+                    encoding,
+                },
+            );
+            let json = serde_json::to_string(&event).unwrap();
+            for client_id in clients {
+                deliver_output(&state, &handle, client_id, json.clone(), chunk.len()).await;
+            }
+        }
+    }
+
+    // Terminal exited
+    let exit_code = handle.try_wait().await.flatten();
+
+    // Send terminal_exited event
+    let event = Event::new(
+        EVENT_TERMINAL_EXITED,
+        TerminalExitedParams {
+            session_id,
+            terminal_id,
+            exit_code,
+        },
+    );
+    let json = serde_json::to_string(&event).unwrap();
+    state.send_to_clients(&handle.subscribers(), json).await;
+
+    // Clean up terminal (unless it was already replaced under the same key)
+    if state
+        .get_terminal(&key)
+        .await
+        .is_some_and(|current| Arc::ptr_eq(&current, &handle))
+    {
+        state.close_terminal(&key).await;
+    }
+}
+
+/// Send output to one subscriber. If its queue is full the output is dropped; a
+/// `terminal_output_dropped` event precedes the next output that fits.
+async fn deliver_output(
+    state: &DaemonState,
+    handle: &TerminalHandle,
+    client_id: ClientId,
+    json: String,
+    bytes: usize,
+) {
+    let clients = state.clients.read().await;
+    let Some(tx) = clients.get(&client_id) else {
+        return;
+    };
+
+    let dropped = handle.dropped_bytes(client_id);
+    if dropped > 0 {
         let event = Event::new(
-            EVENT_TERMINAL_EXITED,
-            TerminalExitedParams {
-                session_id: session_id.clone(),
-                terminal_id: terminal_id.clone(),
-                exit_code,
+            EVENT_TERMINAL_OUTPUT_DROPPED,
+            TerminalOutputDroppedParams {
+                session_id: handle.session_id().to_string(),
+                terminal_id: handle.terminal_id().to_string(),
+                dropped_bytes: dropped,
             },
         );
-        let json = serde_json::to_string(&event).unwrap();
-        rt.block_on(state.send_to_client(owner_client_id, json));
+        if !tx.offer(serde_json::to_string(&event).unwrap()) {
+            handle.add_dropped(client_id, bytes);
+            return;
+        }
+        debug!("Client {client_id} missed {dropped} bytes of terminal {}", handle.terminal_id());
+        handle.clear_dropped(client_id);
+    }
 
-        // Clean up terminal
-        rt.block_on(state.close_terminal(&key));
-    });
+    if !tx.offer(json) {
+        handle.add_dropped(client_id, bytes);
+    }
 }
 
-pub async fn handle_write(request: &Request, state: &DaemonState) -> String {
+pub async fn handle_write(request: &Request, state: &DaemonState, client_id: ClientId) -> String {
     let params: TerminalWriteParams = match serde_json::from_value(request.params.clone()) {
         Ok(p) => p,
         Err(e) => {
@@ -165,7 +338,21 @@ pub async fn handle_write(request: &Request, state: &DaemonState) -> String {
 
     match state.get_terminal(&key).await {
         Some(handle) => {
-            if let Err(e) = handle.write(params.data.as_bytes()).await {
+            if let Some(resp) = require_read_write(request, &handle, client_id) {
+                return resp;
+            }
+            let data = match params.encoding {
+                TerminalEncoding::Utf8 => params.data.into_bytes(),
+                TerminalEncoding::Base64 => match BASE64.decode(&params.data) {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        let resp =
+                            ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid base64 data: {e}"));
+                        return serde_json::to_string(&resp).unwrap();
+                    }
+                },
+            };
+            if let Err(e) = handle.write(&data).await {
                 let resp = ErrorResponse::new(request.id, INTERNAL_ERROR, e);
                 return serde_json::to_string(&resp).unwrap();
             }
@@ -183,7 +370,7 @@ pub async fn handle_write(request: &Request, state: &DaemonState) -> String {
     }
 }
 
-pub async fn handle_resize(request: &Request, state: &DaemonState) -> String {
+pub async fn handle_resize(request: &Request, state: &DaemonState, client_id: ClientId) -> String {
     let params: TerminalResizeParams = match serde_json::from_value(request.params.clone()) {
         Ok(p) => p,
         Err(e) => {
@@ -196,6 +383,9 @@ pub async fn handle_resize(request: &Request, state: &DaemonState) -> String {
 
     match state.get_terminal(&key).await {
         Some(handle) => {
+            if let Some(resp) = require_read_write(request, &handle, client_id) {
+                return resp;
+            }
             if let Err(e) = handle.resize(params.cols, params.rows).await {
                 let resp = ErrorResponse::new(request.id, INTERNAL_ERROR, e);
                 return serde_json::to_string(&resp).unwrap();
@@ -214,7 +404,7 @@ pub async fn handle_resize(request: &Request, state: &DaemonState) -> String {
     }
 }
 
-pub async fn handle_close(request: &Request, state: &DaemonState) -> String {
+pub async fn handle_close(request: &Request, state: &DaemonState, client_id: ClientId) -> String {
     let params: TerminalCloseParams = match serde_json::from_value(request.params.clone()) {
         Ok(p) => p,
         Err(e) => {
@@ -225,13 +415,17 @@ pub async fn handle_close(request: &Request, state: &DaemonState) -> String {
 
     let key = DaemonState::terminal_key(&params.session_id, &params.terminal_id);
 
-    if !state.terminal_exists(&key).await {
+    let Some(handle) = state.get_terminal(&key).await else {
         let resp = ErrorResponse::new(
             request.id,
             TERMINAL_NOT_FOUND,
             format!("Terminal not found: {}", params.terminal_id),
         );
         return serde_json::to_string(&resp).unwrap();
+    };
+
+    if let Some(resp) = require_read_write(request, &handle, client_id) {
+        return resp;
     }
 
     state.close_terminal(&key).await;
@@ -239,3 +433,306 @@ pub async fn handle_close(request: &Request, state: &DaemonState) -> String {
     let resp = SuccessResponse::new(request.id, serde_json::json!({}));
     serde_json::to_string(&resp).unwrap()
 }
+
+pub async fn handle_list(
+    request: &Request,
+    state: &DaemonState,
+    client_id: ClientId,
+    principal: &Principal,
+) -> String {
+    let params: TerminalListParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    let mut terminals: Vec<TerminalInfo> = state
+        .terminals
+        .read()
+        .await
+        .values()
+        .filter(|h| principal.allows_session(h.session_id()))
+        .filter(|h| params.session_id.as_deref().is_none_or(|s| s == h.session_id()))
+        .map(|h| TerminalInfo {
+            session_id: h.session_id().to_string(),
+            terminal_id: h.terminal_id().to_string(),
+            attached: !h.subscribers().is_empty(),
+            subscribers: h.subscribers().len(),
+            role: h.role(client_id),
+            detached_secs: h.detached_at().map(|t| t.elapsed().as_secs()),
+        })
+        .collect();
+    terminals.sort_by(|a, b| {
+        (&a.session_id, &a.terminal_id).cmp(&(&b.session_id, &b.terminal_id))
+    });
+
+    let resp = SuccessResponse::new(request.id, TerminalListResult { terminals });
+    serde_json::to_string(&resp).unwrap()
+}
+
+/// Reattach to a terminal with read-write access, replaying its scrollback
+pub async fn handle_attach(request: &Request, state: &DaemonState, client_id: ClientId) -> String {
+    let params: TerminalAttachParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    // Reattaching keeps the current role; a read-only subscriber cannot take write access
+    let key = DaemonState::terminal_key(&params.session_id, &params.terminal_id);
+    let current = match state.get_terminal(&key).await {
+        Some(handle) => handle.role(client_id),
+        None => None,
+    };
+    let role = match (current, params.role) {
+        (Some(TerminalRole::ReadOnly), Some(TerminalRole::ReadWrite)) => {
+            let resp = ErrorResponse::new(
+                request.id,
+                FORBIDDEN,
+                format!("Read-only subscriber of terminal: {}", params.terminal_id),
+            );
+            return serde_json::to_string(&resp).unwrap();
+        }
+        (_, Some(role)) => role,
+        (Some(role), None) => role,
+        (None, None) => TerminalRole::ReadWrite,
+    };
+
+    let subscriber = Subscriber {
+        role,
+        encoding: params.encoding,
+    };
+    subscribe(request, state, client_id, &params.session_id, params.terminal_id, subscriber).await
+}
+
+pub async fn handle_subscribe(request: &Request, state: &DaemonState, client_id: ClientId) -> String {
+    let params: TerminalSubscribeParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    let subscriber = Subscriber {
+        role: params.role,
+        encoding: params.encoding,
+    };
+    subscribe(request, state, client_id, &params.session_id, params.terminal_id, subscriber).await
+}
+
+pub async fn handle_unsubscribe(
+    request: &Request,
+    state: Arc<DaemonState>,
+    client_id: ClientId,
+) -> String {
+    let params: TerminalCloseParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    let key = DaemonState::terminal_key(&params.session_id, &params.terminal_id);
+
+    let Some(handle) = state.get_terminal(&key).await else {
+        let resp = ErrorResponse::new(
+            request.id,
+            TERMINAL_NOT_FOUND,
+            format!("Terminal not found: {}", params.terminal_id),
+        );
+        return serde_json::to_string(&resp).unwrap();
+    };
+
+    state.release_terminal(&key, &handle, client_id).await;
+
+    let resp = SuccessResponse::new(request.id, serde_json::json!({}));
+    serde_json::to_string(&resp).unwrap()
+}
+
+/// Subscribe a client to a terminal and return its scrollback
+async fn subscribe(
+    request: &Request,
+    state: &DaemonState,
+    client_id: ClientId,
+    session_id: &str,
+    terminal_id: String,
+    subscriber: Subscriber,
+) -> String {
+    let key = DaemonState::terminal_key(session_id, &terminal_id);
+
+    let Some(handle) = state.get_terminal(&key).await else {
+        let resp = ErrorResponse::new(
+            request.id,
+            TERMINAL_NOT_FOUND,
+            format!("Terminal not found: {terminal_id}"),
+        );
+        return serde_json::to_string(&resp).unwrap();
+    };
+
+    // Output read after this point is sent to this client as well
+    let scrollback = handle.subscribe(client_id, subscriber);
+    info!("Client {client_id} subscribed to terminal {key} ({:?})", subscriber.role);
+
+    let scrollback = match subscriber.encoding {
+        TerminalEncoding::Utf8 => decode_scrollback(&scrollback),
+        TerminalEncoding::Base64 => BASE64.encode(&scrollback),
+    };
+
+    let resp = SuccessResponse::new(request.id, TerminalAttachResult {
+        terminal_id,
+        role: subscriber.role,
+        scrollback,
+        encoding: subscriber.encoding,
+    });
+    serde_json::to_string(&resp).unwrap()
+}
+
+/// Error response unless the client is a read-write subscriber
+fn require_read_write(
+    request: &Request,
+    handle: &TerminalHandle,
+    client_id: ClientId,
+) -> Option<String> {
+    match handle.role(client_id) {
+        Some(TerminalRole::ReadWrite) => None,
+        Some(TerminalRole::ReadOnly) => {
+            let resp = ErrorResponse::new(
+                request.id,
+                FORBIDDEN,
+                format!("Read-only subscriber of terminal: {}", handle.terminal_id()),
+            );
+            Some(serde_json::to_string(&resp).unwrap())
+        }
+        None => {
+            let resp = ErrorResponse::new(
+                request.id,
+                FORBIDDEN,
+                format!("Not attached to terminal: {}", handle.terminal_id()),
+            );
+            Some(serde_json::to_string(&resp).unwrap())
+        }
+    }
+}
+
+/// Named terminal profiles configured in config.json
+pub fn handle_profile_list(request: &Request, state: &DaemonState) -> String {
+    let mut profiles: Vec<NamedTerminalProfile> = state
+        .terminal_profiles
+        .iter()
+        .map(|(name, profile)| NamedTerminalProfile {
+            name: name.clone(),
+            profile: profile.clone(),
+        })
+        .collect();
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let resp = SuccessResponse::new(request.id, TerminalProfileListResult { profiles });
+    serde_json::to_string(&resp).unwrap()
+}
+
+pub async fn handle_recording_list(
+    request: &Request,
+    state: &DaemonState,
+    principal: &Principal,
+) -> String {
+    let params: RecordingListParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    let dir = recording::recordings_dir(&state.data_dir);
+    let recordings = match tokio::task::spawn_blocking(move || recording::list_recordings(&dir)).await {
+        Ok(Ok(recordings)) => recordings,
+        Ok(Err(e)) => {
+            let resp = ErrorResponse::new(request.id, INTERNAL_ERROR, e);
+            return serde_json::to_string(&resp).unwrap();
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INTERNAL_ERROR, format!("Failed to list recordings: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    let recordings = recordings
+        .into_iter()
+        .filter(|r| principal.allows_session(&r.session_id))
+        .filter(|r| params.session_id.as_deref().is_none_or(|s| s == r.session_id))
+        .collect();
+
+    let resp = SuccessResponse::new(request.id, RecordingListResult { recordings });
+    serde_json::to_string(&resp).unwrap()
+}
+
+pub async fn handle_recording_get(
+    request: &Request,
+    state: &DaemonState,
+    principal: &Principal,
+) -> String {
+    let params: RecordingGetParams = match serde_json::from_value(request.params.clone()) {
+        Ok(p) => p,
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, format!("Invalid params: {e}"));
+            return serde_json::to_string(&resp).unwrap();
+        }
+    };
+
+    if !recording::is_valid_recording_id(&params.recording_id) {
+        let resp = ErrorResponse::new(
+            request.id,
+            INVALID_PARAMS,
+            format!("Invalid recording id: {}", params.recording_id),
+        );
+        return serde_json::to_string(&resp).unwrap();
+    }
+
+    let dir = recording::recordings_dir(&state.data_dir);
+    let recording_id = params.recording_id.clone();
+    let offset = params.offset;
+    let result = tokio::task::spawn_blocking(move || {
+        let session = recording::recording_session(&dir, &recording_id);
+        recording::read_recording(&dir, &recording_id, offset, MAX_RECORDING_CHUNK)
+            .map(|(data, total)| (session, data, total))
+    })
+    .await;
+
+    match result {
+        Ok(Ok((session, data, total_size))) => {
+            // Session-restricted tokens only see recordings of their sessions
+            if principal.sessions.is_some() && !session.is_some_and(|s| principal.allows_session(&s)) {
+                let resp = ErrorResponse::new(
+                    request.id,
+                    FORBIDDEN,
+                    format!("Token '{}' may not access recording: {}", principal.name, params.recording_id),
+                );
+                return serde_json::to_string(&resp).unwrap();
+            }
+
+            let end = params.offset + data.len() as u64;
+            let resp = SuccessResponse::new(request.id, RecordingGetResult {
+                recording_id: params.recording_id,
+                data,
+                offset: params.offset,
+                next_offset: (end < total_size).then_some(end),
+                total_size,
+            });
+            serde_json::to_string(&resp).unwrap()
+        }
+        Ok(Err(e)) => {
+            let resp = ErrorResponse::new(request.id, INVALID_PARAMS, e);
+            serde_json::to_string(&resp).unwrap()
+        }
+        Err(e) => {
+            let resp = ErrorResponse::new(request.id, INTERNAL_ERROR, format!("Failed to read recording: {e}"));
+            serde_json::to_string(&resp).unwrap()
+        }
+    }
+}
@@ -1,3 +1,6 @@
+mod access;
+mod audit;
+mod auth;
 mod config;
 mod connection;
 mod claude_sdk;
@@ -5,17 +8,28 @@ mod git;
 mod handlers;
 mod opencode;
 mod protocol;
+mod recording;
 mod state;
 mod terminal;
+mod tls;
+mod transport;
+mod watcher;
 
+use std::ffi::OsString;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::Path;
 use std::sync::Arc;
 
 use clap::Parser;
-use tokio::net::TcpListener;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, UnixListener};
+use tokio::time::timeout;
+use tokio_rustls::TlsAcceptor;
 use tracing::{error, info, warn};
 use tracing_subscriber::EnvFilter;
 
-use config::{Args, SessionsConfig};
+use access::ApiToken;
+use config::{Args, DaemonConfig, SessionsConfig};
 use state::DaemonState;
 
 #[tokio::main]
@@ -30,20 +44,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Parse CLI arguments
     let args = Args::parse();
 
-    // Determine token
-    let token = if args.require_auth() {
-        match &args.token {
-            Some(t) => Some(t.clone()),
-            None => {
-                error!("Token required. Use --token or set MAESTRO_DAEMON_TOKEN");
-                std::process::exit(1);
-            }
-        }
-    } else {
-        warn!("Auth disabled (--insecure-no-auth). Do not use in production!");
-        None
-    };
-
     // Load sessions config
     let data_dir = args.data_dir();
     info!("Data directory: {}", data_dir.display());
@@ -53,6 +53,29 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         info!("Created data directory: {}", data_dir.display());
     }
 
+    // Determine tokens (--token is an admin token alongside config.json tokens)
+    let daemon_config = DaemonConfig::load(&data_dir)?;
+    let terminal_profiles = daemon_config.terminal_profiles;
+    let tokens = if args.require_auth() {
+        let mut tokens = daemon_config.tokens;
+        if let Some(t) = &args.token {
+            tokens.push(ApiToken::admin("default", t));
+        }
+        if tokens.is_empty() {
+            if args.needs_token() {
+                error!("Token required. Use --token, set MAESTRO_DAEMON_TOKEN or add tokens to config.json");
+                std::process::exit(1);
+            }
+            info!("No token configured; only same-user Unix socket peers can connect");
+        } else {
+            info!("Loaded {} API token(s)", tokens.len());
+        }
+        tokens
+    } else {
+        warn!("Auth disabled (--insecure-no-auth). Do not use in production!");
+        Vec::new()
+    };
+
     let sessions_config = SessionsConfig::load(&data_dir)?;
     let sessions = sessions_config.to_session_infos();
     info!("Loaded {} session(s)", sessions.len());
@@ -66,19 +89,164 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Create shared state
-    let state = Arc::new(DaemonState::new(token, sessions));
+    let mut state = DaemonState::new(tokens, sessions, data_dir.clone());
+    state.terminal_grace = std::time::Duration::from_secs(args.terminal_grace_secs);
+    state.record_terminals = args.record_terminals;
+    state.terminal_profiles = terminal_profiles;
+    let state = Arc::new(state);
+
+    // Watch sessions.json for external edits (keep the watcher alive for the daemon lifetime)
+    let _sessions_watcher = match watcher::spawn_sessions_watcher(state.clone()) {
+        Ok(w) => Some(w),
+        Err(e) => {
+            warn!("sessions.json hot reload disabled: {e}");
+            None
+        }
+    };
+
+    // Optional TLS
+    let tls_acceptor = if args.tls_enabled() {
+        let (acceptor, fingerprint) =
+            tls::load_acceptor(args.tls_cert.as_deref(), args.tls_key.as_deref(), &data_dir)?;
+        info!("TLS enabled, certificate SHA-256 fingerprint: {fingerprint}");
+        Some(acceptor)
+    } else {
+        None
+    };
+
+    let mut listeners = Vec::new();
 
     // Bind TCP listener
-    let listener = TcpListener::bind(&args.listen).await?;
-    info!("Listening on {}", args.listen);
+    if let Some(listen) = args.listen_addr() {
+        let listener = TcpListener::bind(&listen).await?;
+        info!("Listening on {listen}");
+        listeners.push(tokio::spawn(accept_loop(
+            listener,
+            tls_acceptor.clone(),
+            Framing::Lines,
+            state.clone(),
+        )));
+    }
 
-    // Accept loop
+    // Bind WebSocket listener
+    if let Some(ws_listen) = &args.ws_listen {
+        let listener = TcpListener::bind(ws_listen).await?;
+        info!("Listening for WebSocket clients on {ws_listen}");
+        listeners.push(tokio::spawn(accept_loop(
+            listener,
+            tls_acceptor.clone(),
+            Framing::WebSocket,
+            state.clone(),
+        )));
+    }
+
+    // Bind Unix socket listener
+    if let Some(socket_path) = &args.unix_socket {
+        let listener = bind_unix_socket(socket_path)?;
+        info!("Listening on Unix socket {}", socket_path.display());
+        listeners.push(tokio::spawn(accept_unix_loop(
+            listener,
+            args.unix_socket_require_token,
+            state.clone(),
+        )));
+    }
+
+    futures::future::join_all(listeners).await;
+    Ok(())
+}
+
+/// Bind a Unix socket readable and writable only by the daemon user
+fn bind_unix_socket(path: &Path) -> Result<UnixListener, String> {
+    // Remove a stale socket left by a previous run (never an unrelated file)
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(format!("{} exists and is not a socket", path.display()));
+        }
+        std::fs::remove_file(path)
+            .map_err(|e| format!("Failed to remove stale socket {}: {e}", path.display()))?;
+    }
+
+    // Bind inside a private 0700 directory and narrow the socket to 0600 before
+    // moving it into place, so other users never get a window to connect
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("Invalid socket path {}", path.display()))?;
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut private_name = OsString::from(".");
+    private_name.push(name);
+    private_name.push(format!(".{}", std::process::id()));
+    let private_dir = parent.join(private_name);
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private_dir)
+        .map_err(|e| format!("Failed to create {}: {e}", private_dir.display()))?;
+
+    let private_path = private_dir.join(name);
+    let listener = UnixListener::bind(&private_path)
+        .map_err(|e| format!("Failed to bind {}: {e}", path.display()))
+        .and_then(|listener| {
+            std::fs::set_permissions(&private_path, std::fs::Permissions::from_mode(0o600))
+                .map_err(|e| format!("Failed to set permissions on {}: {e}", path.display()))?;
+            std::fs::rename(&private_path, path)
+                .map_err(|e| format!("Failed to move socket to {}: {e}", path.display()))?;
+            Ok(listener)
+        });
+
+    let _ = std::fs::remove_file(&private_path);
+    let _ = std::fs::remove_dir(&private_dir);
+    listener
+}
+
+/// Accept Unix socket connections forever
+async fn accept_unix_loop(listener: UnixListener, require_token: bool, state: Arc<DaemonState>) {
     loop {
         match listener.accept().await {
             Ok((stream, _)) => {
                 let state = state.clone();
                 tokio::spawn(async move {
-                    connection::handle_client(stream, state).await;
+                    connection::handle_unix_client(stream, require_token, state).await;
+                });
+            }
+            Err(e) => {
+                error!("Unix socket accept error: {e}");
+            }
+        }
+    }
+}
+
+/// Wire format spoken on a listener
+#[derive(Clone, Copy)]
+enum Framing {
+    /// Newline-delimited JSON
+    Lines,
+    /// One JSON frame per WebSocket text message
+    WebSocket,
+}
+
+/// Accept connections forever, optionally wrapping them in TLS
+async fn accept_loop(
+    listener: TcpListener,
+    tls_acceptor: Option<TlsAcceptor>,
+    framing: Framing,
+    state: Arc<DaemonState>,
+) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                let state = state.clone();
+                let tls_acceptor = tls_acceptor.clone();
+                let peer = peer.to_string();
+                tokio::spawn(async move {
+                    match tls_acceptor {
+                        Some(acceptor) => {
+                            match timeout(tls::TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                                Ok(Ok(stream)) => serve(stream, peer, framing, state).await,
+                                Ok(Err(e)) => warn!("TLS handshake failed for {peer}: {e}"),
+                                Err(_) => warn!("TLS handshake timeout for {peer}"),
+                            }
+                        }
+                        None => serve(stream, peer, framing, state).await,
+                    }
                 });
             }
             Err(e) => {
@@ -87,3 +255,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 }
+
+async fn serve<S>(stream: S, peer: String, framing: Framing, state: Arc<DaemonState>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    match framing {
+        Framing::Lines => connection::handle_client(stream, peer, state).await,
+        Framing::WebSocket => connection::handle_ws_client(stream, peer, state).await,
+    }
+}
@@ -247,7 +247,7 @@ async fn run_sse_stream(
                     let msg =
                         serde_json::to_string(&event).unwrap_or_else(|_| "{}".to_string());
 
-                    state.broadcast_to_all_clients(msg).await;
+                    state.broadcast_to_session_clients(workspace_path, msg).await;
                 }
             }
             Err(e) => {
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
 
 /// JSON-RPC request from client
 #[derive(Debug, Deserialize)]
@@ -41,14 +42,35 @@ pub struct Event {
 // Error codes
 pub const AUTH_REQUIRED: &str = "auth_required";
 pub const AUTH_FAILED: &str = "auth_failed";
+pub const AUTH_LOCKED: &str = "auth_locked";
+pub const FORBIDDEN: &str = "forbidden";
 pub const INVALID_PARAMS: &str = "invalid_params";
 pub const SESSION_NOT_FOUND: &str = "session_not_found";
+pub const SESSION_EXISTS: &str = "session_exists";
 pub const TERMINAL_NOT_FOUND: &str = "terminal_not_found";
 pub const TERMINAL_EXISTS: &str = "terminal_exists";
 pub const GIT_ERROR: &str = "git_error";
+pub const NOT_A_GIT_REPO: &str = "not_a_git_repo";
+pub const NOTHING_TO_COMMIT: &str = "nothing_to_commit";
+pub const CONFIRMATION_REQUIRED: &str = "confirmation_required";
+pub const DIFF_CHANGED: &str = "diff_changed";
+pub const COMMIT_NOT_FOUND: &str = "commit_not_found";
+pub const DIFF_NOT_FOUND: &str = "diff_not_found";
+pub const BRANCH_NOT_FOUND: &str = "branch_not_found";
+pub const BRANCH_EXISTS: &str = "branch_exists";
+pub const REMOTE_NOT_FOUND: &str = "remote_not_found";
+pub const GIT_BUSY: &str = "git_busy";
+pub const GIT_AUTH_FAILED: &str = "git_auth_failed";
+pub const NOT_CONFLICTED: &str = "not_conflicted";
+pub const UNRESOLVED_CONFLICTS: &str = "unresolved_conflicts";
+pub const NO_OPERATION_IN_PROGRESS: &str = "no_operation_in_progress";
+pub const WORKTREE_DIRTY: &str = "worktree_dirty";
 pub const INTERNAL_ERROR: &str = "internal_error";
 pub const OPENCODE_ERROR: &str = "opencode_error";
 pub const OPENCODE_NOT_CONNECTED: &str = "opencode_not_connected";
+pub const EXEC_EXISTS: &str = "exec_exists";
+pub const EXEC_NOT_FOUND: &str = "exec_not_found";
+pub const EXEC_ERROR: &str = "exec_error";
 pub const CLAUDE_SDK_ERROR: &str = "claude_sdk_error";
 pub const CLAUDE_SDK_NOT_CONNECTED: &str = "claude_sdk_not_connected";
 
@@ -56,13 +78,46 @@ pub const CLAUDE_SDK_NOT_CONNECTED: &str = "claude_sdk_not_connected";
 pub const METHOD_AUTH: &str = "auth";
 pub const METHOD_LIST_SESSIONS: &str = "list_sessions";
 pub const METHOD_SESSION_INFO: &str = "session_info";
+pub const METHOD_SESSION_ADD: &str = "session_add";
+pub const METHOD_SESSION_REMOVE: &str = "session_remove";
+pub const METHOD_SESSION_RENAME: &str = "session_rename";
 pub const METHOD_TERMINAL_OPEN: &str = "terminal_open";
 pub const METHOD_TERMINAL_WRITE: &str = "terminal_write";
 pub const METHOD_TERMINAL_RESIZE: &str = "terminal_resize";
 pub const METHOD_TERMINAL_CLOSE: &str = "terminal_close";
+pub const METHOD_TERMINAL_LIST: &str = "terminal_list";
+pub const METHOD_TERMINAL_ATTACH: &str = "terminal_attach";
+pub const METHOD_TERMINAL_SUBSCRIBE: &str = "terminal_subscribe";
+pub const METHOD_TERMINAL_UNSUBSCRIBE: &str = "terminal_unsubscribe";
+pub const METHOD_TERMINAL_RECORDING_LIST: &str = "terminal_recording_list";
+pub const METHOD_TERMINAL_RECORDING_GET: &str = "terminal_recording_get";
+pub const METHOD_TERMINAL_PROFILE_LIST: &str = "terminal_profile_list";
+pub const METHOD_EXEC: &str = "exec";
+pub const METHOD_EXEC_CANCEL: &str = "exec_cancel";
 pub const METHOD_GIT_STATUS: &str = "git_status";
 pub const METHOD_GIT_DIFF: &str = "git_diff";
+pub const METHOD_GIT_DIFF_FILE: &str = "git_diff_file";
 pub const METHOD_GIT_LOG: &str = "git_log";
+pub const METHOD_GIT_SHOW: &str = "git_show";
+pub const METHOD_GIT_BRANCHES: &str = "git_branches";
+pub const METHOD_GIT_BRANCH_CREATE: &str = "git_branch_create";
+pub const METHOD_GIT_CHECKOUT: &str = "git_checkout";
+pub const METHOD_GIT_BRANCH_DELETE: &str = "git_branch_delete";
+pub const METHOD_GIT_STAGE: &str = "git_stage";
+pub const METHOD_GIT_UNSTAGE: &str = "git_unstage";
+pub const METHOD_GIT_DISCARD: &str = "git_discard";
+pub const METHOD_GIT_COMMIT: &str = "git_commit";
+pub const METHOD_GIT_STAGE_HUNKS: &str = "git_stage_hunks";
+pub const METHOD_GIT_UNSTAGE_HUNKS: &str = "git_unstage_hunks";
+pub const METHOD_GIT_FETCH: &str = "git_fetch";
+pub const METHOD_GIT_PULL: &str = "git_pull";
+pub const METHOD_GIT_PUSH: &str = "git_push";
+pub const METHOD_GIT_CONFLICTS: &str = "git_conflicts";
+pub const METHOD_GIT_RESOLVE: &str = "git_resolve";
+pub const METHOD_GIT_MERGE_ABORT: &str = "git_merge_abort";
+pub const METHOD_GIT_REBASE_CONTINUE: &str = "git_rebase_continue";
+pub const METHOD_GIT_REBASE_ABORT: &str = "git_rebase_abort";
+pub const METHOD_AUDIT_QUERY: &str = "audit_query";
 
 // OpenCode method names
 pub const METHOD_OPENCODE_CONNECT_WORKSPACE: &str = "opencode_connect_workspace";
@@ -93,6 +148,13 @@ pub const METHOD_CLAUDE_SDK_SESSION_SETTINGS_UPDATE: &str = "claude_sdk_session_
 // Event names
 pub const EVENT_TERMINAL_OUTPUT: &str = "terminal_output";
 pub const EVENT_TERMINAL_EXITED: &str = "terminal_exited";
+pub const EVENT_TERMINAL_OUTPUT_DROPPED: &str = "terminal_output_dropped";
+pub const EVENT_SESSIONS_CHANGED: &str = "sessions_changed";
+pub const EVENT_EXEC_OUTPUT: &str = "exec_output";
+pub const EVENT_EXEC_EXITED: &str = "exec_exited";
+pub const EVENT_GIT_STATUS_CHANGED: &str = "git_status_changed";
+pub const EVENT_GIT_PROGRESS: &str = "git_progress";
+pub const EVENT_GIT_OPERATION_FINISHED: &str = "git_operation_finished";
 #[allow(dead_code)]
 pub const EVENT_OPENCODE: &str = "opencode:event";
 
@@ -108,12 +170,59 @@ pub struct SessionIdParams {
     pub session_id: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SessionAddParams {
+    pub path: String,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionRenameParams {
+    pub session_id: String,
+    pub name: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct TerminalOpenParams {
     pub session_id: String,
     pub terminal_id: String,
     pub cols: u16,
     pub rows: u16,
+    /// Encoding of `terminal_output` data sent to this client
+    #[serde(default)]
+    pub encoding: TerminalEncoding,
+    /// Record to an asciinema cast (defaults to the daemon's --record-terminals)
+    #[serde(default)]
+    pub record: Option<bool>,
+    /// Named profile from config.json or an inline profile (default: interactive $SHELL)
+    #[serde(default)]
+    pub profile: Option<TerminalProfileRef>,
+}
+
+/// What a terminal runs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TerminalProfile {
+    /// Program and arguments to run instead of the shell (e.g. ["bun", "run", "dev"])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub argv: Option<Vec<String>>,
+    /// Start the shell as a login shell (-l) rather than interactive (-i)
+    #[serde(default)]
+    pub login: bool,
+    /// Extra environment variables
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    /// Working directory relative to the session path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+}
+
+/// A profile by name (config.json `terminal_profiles`) or given inline
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum TerminalProfileRef {
+    Named(String),
+    Inline(TerminalProfile),
 }
 
 #[derive(Debug, Deserialize)]
@@ -121,6 +230,8 @@ pub struct TerminalWriteParams {
     pub session_id: String,
     pub terminal_id: String,
     pub data: String,
+    #[serde(default)]
+    pub encoding: TerminalEncoding,
 }
 
 #[derive(Debug, Deserialize)]
@@ -137,12 +248,337 @@ pub struct TerminalCloseParams {
     pub terminal_id: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct TerminalListParams {
+    /// Only terminals in this session
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TerminalAttachParams {
+    pub session_id: String,
+    pub terminal_id: String,
+    /// Defaults to the current role, or read-write for a new subscriber
+    #[serde(default)]
+    pub role: Option<TerminalRole>,
+    #[serde(default)]
+    pub encoding: TerminalEncoding,
+}
+
+/// Encoding of terminal data on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TerminalEncoding {
+    /// UTF-8 text (invalid bytes become U+FFFD)
+    #[default]
+    Utf8,
+    /// Base64 of the raw bytes
+    Base64,
+}
+
+impl TerminalEncoding {
+    pub fn is_utf8(&self) -> bool {
+        *self == TerminalEncoding::Utf8
+    }
+}
+
+/// Access a client has to a shared terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TerminalRole {
+    /// Receives output only
+    #[default]
+    ReadOnly,
+    /// May also write, resize and close
+    ReadWrite,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TerminalSubscribeParams {
+    pub session_id: String,
+    pub terminal_id: String,
+    #[serde(default)]
+    pub role: TerminalRole,
+    #[serde(default)]
+    pub encoding: TerminalEncoding,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecordingListParams {
+    /// Only recordings of this session
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecordingGetParams {
+    pub recording_id: String,
+    /// Byte offset to read from (use `next_offset` from the previous chunk)
+    #[serde(default)]
+    pub offset: u64,
+}
+
+/// `exec` replies `{exec_id}` as soon as the process is spawned. Output streams as
+/// `exec_output` events and completion (exit status, timeout, cancellation) arrives
+/// as a single `exec_exited` event, which is also sent for timed-out processes.
+#[derive(Debug, Deserialize)]
+pub struct ExecParams {
+    pub session_id: String,
+    /// Client-chosen id used to tag `exec_output`/`exec_exited` events
+    pub exec_id: String,
+    /// Program and arguments (no shell)
+    pub argv: Vec<String>,
+    /// Environment overrides
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Kill the process group after this long (reported via `exec_exited.timed_out`)
+    pub timeout_ms: Option<u64>,
+    /// Combined stdout/stderr bytes before the process is killed
+    pub max_output_bytes: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExecCancelParams {
+    pub exec_id: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GitLogParams {
     pub session_id: String,
     pub limit: Option<u32>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct GitFetchParams {
+    pub session_id: String,
+    /// Client-chosen id used to tag `git_progress`/`git_operation_finished` events
+    pub operation_id: String,
+    /// Configured remote (default: the upstream's remote, or origin)
+    pub remote: Option<String>,
+    /// Remove remote-tracking branches deleted on the remote
+    #[serde(default)]
+    pub prune: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GitPullMode {
+    /// Fail unless the branch can be fast-forwarded
+    #[default]
+    FfOnly,
+    /// Rebase local commits onto the fetched branch
+    Rebase,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitPullParams {
+    pub session_id: String,
+    pub operation_id: String,
+    /// Remote and branch to pull (default: the upstream)
+    pub remote: Option<String>,
+    /// Requires `remote`
+    pub branch: Option<String>,
+    #[serde(default)]
+    pub mode: GitPullMode,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitPushParams {
+    pub session_id: String,
+    pub operation_id: String,
+    /// Remote to push to (default: the upstream's remote)
+    pub remote: Option<String>,
+    /// Branch to push (default: the current branch); requires `remote`
+    pub branch: Option<String>,
+    /// Make the pushed branch the upstream of the current branch; requires `remote`
+    #[serde(default)]
+    pub set_upstream: bool,
+    /// Overwrite the remote branch only if it is where we last fetched it
+    #[serde(default)]
+    pub force_with_lease: bool,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GitResolution {
+    /// Our side (HEAD; the branch being rebased onto during a rebase)
+    Ours,
+    Theirs,
+    /// The `content` given in params
+    Content,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitResolveParams {
+    pub session_id: String,
+    pub path: String,
+    pub resolution: GitResolution,
+    /// Resolved file content, with `resolution: "content"`
+    pub content: Option<String>,
+}
+
+/// Whitespace handling of `git_diff`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WhitespaceMode {
+    #[default]
+    Show,
+    /// `-w`
+    IgnoreAll,
+    /// `-b`
+    IgnoreChange,
+    /// `--ignore-space-at-eol`
+    IgnoreAtEol,
+}
+
+/// Options shared by `git_diff` and `git_diff_file`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GitDiffOptions {
+    /// Compare from this ref instead of HEAD/the index. May be a range ("main...HEAD"
+    /// diffs HEAD against its merge base with main, "a..b" diffs a against b).
+    pub base: Option<String>,
+    /// Compare to this ref instead of the working tree (requires a non-range `base`)
+    pub target: Option<String>,
+    /// Lines of context around each change (default 3)
+    pub context_lines: Option<u32>,
+    #[serde(default)]
+    pub whitespace: WhitespaceMode,
+    /// Rename detection similarity in percent; 0 disables rename detection
+    pub rename_threshold: Option<u8>,
+    /// Word-level diffs in `--word-diff=porcelain` format
+    #[serde(default)]
+    pub word_diff: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitDiffParams {
+    pub session_id: String,
+    #[serde(flatten)]
+    pub options: GitDiffOptions,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitDiffFileParams {
+    pub session_id: String,
+    pub path: String,
+    /// Staged (HEAD → index) rather than unstaged diff; ignored with `base`
+    #[serde(default)]
+    pub staged: bool,
+    /// First hunk to return
+    #[serde(default)]
+    pub hunk_offset: usize,
+    /// Maximum hunks to return (default: as many as fit in 1 MB)
+    pub hunk_limit: Option<usize>,
+    #[serde(flatten)]
+    pub options: GitDiffOptions,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitShowParams {
+    pub session_id: String,
+    /// Commit sha or other revision (branch, tag, HEAD~2)
+    pub sha: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitBranchCreateParams {
+    pub session_id: String,
+    pub name: String,
+    /// Commit or branch to start from (default HEAD)
+    pub start_point: Option<String>,
+    /// Track `start_point` as upstream (it must be a remote branch)
+    #[serde(default)]
+    pub track: bool,
+    /// Switch to the new branch
+    #[serde(default)]
+    pub checkout: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitCheckoutParams {
+    pub session_id: String,
+    /// Local branch, or a remote branch name to create a tracking branch for
+    pub branch: String,
+    /// Switch even with uncommitted changes, carrying them over (git still refuses
+    /// if they conflict)
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitBranchDeleteParams {
+    pub session_id: String,
+    pub name: String,
+    /// Delete even if not merged
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Paths are relative to the session directory
+#[derive(Debug, Deserialize)]
+pub struct GitPathsParams {
+    pub session_id: String,
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitDiscardParams {
+    pub session_id: String,
+    pub paths: Vec<String>,
+    /// Also delete untracked files under `paths`
+    #[serde(default)]
+    pub include_untracked: bool,
+    /// Token from a previous preview call; omit to preview
+    pub confirm_token: Option<String>,
+}
+
+/// Hunks of one file's diff (as returned by `git_diff`) to stage or unstage
+#[derive(Debug, Deserialize)]
+pub struct GitHunksParams {
+    pub session_id: String,
+    pub path: String,
+    /// `hash` of the file diff the selection refers to
+    pub diff_hash: String,
+    pub hunks: Vec<GitHunkSelection>,
+    /// Options the diff was fetched with. Only context and rename options are
+    /// accepted: other diffs do not apply to the index.
+    #[serde(flatten)]
+    pub options: GitDiffOptions,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitHunkSelection {
+    /// 0-based index of the hunk in the file diff
+    pub hunk: usize,
+    /// 0-based line offsets within the hunk body to include (all changes if omitted)
+    pub lines: Option<Vec<usize>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitCommitParams {
+    pub session_id: String,
+    /// May be empty only with `amend` (keeps the existing message)
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub amend: bool,
+    #[serde(default)]
+    pub signoff: bool,
+    /// Author override as "Name <email>"
+    pub author: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQueryParams {
+    /// Maximum entries to return (most recent first)
+    pub limit: Option<usize>,
+    /// Only entries for this method
+    pub method: Option<String>,
+    /// Only entries for this session path
+    pub session_id: Option<String>,
+}
+
 // --- OpenCode request params ---
 
 #[derive(Debug, Deserialize)]
@@ -262,14 +698,103 @@ pub struct SessionInfoResult {
 #[derive(Debug, Serialize)]
 pub struct TerminalOpenResult {
     pub terminal_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recording_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Serialize)]
+pub struct TerminalInfo {
+    pub session_id: String,
+    pub terminal_id: String,
+    /// Whether any client is attached
+    pub attached: bool,
+    /// Number of attached clients
+    pub subscribers: usize,
+    /// The requesting client's role (None if not attached)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<TerminalRole>,
+    /// Seconds since the last client left (detached terminals only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detached_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TerminalListResult {
+    pub terminals: Vec<TerminalInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NamedTerminalProfile {
+    pub name: String,
+    #[serde(flatten)]
+    pub profile: TerminalProfile,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TerminalProfileListResult {
+    pub profiles: Vec<NamedTerminalProfile>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecordingInfo {
+    pub recording_id: String,
+    pub session_id: String,
+    pub terminal_id: String,
+    /// RFC 3339 UTC start time
+    pub started_at: Option<String>,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecordingListResult {
+    pub recordings: Vec<RecordingInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecordingGetResult {
+    pub recording_id: String,
+    /// asciinema v2 cast content (whole lines)
+    pub data: String,
+    pub offset: u64,
+    /// Offset of the next chunk (None at end of file)
+    pub next_offset: Option<u64>,
+    pub total_size: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TerminalAttachResult {
+    pub terminal_id: String,
+    pub role: TerminalRole,
+    /// Buffered output to replay before live output
+    pub scrollback: String,
+    #[serde(skip_serializing_if = "TerminalEncoding::is_utf8")]
+    pub encoding: TerminalEncoding,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct GitFileStatus {
     pub path: String,
     pub status: String,
     pub additions: i32,
     pub deletions: i32,
+    /// Source path of a rename or copy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orig_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub submodule: Option<GitSubmoduleStatus>,
+    /// Kind of merge conflict for "unmerged" files ("both_modified", "deleted_by_them", ...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GitSubmoduleStatus {
+    /// Checked-out commit differs from the recorded one
+    pub commit_changed: bool,
+    /// Has tracked changes
+    pub modified: bool,
+    /// Has untracked files
+    pub untracked: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -287,12 +812,43 @@ pub struct GitFileDiff {
     pub diff: String,
 }
 
+/// A changed file in `git_diff`; fetch its diff with `git_diff_file`
+#[derive(Debug, Clone, Serialize)]
+pub struct GitDiffFileInfo {
+    pub path: String,
+    /// Source path of a rename or copy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orig_path: Option<String>,
+    pub status: String,
+    /// Whether this is the staged (HEAD → index) diff rather than index → worktree
+    pub staged: bool,
+    pub binary: bool,
+    /// Size of the full diff in bytes
+    pub size: usize,
+    pub hunks: usize,
+    pub additions: i32,
+    pub deletions: i32,
+    /// Fingerprint passed back to `git_stage_hunks`/`git_unstage_hunks` (which only
+    /// accept diffs listed without options)
+    pub hash: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct GitDiffResult {
-    pub files: Vec<GitFileDiff>,
-    pub truncated: bool,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    pub truncated_files: Vec<String>,
+    pub files: Vec<GitDiffFileInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GitDiffFileResult {
+    pub path: String,
+    pub staged: bool,
+    pub hash: String,
+    /// File header plus the hunks of this page
+    pub diff: String,
+    pub total_hunks: usize,
+    pub hunk_offset: usize,
+    /// Offset of the next page, if any hunks remain
+    pub next_hunk_offset: Option<usize>,
 }
 
 #[derive(Debug, Serialize)]
@@ -311,6 +867,95 @@ pub struct GitLogResult {
     pub upstream: Option<String>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct GitBranch {
+    /// Short name ("main", "origin/main")
+    pub name: String,
+    pub remote: bool,
+    pub current: bool,
+    /// Last commit
+    pub sha: String,
+    pub summary: String,
+    pub timestamp: i64,
+    /// Upstream of a local branch ("origin/main")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<String>,
+    pub ahead: i32,
+    pub behind: i32,
+    /// The upstream branch no longer exists
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub upstream_gone: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GitBranchesResult {
+    /// Checked-out branch (None when HEAD is detached)
+    pub current: Option<String>,
+    pub branches: Vec<GitBranch>,
+}
+
+/// A commit's metadata and its changes against its first parent
+#[derive(Debug, Serialize)]
+pub struct GitShowResult {
+    pub sha: String,
+    pub parents: Vec<String>,
+    pub author: String,
+    pub author_email: String,
+    pub author_timestamp: i64,
+    pub committer: String,
+    pub committer_email: String,
+    pub committer_timestamp: i64,
+    /// Full commit message
+    pub message: String,
+    pub files: Vec<GitFileStatus>,
+    pub diffs: Vec<GitFileDiff>,
+    pub truncated: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub truncated_files: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GitDiscardResult {
+    /// Files that are (or would be) discarded
+    pub files: Vec<String>,
+    /// False for a preview
+    pub discarded: bool,
+    /// Pass back to confirm a preview; changes if the files change in between
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirm_token: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GitCommitResult {
+    pub sha: String,
+    pub summary: String,
+}
+
+/// One audit log record (data_dir/audit.log, one JSON object per line)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// RFC 3339 UTC timestamp
+    pub ts: String,
+    pub client_id: u64,
+    pub peer: String,
+    pub token_name: Option<String>,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace_id: Option<String>,
+    /// SHA-256 of the request params JSON
+    pub params_digest: String,
+    /// "ok" or the error code
+    pub outcome: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditQueryResult {
+    pub entries: Vec<AuditEntry>,
+}
+
 // --- OpenCode response types ---
 
 #[derive(Debug, Serialize)]
@@ -350,11 +995,134 @@ pub struct ClaudeSdkStatusResult {
 
 // --- Event params ---
 
+#[derive(Debug, Serialize)]
+pub struct SessionsChangedParams {
+    pub sessions: Vec<SessionInfo>,
+}
+
+/// A conflict marked in a working tree file, from "<<<<<<<" to ">>>>>>>"
+#[derive(Debug, Serialize)]
+pub struct GitConflictRegion {
+    /// 1-based lines of the opening and closing markers
+    pub start_line: usize,
+    pub end_line: usize,
+    pub ours_label: String,
+    pub theirs_label: String,
+    pub ours: String,
+    /// Only with `merge.conflictStyle` diff3 or zdiff3
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    pub theirs: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GitConflictFile {
+    pub path: String,
+    /// "both_modified", "deleted_by_them", ...
+    pub conflict: String,
+    /// Blob contents of each side; absent if the side has no version of the file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ours: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theirs: Option<String>,
+    /// Contents left out because the file is binary or larger than 1 MB
+    pub omitted: bool,
+    pub regions: Vec<GitConflictRegion>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GitConflictsResult {
+    /// "merge", "rebase", "cherry_pick" or "revert"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation: Option<&'static str>,
+    pub files: Vec<GitConflictFile>,
+}
+
+/// A progress line of a network git command ("Receiving objects:  45% (9/20)")
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GitProgress {
+    pub phase: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percent: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GitProgressParams {
+    pub session_id: String,
+    pub operation_id: String,
+    #[serde(flatten)]
+    pub progress: GitProgress,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GitOperationFinishedParams {
+    pub session_id: String,
+    pub operation_id: String,
+    /// "fetch", "pull" or "push"
+    pub operation: &'static str,
+    pub success: bool,
+    /// `git_auth_failed` when credentials were missing or rejected, otherwise `git_error`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GitStatusChangedParams {
+    pub session_id: String,
+    pub status: GitStatusResult,
+}
+
 #[derive(Debug, Serialize)]
 pub struct TerminalOutputParams {
     pub session_id: String,
     pub terminal_id: String,
     pub data: String,
+    #[serde(skip_serializing_if = "TerminalEncoding::is_utf8")]
+    pub encoding: TerminalEncoding,
+}
+
+/// Output was elided because the client fell behind; scrollback (via
+/// `terminal_attach`/`terminal_subscribe`) still holds the recent output
+#[derive(Debug, Serialize)]
+pub struct TerminalOutputDroppedParams {
+    pub session_id: String,
+    pub terminal_id: String,
+    pub dropped_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExecOutputParams {
+    pub exec_id: String,
+    pub stream: ExecStream,
+    pub data: String,
+}
+
+/// Final event of an exec, sent after all of its `exec_output` events
+#[derive(Debug, Serialize)]
+pub struct ExecExitedParams {
+    pub exec_id: String,
+    /// Exit code (None if killed by a signal)
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub timed_out: bool,
+    pub cancelled: bool,
+    /// Output exceeded `max_output_bytes` and the process was killed
+    pub output_truncated: bool,
+    pub duration_ms: u64,
 }
 
 #[derive(Debug, Serialize)]
@@ -399,7 +1167,8 @@ impl Event {
 #[cfg(test)]
 mod tests {
     use super::{
-        ErrorResponse, Event, GitDiffResult, Request, RpcError, SuccessResponse, AUTH_FAILED,
+        ErrorResponse, Event, GitDiffFileInfo, GitDiffResult, Request, RpcError, SuccessResponse,
+        AUTH_FAILED,
         EVENT_TERMINAL_OUTPUT,
     };
     use serde_json::json;
@@ -440,14 +1209,24 @@ mod tests {
     }
 
     #[test]
-    fn git_diff_result_omits_empty_truncated_files() {
+    fn git_diff_file_info_omits_missing_orig_path() {
         let result = GitDiffResult {
-            files: vec![],
-            truncated: false,
-            truncated_files: vec![],
+            files: vec![GitDiffFileInfo {
+                path: "a.rs".to_string(),
+                orig_path: None,
+                status: "modified".to_string(),
+                staged: false,
+                binary: false,
+                size: 10,
+                hunks: 1,
+                additions: 1,
+                deletions: 0,
+                hash: "00".to_string(),
+            }],
         };
         let value = serde_json::to_value(result).expect("diff result to serialize");
-        assert!(value.get("truncated_files").is_none());
+        assert!(value["files"][0].get("orig_path").is_none());
+        assert_eq!(value["files"][0]["hunks"], 1);
     }
 
     #[test]
@@ -0,0 +1,277 @@
+//! Terminal recordings in asciinema v2 format
+//!
+//! Each recorded terminal writes `data_dir/recordings/<recording_id>.cast`: a JSON header
+//! line followed by `[elapsed, "o", text]` output and `[elapsed, "r", "COLSxROWS"]`
+//! resize events. The header carries `session_id`/`terminal_id` for listing.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::auth::format_timestamp;
+use crate::protocol::RecordingInfo;
+
+/// Largest chunk returned by one `terminal_recording_get`
+pub const MAX_RECORDING_CHUNK: usize = 4 * 1024 * 1024;
+
+/// Suffixed ids tried when a recording id is already taken
+const MAX_ID_ATTEMPTS: usize = 16;
+
+pub fn recordings_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("recordings")
+}
+
+/// Recording ids are file stems: ASCII letters, digits, '-' and '_'
+pub fn is_valid_recording_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Writer for one asciinema v2 cast file. Events are written by a background thread
+/// so terminal output is never blocked on disk I/O.
+pub struct CastWriter {
+    tx: mpsc::Sender<String>,
+    start: Instant,
+}
+
+impl CastWriter {
+    /// Create a cast file and write its header. Returns the writer and recording id.
+    pub fn create(
+        dir: &Path,
+        session_id: &str,
+        terminal_id: &str,
+        cols: u16,
+        rows: u16,
+    ) -> Result<(Self, String), String> {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create recordings dir: {e}"))?;
+
+        let now = SystemTime::now();
+        let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let safe_terminal_id: String = terminal_id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        // Terminal ids repeat across sessions: qualify with a short session hash
+        let session_hash: String = Sha256::digest(session_id.as_bytes())[..4]
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        let base_id = format!("{}-{session_hash}-{safe_terminal_id}", since_epoch.as_millis());
+
+        // Owner-only: terminal output may contain secrets
+        let mut attempt = 0;
+        let (file, recording_id) = loop {
+            let recording_id = match attempt {
+                0 => base_id.clone(),
+                n => format!("{base_id}_{n}"),
+            };
+            let path = dir.join(format!("{recording_id}.cast"));
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&path)
+            {
+                Ok(file) => break (file, recording_id),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists && attempt < MAX_ID_ATTEMPTS => {
+                    attempt += 1;
+                }
+                Err(e) => return Err(format!("Failed to create {}: {e}", path.display())),
+            }
+        };
+
+        let header = json!({
+            "version": 2,
+            "width": cols,
+            "height": rows,
+            "timestamp": since_epoch.as_secs(),
+            "title": terminal_id,
+            "env": { "TERM": "xterm-256color" },
+            "session_id": session_id,
+            "terminal_id": terminal_id,
+        });
+
+        let mut file = BufWriter::new(file);
+        writeln!(file, "{header}")
+            .and_then(|_| file.flush())
+            .map_err(|e| format!("Failed to write recording: {e}"))?;
+
+        let (tx, rx) = mpsc::channel::<String>();
+        std::thread::spawn(move || {
+            // Flush once the queue is drained rather than per event
+            while let Ok(line) = rx.recv() {
+                let result = std::iter::once(line)
+                    .chain(rx.try_iter())
+                    .try_for_each(|line| writeln!(file, "{line}"))
+                    .and_then(|_| file.flush());
+                if let Err(e) = result {
+                    warn!("Failed to write recording: {e}");
+                    break;
+                }
+            }
+        });
+
+        let writer = Self {
+            tx,
+            start: Instant::now(),
+        };
+        Ok((writer, recording_id))
+    }
+
+    /// Record terminal output
+    pub fn output(&mut self, text: &str) -> Result<(), String> {
+        self.event("o", text)
+    }
+
+    /// Record a terminal resize
+    pub fn resize(&mut self, cols: u16, rows: u16) -> Result<(), String> {
+        self.event("r", &format!("{cols}x{rows}"))
+    }
+
+    /// Queue an event. Fails once the writer thread has stopped on a write error.
+    fn event(&mut self, kind: &str, data: &str) -> Result<(), String> {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let line = json!([(elapsed * 1_000_000.0).round() / 1_000_000.0, kind, data]);
+        self.tx
+            .send(line.to_string())
+            .map_err(|_| "Recording writer stopped".to_string())
+    }
+}
+
+#[derive(Deserialize)]
+struct CastHeader {
+    #[serde(default)]
+    timestamp: Option<u64>,
+    #[serde(default)]
+    session_id: Option<String>,
+    #[serde(default)]
+    terminal_id: Option<String>,
+}
+
+/// List recordings, newest first
+pub fn list_recordings(dir: &Path) -> Result<Vec<RecordingInfo>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read recordings dir: {e}")),
+    };
+
+    let mut recordings = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("cast") {
+            continue;
+        }
+        let Some(recording_id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let Some(header) = read_header(&path) else {
+            continue;
+        };
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        let started_at = header
+            .timestamp
+            .map(|t| format_timestamp(UNIX_EPOCH + std::time::Duration::from_secs(t)));
+
+        recordings.push(RecordingInfo {
+            recording_id: recording_id.to_string(),
+            session_id: header.session_id.unwrap_or_default(),
+            terminal_id: header.terminal_id.unwrap_or_default(),
+            started_at,
+            size,
+        });
+    }
+
+    recordings.sort_by(|a, b| b.started_at.cmp(&a.started_at).then(b.recording_id.cmp(&a.recording_id)));
+    Ok(recordings)
+}
+
+fn read_header(path: &Path) -> Option<CastHeader> {
+    let file = File::open(path).ok()?;
+    let mut line = String::new();
+    BufReader::new(file).read_line(&mut line).ok()?;
+    serde_json::from_str(&line).ok()
+}
+
+/// Session a recording belongs to (from its header)
+pub fn recording_session(dir: &Path, recording_id: &str) -> Option<String> {
+    read_header(&dir.join(format!("{recording_id}.cast")))?.session_id
+}
+
+/// Read up to `limit` bytes of a recording from `offset`, ending on a line boundary
+/// unless the end of the file is reached. Returns (data, total size).
+pub fn read_recording(
+    dir: &Path,
+    recording_id: &str,
+    offset: u64,
+    limit: usize,
+) -> Result<(String, u64), String> {
+    let path = dir.join(format!("{recording_id}.cast"));
+    let mut file =
+        File::open(&path).map_err(|e| format!("Failed to open recording {recording_id}: {e}"))?;
+    let total = file
+        .metadata()
+        .map_err(|e| format!("Failed to read recording {recording_id}: {e}"))?
+        .len();
+
+    file.seek(SeekFrom::Start(offset.min(total)))
+        .map_err(|e| format!("Failed to read recording {recording_id}: {e}"))?;
+    let mut buf = Vec::new();
+    file.take(limit as u64)
+        .read_to_end(&mut buf)
+        .map_err(|e| format!("Failed to read recording {recording_id}: {e}"))?;
+
+    // Keep whole lines so every chunk is valid UTF-8 and parseable on its own
+    if offset + (buf.len() as u64) < total {
+        match buf.iter().rposition(|&b| b == b'\n') {
+            Some(end) => buf.truncate(end + 1),
+            None => return Err(format!("Recording line exceeds {limit} bytes")),
+        }
+    }
+
+    let data = String::from_utf8(buf).map_err(|e| format!("Recording is not UTF-8: {e}"))?;
+    Ok((data, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recordings_of_the_same_terminal_id_do_not_collide() {
+        let dir = std::env::temp_dir().join(format!("maestro-recording-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        // Same terminal id in two sessions, and twice in one session
+        let (mut first, a) = CastWriter::create(&dir, "/repo/a", "main", 80, 24).unwrap();
+        let (_, b) = CastWriter::create(&dir, "/repo/b", "main", 80, 24).unwrap();
+        let (_, c) = CastWriter::create(&dir, "/repo/a", "main", 80, 24).unwrap();
+        assert!(a != b && b != c && a != c);
+        assert!([&a, &b, &c].iter().all(|id| is_valid_recording_id(id)));
+
+        first.output("hello").unwrap();
+        drop(first);
+        let deadline = Instant::now() + std::time::Duration::from_secs(5);
+        let data = loop {
+            let (data, _) = read_recording(&dir, &a, 0, MAX_RECORDING_CHUNK).unwrap();
+            if data.lines().count() == 2 || Instant::now() > deadline {
+                break data;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+        assert!(data.lines().nth(1).unwrap().ends_with(r#","o","hello"]"#));
+        assert_eq!(recording_session(&dir, &b).as_deref(), Some("/repo/b"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
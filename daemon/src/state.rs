@@ -1,14 +1,28 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex, RwLock};
-
+use std::time::Duration;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, Mutex, Notify, RwLock};
+use tracing::{debug, info, warn};
+
+use crate::access::{ApiToken, Principal};
+use crate::audit::AuditLog;
+use crate::auth::{self, AuthAuditLog, AuthLimiter, AuthOutcome};
 use crate::claude_sdk::ClaudeSdkServer;
+use crate::config::SessionsConfig;
+use crate::git::DiffCache;
 use crate::opencode::OpenCodeServer;
-use crate::protocol::SessionInfo;
+use crate::protocol::{
+    Event, SessionInfo, SessionsChangedParams, TerminalProfile, EVENT_SESSIONS_CHANGED,
+};
 use crate::terminal::TerminalHandle;
 
 use crate::protocol::ClaudeSdkServerStatus;
 
+/// Default grace period for detached terminals
+pub const DEFAULT_TERMINAL_GRACE: Duration = Duration::from_secs(300);
+
 /// Server status for restart resilience (spec §3)
 #[derive(Debug, Clone)]
 pub enum ServerStatus {
@@ -48,21 +62,57 @@ pub type ClientId = u64;
 
 /// Daemon-wide shared state
 pub struct DaemonState {
-    /// Token for authentication (None if auth disabled)
-    pub token: Option<String>,
+    /// API tokens accepted by `auth` (empty if auth disabled)
+    pub tokens: Vec<ApiToken>,
+
+    /// Data directory (holds sessions.json)
+    pub data_dir: PathBuf,
 
     /// Configured sessions (path → SessionInfo)
     pub sessions: RwLock<HashMap<String, SessionInfo>>,
 
+    /// Serializes sessions.json writes and reloads so `sessions` is not locked during I/O
+    pub sessions_save: Mutex<()>,
+
     /// Active terminals (sessionPath:terminalId → TerminalHandle)
     pub terminals: RwLock<HashMap<String, Arc<TerminalHandle>>>,
 
-    /// Terminal ownership (terminalKey → ClientId)
-    pub terminal_owners: RwLock<HashMap<String, ClientId>>,
+    /// How long a terminal survives after its owner disconnects
+    pub terminal_grace: Duration,
+
+    /// Record terminals unless `terminal_open` says otherwise
+    pub record_terminals: bool,
+
+    /// Named terminal profiles from config.json
+    pub terminal_profiles: HashMap<String, TerminalProfile>,
+
+    /// Per-session git diff snapshots
+    pub diff_cache: DiffCache,
+
+    /// Sessions with a running fetch, pull or push (session path → operation id)
+    pub git_operations: RwLock<HashMap<String, String>>,
+
+    /// Running exec processes ((ClientId, execId) → cancel signal)
+    pub execs: RwLock<HashMap<(ClientId, String), Arc<Notify>>>,
 
     /// Client event senders (ClientId → sender)
     pub clients: RwLock<HashMap<ClientId, ClientSender>>,
 
+    /// Authenticated identity per client (ClientId → Principal)
+    pub client_principals: RwLock<HashMap<ClientId, Principal>>,
+
+    /// Peer address per client (ClientId → peer)
+    pub client_peers: RwLock<HashMap<ClientId, String>>,
+
+    /// Failed auth attempt tracking per peer
+    auth_limiter: AuthLimiter,
+
+    /// Append-only auth audit log (data_dir/auth.log)
+    auth_log: AuthAuditLog,
+
+    /// RPC audit log of mutating requests (data_dir/audit.log)
+    pub audit_log: AuditLog,
+
     /// Next client ID counter
     next_client_id: Mutex<ClientId>,
 
@@ -76,22 +126,71 @@ pub struct DaemonState {
     pub claude_server_runtimes: RwLock<HashMap<String, ClaudeServerRuntime>>,
 }
 
-/// Channel for sending events to a client
-pub type ClientSender = mpsc::UnboundedSender<String>;
+/// Output events queued per client before producers drop output or wait
+pub const CLIENT_QUEUE: usize = 256;
+
+/// Channels for sending events to a client.
+///
+/// Output goes through a bounded lane: terminal output is dropped when the client
+/// falls behind (subscribers resync from the drop notice), exec output waits for
+/// room. All other events go through an unbounded lane and are never dropped.
+#[derive(Clone)]
+pub struct ClientSender {
+    output: mpsc::Sender<String>,
+    events: mpsc::UnboundedSender<String>,
+}
+
+impl ClientSender {
+    /// Queue an event that must not be lost
+    pub fn send(&self, msg: String) {
+        let _ = self.events.send(msg);
+    }
+
+    /// Queue output that may be dropped. Returns false if the queue is full.
+    pub fn offer(&self, msg: String) -> bool {
+        !matches!(self.output.try_send(msg), Err(TrySendError::Full(_)))
+    }
+
+    /// Wait for room in the output queue. Returns None if the client is gone.
+    pub async fn reserve(&self) -> Option<mpsc::Permit<'_, String>> {
+        self.output.reserve().await.ok()
+    }
+}
+
+/// Receiving side of a client's event channels
+pub struct ClientReceiver {
+    pub output: mpsc::Receiver<String>,
+    pub events: mpsc::UnboundedReceiver<String>,
+}
 
 impl DaemonState {
-    pub fn new(token: Option<String>, sessions: Vec<SessionInfo>) -> Self {
+    pub fn new(tokens: Vec<ApiToken>, sessions: Vec<SessionInfo>, data_dir: PathBuf) -> Self {
         let sessions_map: HashMap<String, SessionInfo> = sessions
             .into_iter()
             .map(|s| (s.path.clone(), s))
             .collect();
 
+        let auth_log = AuthAuditLog::new(&data_dir);
+        let audit_log = AuditLog::new(&data_dir);
+
         Self {
-            token,
+            tokens,
+            data_dir,
             sessions: RwLock::new(sessions_map),
+            sessions_save: Mutex::new(()),
             terminals: RwLock::new(HashMap::new()),
-            terminal_owners: RwLock::new(HashMap::new()),
+            terminal_grace: DEFAULT_TERMINAL_GRACE,
+            record_terminals: false,
+            terminal_profiles: HashMap::new(),
+            diff_cache: DiffCache::default(),
+            git_operations: RwLock::new(HashMap::new()),
+            execs: RwLock::new(HashMap::new()),
             clients: RwLock::new(HashMap::new()),
+            client_principals: RwLock::new(HashMap::new()),
+            client_peers: RwLock::new(HashMap::new()),
+            auth_limiter: AuthLimiter::default(),
+            auth_log,
+            audit_log,
             next_client_id: Mutex::new(1),
             opencode_servers: RwLock::new(HashMap::new()),
             claude_sdk_servers: RwLock::new(HashMap::new()),
@@ -100,37 +199,135 @@ impl DaemonState {
     }
 
     /// Register a new client, returning its ID and event receiver
-    pub async fn register_client(&self) -> (ClientId, mpsc::UnboundedReceiver<String>) {
+    pub async fn register_client(&self, peer: &str) -> (ClientId, ClientReceiver) {
         let mut id = self.next_client_id.lock().await;
         let client_id = *id;
         *id += 1;
 
-        let (tx, rx) = mpsc::unbounded_channel();
-        self.clients.write().await.insert(client_id, tx);
+        let (output_tx, output) = mpsc::channel(CLIENT_QUEUE);
+        let (events_tx, events) = mpsc::unbounded_channel();
+        let sender = ClientSender {
+            output: output_tx,
+            events: events_tx,
+        };
+        self.clients.write().await.insert(client_id, sender);
+        self.client_peers.write().await.insert(client_id, peer.to_string());
 
-        (client_id, rx)
+        (client_id, ClientReceiver { output, events })
     }
 
-    /// Unregister a client and clean up its terminals
-    pub async fn unregister_client(&self, client_id: ClientId) {
+    /// Unregister a client and detach it from its terminals
+    pub async fn unregister_client(self: &Arc<Self>, client_id: ClientId) {
         self.clients.write().await.remove(&client_id);
+        self.client_principals.write().await.remove(&client_id);
+        self.client_peers.write().await.remove(&client_id);
+
+        // Cancel this client's exec processes
+        for ((owner, _), cancel) in self.execs.read().await.iter() {
+            if *owner == client_id {
+                cancel.notify_one();
+            }
+        }
 
-        // Find all terminals owned by this client
-        let owned_terminals: Vec<String> = {
-            let owners = self.terminal_owners.read().await;
-            owners
+        // Find all terminals this client is attached to
+        let attached_terminals: Vec<(String, Arc<TerminalHandle>)> = {
+            let terminals = self.terminals.read().await;
+            terminals
                 .iter()
-                .filter(|(_, &owner)| owner == client_id)
-                .map(|(key, _)| key.clone())
+                .filter(|(_, handle)| handle.role(client_id).is_some())
+                .map(|(key, handle)| (key.clone(), handle.clone()))
                 .collect()
         };
 
-        // Close each owned terminal
-        for key in owned_terminals {
-            self.close_terminal(&key).await;
+        for (key, handle) in attached_terminals {
+            self.release_terminal(&key, &handle, client_id).await;
         }
     }
 
+    /// Detach a client from a terminal. When the last client leaves, the terminal
+    /// is closed after the grace period unless someone reattaches.
+    pub async fn release_terminal(
+        self: &Arc<Self>,
+        key: &str,
+        handle: &Arc<TerminalHandle>,
+        client_id: ClientId,
+    ) {
+        let Some(detached_at) = handle.unsubscribe(client_id) else {
+            return;
+        };
+
+        if self.terminal_grace.is_zero() {
+            self.close_terminal(key).await;
+            return;
+        }
+
+        debug!("Terminal {key} detached; closing in {:?} unless reattached", self.terminal_grace);
+
+        let state = self.clone();
+        let key = key.to_string();
+        let handle = handle.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(state.terminal_grace).await;
+            // Close only if nobody reattached (and it did not detach again later)
+            if handle.detached_at() == Some(detached_at) {
+                info!("Closing detached terminal {key} after grace period");
+                state.close_terminal(&key).await;
+            }
+        });
+    }
+
+    /// Whether clients must authenticate with a token
+    pub fn auth_required(&self) -> bool {
+        !self.tokens.is_empty()
+    }
+
+    /// Check a token presented by a peer, applying lockout and recording the attempt
+    pub fn authenticate(&self, peer: &str, token: &str) -> AuthOutcome {
+        let key = auth::peer_key(peer);
+
+        let outcome = if let Some(remaining) = self.auth_limiter.locked_for(&key) {
+            AuthOutcome::LockedOut(remaining)
+        } else if let Some(matched) = auth::match_token(&self.tokens, token) {
+            self.auth_limiter.record_success(&key);
+            AuthOutcome::Authenticated(Principal::from_token(matched))
+        } else {
+            if let Some(lockout) = self.auth_limiter.record_failure(&key) {
+                warn!("Auth failures from {key}: locked out for {}s", lockout.as_secs());
+            }
+            AuthOutcome::Rejected
+        };
+
+        self.auth_log.record(peer, &outcome);
+        outcome
+    }
+
+    /// Get the peer address of a client
+    pub async fn client_peer(&self, client_id: ClientId) -> Option<String> {
+        self.client_peers.read().await.get(&client_id).cloned()
+    }
+
+    /// Record the authenticated identity of a client
+    pub async fn set_client_principal(&self, client_id: ClientId, principal: Principal) {
+        self.client_principals.write().await.insert(client_id, principal);
+    }
+
+    /// Get the authenticated identity of a client
+    pub async fn get_client_principal(&self, client_id: ClientId) -> Option<Principal> {
+        self.client_principals.read().await.get(&client_id).cloned()
+    }
+
+    /// Workspace path of a connected OpenCode or Claude SDK workspace
+    pub async fn workspace_path(&self, workspace_id: &str) -> Option<String> {
+        if let Some(server) = self.opencode_servers.read().await.get(workspace_id) {
+            return Some(server.workspace_path.clone());
+        }
+        self.claude_sdk_servers
+            .read()
+            .await
+            .get(workspace_id)
+            .map(|s| s.workspace_path.clone())
+    }
+
     /// Get session info by path
     pub async fn get_session(&self, path: &str) -> Option<SessionInfo> {
         self.sessions.read().await.get(path).cloned()
@@ -141,20 +338,135 @@ impl DaemonState {
         self.sessions.read().await.values().cloned().collect()
     }
 
+    /// Add a session and persist sessions.json.
+    /// Returns false if a session with the same path already exists.
+    pub async fn add_session(&self, session: SessionInfo) -> Result<bool, String> {
+        let added = self
+            .update_sessions(|sessions| {
+                if sessions.contains_key(&session.path) {
+                    return None;
+                }
+                sessions.insert(session.path.clone(), session);
+                Some(())
+            })
+            .await?;
+        Ok(added.is_some())
+    }
+
+    /// Remove a session and persist sessions.json. Returns the removed session.
+    pub async fn remove_session(&self, path: &str) -> Result<Option<SessionInfo>, String> {
+        self.update_sessions(|sessions| sessions.remove(path)).await
+    }
+
+    /// Rename a session and persist sessions.json. Returns the updated session.
+    pub async fn rename_session(
+        &self,
+        path: &str,
+        name: String,
+    ) -> Result<Option<SessionInfo>, String> {
+        self.update_sessions(|sessions| {
+            sessions.get_mut(path).map(|s| {
+                s.name = name;
+                s.clone()
+            })
+        })
+        .await
+    }
+
+    /// Apply `update` to a copy of the sessions, write it to sessions.json and then
+    /// install it. Nothing changes if `update` returns None or the write fails.
+    async fn update_sessions<T>(
+        &self,
+        update: impl FnOnce(&mut HashMap<String, SessionInfo>) -> Option<T>,
+    ) -> Result<Option<T>, String> {
+        let _save = self.sessions_save.lock().await;
+        let mut updated = self.sessions.read().await.clone();
+        let Some(result) = update(&mut updated) else {
+            return Ok(None);
+        };
+
+        let infos: Vec<SessionInfo> = updated.values().cloned().collect();
+        let config = SessionsConfig::from_session_infos(&infos);
+        let data_dir = self.data_dir.clone();
+        tokio::task::spawn_blocking(move || config.save(&data_dir))
+            .await
+            .map_err(|e| format!("Failed to write sessions.json: {e}"))??;
+
+        *self.sessions.write().await = updated;
+        Ok(Some(result))
+    }
+
+    /// Close terminals and agent servers that belong to a session
+    pub async fn teardown_session(&self, path: &str) {
+        let terminal_keys: Vec<String> = self
+            .terminals
+            .read()
+            .await
+            .iter()
+            .filter(|(_, handle)| handle.session_id() == path)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in terminal_keys {
+            self.close_terminal(&key).await;
+        }
+
+        let in_session = |workspace_path: &str| Path::new(workspace_path) == Path::new(path);
+
+        let opencode_ids: Vec<String> = self
+            .opencode_servers
+            .read()
+            .await
+            .iter()
+            .filter(|(_, server)| in_session(&server.workspace_path))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in opencode_ids {
+            self.remove_opencode_server(&id).await;
+        }
+
+        let claude_ids: Vec<String> = self
+            .claude_sdk_servers
+            .read()
+            .await
+            .iter()
+            .filter(|(_, server)| in_session(&server.workspace_path))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in claude_ids {
+            self.remove_claude_sdk_server(&id).await;
+            self.remove_claude_server_runtime(&id).await;
+        }
+    }
+
+    /// Broadcast the current session list to all clients, filtered per client allowlist
+    pub async fn broadcast_sessions_changed(&self) {
+        let mut sessions = self.list_sessions().await;
+        sessions.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let clients = self.clients.read().await;
+        let principals = self.client_principals.read().await;
+        for (client_id, principal) in principals.iter() {
+            let Some(tx) = clients.get(client_id) else {
+                continue;
+            };
+            let visible: Vec<SessionInfo> = sessions
+                .iter()
+                .filter(|s| principal.allows_session(&s.path))
+                .cloned()
+                .collect();
+            let event = Event::new(EVENT_SESSIONS_CHANGED, SessionsChangedParams { sessions: visible });
+            tx.send(serde_json::to_string(&event).unwrap());
+        }
+    }
+
     /// Terminal key format
     pub fn terminal_key(session_id: &str, terminal_id: &str) -> String {
         format!("{session_id}:{terminal_id}")
     }
 
-    /// Store a terminal handle with ownership
-    pub async fn store_terminal(
-        &self,
-        key: String,
-        handle: Arc<TerminalHandle>,
-        owner: ClientId,
-    ) {
-        self.terminals.write().await.insert(key.clone(), handle);
-        self.terminal_owners.write().await.insert(key, owner);
+    /// Store a terminal handle
+    pub async fn store_terminal(&self, key: String, handle: Arc<TerminalHandle>) {
+        self.terminals.write().await.insert(key, handle);
     }
 
     /// Get a terminal handle
@@ -172,28 +484,47 @@ impl DaemonState {
         if let Some(handle) = self.terminals.write().await.remove(key) {
             handle.kill().await;
         }
-        self.terminal_owners.write().await.remove(key);
     }
 
-    /// Send an event to a specific client
-    pub async fn send_to_client(&self, client_id: ClientId, msg: String) {
-        if let Some(tx) = self.clients.read().await.get(&client_id) {
-            let _ = tx.send(msg);
+    /// Send an event to several clients
+    pub async fn send_to_clients(&self, client_ids: &[ClientId], msg: String) {
+        let clients = self.clients.read().await;
+        for client_id in client_ids {
+            if let Some(tx) = clients.get(client_id) {
+                tx.send(msg.clone());
+            }
         }
     }
 
-    /// Get terminal owner
+    /// Broadcast a message to all authenticated clients. Returns number of clients.
     #[allow(dead_code)]
-    pub async fn get_terminal_owner(&self, key: &str) -> Option<ClientId> {
-        self.terminal_owners.read().await.get(key).copied()
+    pub async fn broadcast_to_all_clients(&self, msg: String) -> usize {
+        let clients = self.clients.read().await;
+        let principals = self.client_principals.read().await;
+        let mut count = 0;
+        for client_id in principals.keys() {
+            if let Some(tx) = clients.get(client_id) {
+                tx.send(msg.clone());
+                count += 1;
+            }
+        }
+        count
     }
 
-    /// Broadcast a message to all connected clients. Returns number of clients.
-    pub async fn broadcast_to_all_clients(&self, msg: String) -> usize {
+    /// Broadcast a message to authenticated clients allowed to see a session.
+    /// Returns number of clients.
+    pub async fn broadcast_to_session_clients(&self, session_path: &str, msg: String) -> usize {
         let clients = self.clients.read().await;
-        let count = clients.len();
-        for tx in clients.values() {
-            let _ = tx.send(msg.clone());
+        let principals = self.client_principals.read().await;
+        let mut count = 0;
+        for (client_id, principal) in principals.iter() {
+            if !principal.allows_session(session_path) {
+                continue;
+            }
+            if let Some(tx) = clients.get(client_id) {
+                tx.send(msg.clone());
+                count += 1;
+            }
         }
         count
     }
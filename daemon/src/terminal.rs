@@ -1,22 +1,57 @@
 use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use std::collections::{HashMap, VecDeque};
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::protocol::{TerminalEncoding, TerminalProfile, TerminalRole};
+use crate::recording::CastWriter;
+use crate::state::ClientId;
+
+/// Scrollback kept per terminal for replay on reattach
+pub const SCROLLBACK_BYTES: usize = 256 * 1024;
+
+/// An attached client
+#[derive(Debug, Clone, Copy)]
+pub struct Subscriber {
+    pub role: TerminalRole,
+    /// How `terminal_output` data is encoded for this client
+    pub encoding: TerminalEncoding,
+}
+
+/// Output routing and scrollback, updated together so subscribing neither drops nor repeats output
+struct OutputState {
+    scrollback: VecDeque<u8>,
+    /// Attached clients
+    subscribers: HashMap<ClientId, Subscriber>,
+    /// When the last subscriber left (None while any client is attached)
+    detached_at: Option<Instant>,
+    /// Output bytes dropped per slow subscriber, not yet reported
+    dropped: HashMap<ClientId, u64>,
+}
 
 /// Handle to an active terminal PTY
 pub struct TerminalHandle {
-    #[allow(dead_code)]
+    session_id: String,
     terminal_id: String,
     master: Mutex<Box<dyn portable_pty::MasterPty + Send>>,
     writer: Mutex<Box<dyn Write + Send>>,
     child: Mutex<Box<dyn portable_pty::Child + Send>>,
+    output: std::sync::Mutex<OutputState>,
+    /// asciinema recording, if enabled
+    recording: std::sync::Mutex<Option<CastWriter>>,
 }
 
 impl TerminalHandle {
-    /// Open a new PTY in the given working directory
+    /// Open a new PTY in the given working directory (with no subscribers yet).
+    /// Runs the profile's argv, or `$SHELL` as an interactive or login shell.
     pub fn open(
+        session_id: String,
         terminal_id: String,
         cwd: &Path,
+        profile: &TerminalProfile,
         cols: u16,
         rows: u16,
     ) -> Result<(Self, Box<dyn Read + Send>), String> {
@@ -32,17 +67,30 @@ impl TerminalHandle {
             .openpty(size)
             .map_err(|e| format!("Failed to open pty: {e}"))?;
 
-        let shell = shell_path();
-        let mut cmd = CommandBuilder::new(&shell);
+        let mut cmd = match profile.argv.as_deref() {
+            Some([program, args @ ..]) => {
+                let mut cmd = CommandBuilder::new(program);
+                cmd.args(args);
+                cmd
+            }
+            Some([]) => return Err("Profile argv must not be empty".to_string()),
+            None => {
+                let mut cmd = CommandBuilder::new(shell_path());
+                cmd.arg(if profile.login { "-l" } else { "-i" });
+                cmd
+            }
+        };
         cmd.cwd(cwd);
-        cmd.arg("-i");
         cmd.env("TERM", "xterm-256color");
         cmd.env("COLORTERM", "truecolor");
+        for (key, value) in &profile.env {
+            cmd.env(key, value);
+        }
 
         let child = pair
             .slave
             .spawn_command(cmd)
-            .map_err(|e| format!("Failed to spawn shell: {e}"))?;
+            .map_err(|e| format!("Failed to spawn terminal command: {e}"))?;
 
         let reader = pair
             .master
@@ -55,15 +103,111 @@ impl TerminalHandle {
             .map_err(|e| format!("Failed to open pty writer: {e}"))?;
 
         let handle = Self {
+            session_id,
             terminal_id,
             master: Mutex::new(pair.master),
             writer: Mutex::new(writer),
             child: Mutex::new(child),
+            output: std::sync::Mutex::new(OutputState {
+                scrollback: VecDeque::new(),
+                subscribers: HashMap::new(),
+                detached_at: None,
+                dropped: HashMap::new(),
+            }),
+            recording: std::sync::Mutex::new(None),
         };
 
         Ok((handle, reader))
     }
 
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    pub fn terminal_id(&self) -> &str {
+        &self.terminal_id
+    }
+
+    /// Start recording to a new cast file. Returns the recording id.
+    pub fn start_recording(&self, dir: &Path, cols: u16, rows: u16) -> Result<String, String> {
+        let (writer, recording_id) =
+            CastWriter::create(dir, &self.session_id, &self.terminal_id, cols, rows)?;
+        *self.recording.lock().unwrap() = Some(writer);
+        Ok(recording_id)
+    }
+
+    /// Append decoded output to the recording, if any
+    pub fn record_output(&self, text: &str) {
+        let mut recording = self.recording.lock().unwrap();
+        if let Some(writer) = recording.as_mut() {
+            if let Err(e) = writer.output(text) {
+                warn!("Stopping recording of terminal {}: {e}", self.terminal_id);
+                *recording = None;
+            }
+        }
+    }
+
+    /// Append output to the scrollback, returning the clients it should be sent to
+    pub fn push_output(&self, data: &[u8]) -> Vec<(ClientId, Subscriber)> {
+        let mut output = self.output.lock().unwrap();
+        output.scrollback.extend(data);
+        let excess = output.scrollback.len().saturating_sub(SCROLLBACK_BYTES);
+        output.scrollback.drain(..excess);
+        output.subscribers.iter().map(|(&id, &sub)| (id, sub)).collect()
+    }
+
+    /// Add (or update) a subscriber, returning the scrollback to replay
+    pub fn subscribe(&self, client_id: ClientId, subscriber: Subscriber) -> Vec<u8> {
+        let mut output = self.output.lock().unwrap();
+        output.subscribers.insert(client_id, subscriber);
+        output.detached_at = None;
+        output.dropped.remove(&client_id);
+        output.scrollback.iter().copied().collect()
+    }
+
+    /// Remove a subscriber. Returns the detach time if it was the last one.
+    pub fn unsubscribe(&self, client_id: ClientId) -> Option<Instant> {
+        let mut output = self.output.lock().unwrap();
+        output.dropped.remove(&client_id);
+        output.subscribers.remove(&client_id)?;
+        if !output.subscribers.is_empty() {
+            return None;
+        }
+        let now = Instant::now();
+        output.detached_at = Some(now);
+        Some(now)
+    }
+
+    /// Role of a client (None if not subscribed)
+    pub fn role(&self, client_id: ClientId) -> Option<TerminalRole> {
+        self.output.lock().unwrap().subscribers.get(&client_id).map(|s| s.role)
+    }
+
+    /// Output bytes dropped for a client since the last report
+    pub fn dropped_bytes(&self, client_id: ClientId) -> u64 {
+        self.output.lock().unwrap().dropped.get(&client_id).copied().unwrap_or(0)
+    }
+
+    /// Record output dropped because a client's queue was full
+    pub fn add_dropped(&self, client_id: ClientId, bytes: usize) {
+        *self.output.lock().unwrap().dropped.entry(client_id).or_insert(0) += bytes as u64;
+    }
+
+    /// Reset the dropped count once it has been reported
+    pub fn clear_dropped(&self, client_id: ClientId) {
+        self.output.lock().unwrap().dropped.remove(&client_id);
+    }
+
+    /// Clients currently attached
+    pub fn subscribers(&self) -> Vec<ClientId> {
+        self.output.lock().unwrap().subscribers.keys().copied().collect()
+    }
+
+    /// When the last subscriber left (None while attached)
+    pub fn detached_at(&self) -> Option<Instant> {
+        self.output.lock().unwrap().detached_at
+    }
+
     /// Write data to the terminal
     pub async fn write(&self, data: &[u8]) -> Result<(), String> {
         let mut writer = self.writer.lock().await;
@@ -88,6 +232,14 @@ impl TerminalHandle {
         master
             .resize(size)
             .map_err(|e| format!("Failed to resize pty: {e}"))?;
+
+        let mut recording = self.recording.lock().unwrap();
+        if let Some(writer) = recording.as_mut() {
+            if let Err(e) = writer.resize(size.cols, size.rows) {
+                warn!("Stopping recording of terminal {}: {e}", self.terminal_id);
+                *recording = None;
+            }
+        }
         Ok(())
     }
 
@@ -108,6 +260,127 @@ impl TerminalHandle {
     }
 }
 
+/// Incremental UTF-8 decoder that carries partial sequences across reads
+#[derive(Default)]
+pub struct Utf8Decoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    /// Decode the next chunk. A trailing incomplete sequence is held back until the
+    /// next call; invalid bytes become U+FFFD.
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        let mut input = std::mem::take(&mut self.pending);
+        input.extend_from_slice(bytes);
+
+        let mut out = String::with_capacity(input.len());
+        let mut rest = input.as_slice();
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    out.push_str(valid);
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    out.push_str(std::str::from_utf8(valid).unwrap());
+                    match e.error_len() {
+                        Some(len) => {
+                            out.push(char::REPLACEMENT_CHARACTER);
+                            rest = &after[len..];
+                        }
+                        None => {
+                            self.pending = after.to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Decode replayed scrollback, dropping a sequence cut at either end of the buffer
+/// (the tail is delivered complete by the live stream)
+pub fn decode_scrollback(bytes: &[u8]) -> String {
+    let start = bytes
+        .iter()
+        .take(3)
+        .take_while(|&&b| b & 0xC0 == 0x80)
+        .count();
+    Utf8Decoder::default().decode(&bytes[start..])
+}
+
+/// Resolve a profile cwd against the session path. The result must be an existing
+/// directory inside the session (symlinks are followed before checking).
+pub fn resolve_cwd(session_path: &Path, cwd: Option<&str>) -> Result<PathBuf, String> {
+    let Some(cwd) = cwd else {
+        return Ok(session_path.to_path_buf());
+    };
+    let root = session_path
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve session path: {e}"))?;
+    let resolved = root
+        .join(cwd)
+        .canonicalize()
+        .map_err(|e| format!("Invalid cwd {cwd}: {e}"))?;
+    if !resolved.starts_with(&root) {
+        return Err(format!("cwd must be inside the session: {cwd}"));
+    }
+    if !resolved.is_dir() {
+        return Err(format!("cwd is not a directory: {cwd}"));
+    }
+    Ok(resolved)
+}
+
 fn shell_path() -> String {
     std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_scrollback, resolve_cwd, Utf8Decoder};
+
+    #[test]
+    fn decoder_carries_split_sequences() {
+        let bytes = "añ€😀".as_bytes();
+        let mut decoder = Utf8Decoder::default();
+        let mut out = String::new();
+        for chunk in bytes.chunks(1) {
+            out.push_str(&decoder.decode(chunk));
+        }
+        assert_eq!(out, "añ€😀");
+
+        let mut decoder = Utf8Decoder::default();
+        assert_eq!(decoder.decode(&[b'x', 0xFF, b'y', 0xE2, 0x82]), "x\u{FFFD}y");
+        assert_eq!(decoder.decode(&[0xAC]), "€");
+    }
+
+    #[test]
+    fn scrollback_drops_cut_sequences() {
+        let euro = "€".as_bytes();
+        let mut bytes = euro[1..].to_vec();
+        bytes.extend_from_slice(b"ok");
+        bytes.extend_from_slice(&euro[..2]);
+        assert_eq!(decode_scrollback(&bytes), "ok");
+    }
+
+    #[test]
+    fn cwd_must_stay_inside_session() {
+        let root = std::env::temp_dir().join(format!("maestro-cwd-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("web")).unwrap();
+        std::fs::write(root.join("README"), "").unwrap();
+
+        assert_eq!(resolve_cwd(&root, None).unwrap(), root);
+        assert!(resolve_cwd(&root, Some("web")).unwrap().ends_with("web"));
+        assert!(resolve_cwd(&root, Some("web/..")).is_ok());
+        assert!(resolve_cwd(&root, Some("..")).is_err());
+        assert!(resolve_cwd(&root, Some("/tmp")).is_err());
+        assert!(resolve_cwd(&root, Some("README")).is_err());
+        assert!(resolve_cwd(&root, Some("missing")).is_err());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,124 @@
+//! TLS for the daemon TCP listener
+//!
+//! Loads a certificate/key pair from disk, or generates a self-signed pair in the data
+//! directory on first start. Clients pin the certificate by its SHA-256 fingerprint.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+use tracing::info;
+
+/// Max time for a client to complete the TLS handshake
+pub const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+const GENERATED_CERT_FILE: &str = "tls/cert.pem";
+const GENERATED_KEY_FILE: &str = "tls/key.pem";
+
+/// Build a TLS acceptor. Returns the acceptor and the leaf certificate fingerprint.
+///
+/// With no explicit cert/key, a self-signed pair is generated under `data_dir/tls/`
+/// and reused on subsequent starts so pinned fingerprints stay valid.
+pub fn load_acceptor(
+    cert_path: Option<&Path>,
+    key_path: Option<&Path>,
+    data_dir: &Path,
+) -> Result<(TlsAcceptor, String), String> {
+    let (cert_path, key_path) = match (cert_path, key_path) {
+        (Some(cert), Some(key)) => (cert.to_path_buf(), key.to_path_buf()),
+        (None, None) => {
+            let cert = data_dir.join(GENERATED_CERT_FILE);
+            let key = data_dir.join(GENERATED_KEY_FILE);
+            if !cert.exists() || !key.exists() {
+                generate_self_signed(&cert, &key)?;
+            }
+            (cert, key)
+        }
+        _ => return Err("--tls-cert and --tls-key must be given together".to_string()),
+    };
+
+    let certs: Vec<CertificateDer<'static>> = CertificateDer::pem_file_iter(&cert_path)
+        .map_err(|e| format!("Failed to read {}: {e}", cert_path.display()))?
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to parse {}: {e}", cert_path.display()))?;
+
+    let leaf = certs
+        .first()
+        .ok_or_else(|| format!("No certificate found in {}", cert_path.display()))?;
+    let fingerprint = fingerprint(leaf);
+
+    let key = PrivateKeyDer::from_pem_file(&key_path)
+        .map_err(|e| format!("Failed to read {}: {e}", key_path.display()))?;
+
+    let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("Failed to configure TLS: {e}"))?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Invalid TLS certificate/key: {e}"))?;
+
+    Ok((TlsAcceptor::from(Arc::new(config)), fingerprint))
+}
+
+/// SHA-256 fingerprint of a DER certificate, as colon-separated uppercase hex
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn generate_self_signed(cert_path: &Path, key_path: &Path) -> Result<(), String> {
+    let names = vec!["maestro-daemon".to_string(), "localhost".to_string()];
+    let certified = rcgen::generate_simple_self_signed(names)
+        .map_err(|e| format!("Failed to generate self-signed certificate: {e}"))?;
+
+    if let Some(parent) = cert_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {e}", parent.display()))?;
+    }
+
+    write_private(key_path, certified.key_pair.serialize_pem().as_bytes())?;
+    std::fs::write(cert_path, certified.cert.pem())
+        .map_err(|e| format!("Failed to write {}: {e}", cert_path.display()))?;
+
+    info!("Generated self-signed TLS certificate: {}", cert_path.display());
+    Ok(())
+}
+
+/// Write a file readable only by the daemon user
+fn write_private(path: &Path, contents: &[u8]) -> Result<(), String> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    file.write_all(contents)
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fingerprint;
+
+    #[test]
+    fn fingerprint_is_colon_separated_sha256() {
+        let fp = fingerprint(b"maestro");
+        assert_eq!(fp.len(), 32 * 3 - 1);
+        assert!(fp
+            .split(':')
+            .all(|byte| byte.len() == 2 && byte.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_lowercase())));
+    }
+}
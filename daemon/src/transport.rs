@@ -0,0 +1,146 @@
+//! Client transports
+//!
+//! Every transport carries the same JSON frames (`Request`, responses, `Event`): one frame
+//! per line on raw TCP/TLS streams, one frame per text message on WebSockets.
+
+use futures::{SinkExt, StreamExt};
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, ReadHalf, WriteHalf,
+};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+/// A bidirectional JSON frame transport
+pub trait Transport: Send {
+    /// Receive the next frame. Returns `Ok(None)` when the peer closed the connection.
+    ///
+    /// Must be cancellation safe: it is polled inside `tokio::select!`.
+    fn recv(&mut self) -> impl std::future::Future<Output = Result<Option<String>, String>> + Send;
+
+    /// Send a single frame
+    fn send(&mut self, frame: &str) -> impl std::future::Future<Output = Result<(), String>> + Send;
+}
+
+/// Newline-delimited JSON over a byte stream (plain TCP or TLS)
+pub struct LineTransport<S> {
+    reader: BufReader<ReadHalf<S>>,
+    writer: WriteHalf<S>,
+    buf: Vec<u8>,
+}
+
+impl<S: AsyncRead + AsyncWrite> LineTransport<S> {
+    pub fn new(stream: S) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        Self {
+            reader: BufReader::new(reader),
+            writer,
+            buf: Vec::new(),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Send> Transport for LineTransport<S> {
+    async fn recv(&mut self) -> Result<Option<String>, String> {
+        loop {
+            // `read_until` keeps partial lines in `self.buf` if this future is cancelled;
+            // UTF-8 is only decoded once the whole line is in
+            match self.reader.read_until(b'\n', &mut self.buf).await {
+                Ok(0) => return Ok(None),
+                Ok(_) => {
+                    let frame = std::str::from_utf8(&self.buf)
+                        .map(|line| line.trim().to_string())
+                        .map_err(|_| "Read error: frame is not valid UTF-8".to_string());
+                    self.buf.clear();
+                    let frame = frame?;
+                    if !frame.is_empty() {
+                        return Ok(Some(frame));
+                    }
+                }
+                Err(e) => return Err(format!("Read error: {e}")),
+            }
+        }
+    }
+
+    async fn send(&mut self, frame: &str) -> Result<(), String> {
+        self.writer
+            .write_all(frame.as_bytes())
+            .await
+            .map_err(|e| format!("Write error: {e}"))?;
+        self.writer
+            .write_all(b"\n")
+            .await
+            .map_err(|e| format!("Write error: {e}"))
+    }
+}
+
+/// One JSON frame per WebSocket text message
+pub struct WsTransport<S> {
+    ws: WebSocketStream<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> WsTransport<S> {
+    /// Perform the server side of the WebSocket handshake
+    pub async fn accept(stream: S) -> Result<Self, String> {
+        let ws = tokio_tungstenite::accept_async(stream)
+            .await
+            .map_err(|e| format!("WebSocket handshake failed: {e}"))?;
+        Ok(Self { ws })
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> Transport for WsTransport<S> {
+    async fn recv(&mut self) -> Result<Option<String>, String> {
+        loop {
+            let message = match self.ws.next().await {
+                None => return Ok(None),
+                Some(Ok(message)) => message,
+                Some(Err(e)) => return Err(format!("WebSocket error: {e}")),
+            };
+
+            let frame = match message {
+                Message::Text(text) => text.to_string(),
+                Message::Binary(data) => String::from_utf8(data.to_vec())
+                    .map_err(|_| "WebSocket binary frame is not UTF-8".to_string())?,
+                Message::Close(_) => return Ok(None),
+                // Pings are answered by tungstenite itself
+                Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => continue,
+            };
+
+            let frame = frame.trim();
+            if !frame.is_empty() {
+                return Ok(Some(frame.to_string()));
+            }
+        }
+    }
+
+    async fn send(&mut self, frame: &str) -> Result<(), String> {
+        self.ws
+            .send(Message::text(frame))
+            .await
+            .map_err(|e| format!("WebSocket write error: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LineTransport, Transport};
+    use tokio::io::AsyncWriteExt;
+    use tokio::time::{timeout, Duration};
+
+    #[tokio::test]
+    async fn cancelled_recv_keeps_partial_line() {
+        let (client, server) = tokio::io::duplex(64);
+        let mut transport = LineTransport::new(server);
+        let (_, mut writer) = tokio::io::split(client);
+
+        // Stop in the middle of a multi-byte character
+        let frame = "{\"data\":\"é\"}\n".as_bytes();
+        let split = frame.iter().position(|&b| b >= 0x80).unwrap() + 1;
+        writer.write_all(&frame[..split]).await.unwrap();
+        assert!(timeout(Duration::from_millis(50), transport.recv()).await.is_err());
+
+        writer.write_all(&frame[split..]).await.unwrap();
+        let received = transport.recv().await.unwrap();
+        assert_eq!(received.as_deref(), Some("{\"data\":\"é\"}"));
+    }
+}
@@ -0,0 +1,192 @@
+//! sessions.json hot reload
+//!
+//! Watches the data directory and re-applies sessions.json when it changes on disk,
+//! tearing down resources of removed sessions and notifying connected clients.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::config::SessionsConfig;
+use crate::protocol::SessionInfo;
+use crate::state::DaemonState;
+
+/// Quiet period to coalesce bursts of filesystem events (editors, temp + rename)
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+const SESSIONS_FILE: &str = "sessions.json";
+
+/// Difference between two session maps
+#[derive(Debug, Default, PartialEq)]
+pub struct SessionsDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub renamed: Vec<String>,
+}
+
+impl SessionsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.renamed.is_empty()
+    }
+}
+
+/// Compare the current sessions with a freshly loaded set
+pub fn diff_sessions(
+    current: &HashMap<String, SessionInfo>,
+    loaded: &HashMap<String, SessionInfo>,
+) -> SessionsDiff {
+    let mut diff = SessionsDiff::default();
+
+    for (path, session) in loaded {
+        match current.get(path) {
+            None => diff.added.push(path.clone()),
+            Some(existing) if existing.name != session.name => diff.renamed.push(path.clone()),
+            Some(_) => {}
+        }
+    }
+    for path in current.keys() {
+        if !loaded.contains_key(path) {
+            diff.removed.push(path.clone());
+        }
+    }
+
+    diff.added.sort();
+    diff.removed.sort();
+    diff.renamed.sort();
+    diff
+}
+
+/// Start watching sessions.json. The returned watcher must be kept alive.
+pub fn spawn_sessions_watcher(state: Arc<DaemonState>) -> Result<RecommendedWatcher, String> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        match res {
+            Ok(event) => {
+                let touches_sessions = event
+                    .paths
+                    .iter()
+                    .any(|p| p.file_name().is_some_and(|n| n == SESSIONS_FILE));
+                if touches_sessions {
+                    let _ = tx.send(());
+                }
+            }
+            Err(e) => warn!("sessions.json watch error: {e}"),
+        }
+    })
+    .map_err(|e| format!("Failed to create watcher: {e}"))?;
+
+    // Watch the directory rather than the file so atomic replaces are seen
+    watcher
+        .watch(&state.data_dir, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("Failed to watch {}: {e}", state.data_dir.display()))?;
+
+    let sessions_path = state.data_dir.join(SESSIONS_FILE);
+    tokio::spawn(async move {
+        while rx.recv().await.is_some() {
+            // Debounce: wait until events stop arriving
+            while let Ok(Some(())) = tokio::time::timeout(DEBOUNCE, rx.recv()).await {}
+
+            reload_sessions(&state).await;
+        }
+    });
+
+    info!("Watching {} for changes", sessions_path.display());
+    Ok(watcher)
+}
+
+/// Re-read sessions.json and apply any changes to daemon state
+pub async fn reload_sessions(state: &DaemonState) {
+    let save = state.sessions_save.lock().await;
+    let config = match SessionsConfig::load(&state.data_dir) {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("Ignoring sessions.json change: {e}");
+            return;
+        }
+    };
+
+    let loaded: HashMap<String, SessionInfo> = config
+        .to_session_infos()
+        .into_iter()
+        .map(|s| (s.path.clone(), s))
+        .collect();
+
+    let diff = {
+        let mut sessions = state.sessions.write().await;
+        let diff = diff_sessions(&sessions, &loaded);
+        if !diff.is_empty() {
+            *sessions = loaded;
+        }
+        diff
+    };
+    drop(save);
+
+    if diff.is_empty() {
+        debug!("sessions.json changed on disk but sessions are unchanged");
+        return;
+    }
+
+    info!(
+        "Reloaded sessions.json: {} added, {} removed, {} renamed",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.renamed.len()
+    );
+
+    for path in &diff.added {
+        if !Path::new(path).exists() {
+            warn!("Session path does not exist: {path}");
+        }
+    }
+
+    for path in &diff.removed {
+        state.teardown_session(path).await;
+    }
+
+    state.broadcast_sessions_changed().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_sessions, SessionsDiff};
+    use crate::protocol::SessionInfo;
+    use std::collections::HashMap;
+
+    fn sessions(entries: &[(&str, &str)]) -> HashMap<String, SessionInfo> {
+        entries
+            .iter()
+            .map(|(path, name)| {
+                (
+                    path.to_string(),
+                    SessionInfo {
+                        path: path.to_string(),
+                        name: name.to_string(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn diff_sessions_detects_added_removed_and_renamed() {
+        let current = sessions(&[("/a", "a"), ("/b", "b"), ("/c", "c")]);
+        let loaded = sessions(&[("/a", "a"), ("/b", "bee"), ("/d", "d")]);
+
+        let diff = diff_sessions(&current, &loaded);
+        assert_eq!(
+            diff,
+            SessionsDiff {
+                added: vec!["/d".to_string()],
+                removed: vec!["/c".to_string()],
+                renamed: vec!["/b".to_string()],
+            }
+        );
+        assert!(diff_sessions(&current, &current).is_empty());
+    }
+}